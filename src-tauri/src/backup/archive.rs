@@ -0,0 +1,285 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::types::*;
+use super::{BackupError, BackupResult};
+use crate::db::pool::open_connection;
+use crate::db::{replace_database_file, schema, sibling_path, Database, DbError, DbState};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "email_budget.db";
+
+/// Snapshot the database into a zip archive at `archive_path`.
+///
+/// The archive holds `manifest.json` and the database file. An encrypted database
+/// stays encrypted inside the archive, so restoring it needs the same passphrase.
+pub fn create_backup(
+    database: &Database,
+    archive_path: &Path,
+    compress: bool,
+) -> BackupResult<BackupManifest> {
+    let snapshot_path = sibling_path(archive_path, "snapshot");
+    let partial_path = sibling_path(archive_path, "partial");
+
+    let result = write_archive(database, archive_path, &snapshot_path, &partial_path, compress);
+
+    let _ = std::fs::remove_file(&snapshot_path);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+    result
+}
+
+fn write_archive(
+    database: &Database,
+    archive_path: &Path,
+    snapshot_path: &Path,
+    partial_path: &Path,
+    compress: bool,
+) -> BackupResult<BackupManifest> {
+    if snapshot_path.exists() {
+        std::fs::remove_file(snapshot_path)?;
+    }
+    database.snapshot_to(snapshot_path)?;
+
+    let (schema_version, counts) = {
+        let conn = database.open_copy(snapshot_path)?;
+        (schema::get_schema_version(&conn)?, count_rows(&conn)?)
+    };
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        encrypted: database.is_encrypted(),
+        compressed: compress,
+        database_sha256: sha256_file(snapshot_path)?,
+        counts,
+    };
+
+    let options = SimpleFileOptions::default()
+        .compression_method(if compress {
+            CompressionMethod::Deflated
+        } else {
+            CompressionMethod::Stored
+        })
+        .large_file(true);
+
+    // Write next to the destination and rename, so a failed backup never leaves a
+    // truncated archive under the final name
+    let mut zip = ZipWriter::new(File::create(partial_path)?);
+    zip.start_file(MANIFEST_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.start_file(DATABASE_ENTRY, options)?;
+    std::io::copy(&mut File::open(snapshot_path)?, &mut zip)?;
+    zip.finish()?;
+
+    std::fs::rename(partial_path, archive_path)?;
+    Ok(manifest)
+}
+
+/// Read and validate the manifest of a backup archive without restoring it
+pub fn read_manifest(archive_path: &Path) -> BackupResult<BackupManifest> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    manifest_from_archive(&mut archive)
+}
+
+fn manifest_from_archive(archive: &mut ZipArchive<File>) -> BackupResult<BackupManifest> {
+    let mut contents = String::new();
+    archive
+        .by_name(MANIFEST_ENTRY)
+        .map_err(|_| BackupError::Invalid("missing manifest.json".to_string()))?
+        .read_to_string(&mut contents)?;
+
+    let manifest: BackupManifest = serde_json::from_str(&contents)?;
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err(BackupError::Invalid(format!(
+            "archive format v{} is newer than this app supports (v{}); please update the app",
+            manifest.format_version, BACKUP_FORMAT_VERSION
+        )));
+    }
+    if manifest.schema_version > schema::SCHEMA_VERSION {
+        return Err(DbError::SchemaTooNew {
+            found: manifest.schema_version,
+            supported: schema::SCHEMA_VERSION,
+        }
+        .into());
+    }
+
+    Ok(manifest)
+}
+
+/// Replace the open database with the one in `archive_path`.
+///
+/// The archived database is extracted next to the current one, checked against the
+/// manifest (checksum, integrity, schema version, row counts) and migrated to the
+/// current schema before anything is replaced. The current database is then copied
+/// to `rollback_path` and swapped out. `passphrase` is required if the backup is
+/// encrypted, and becomes the passphrase of the restored database.
+pub fn restore_backup(
+    state: &DbState,
+    archive_path: &Path,
+    passphrase: Option<&str>,
+) -> BackupResult<BackupManifest> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let manifest = manifest_from_archive(&mut archive)?;
+
+    let key = match (manifest.encrypted, passphrase) {
+        (true, Some(passphrase)) => Some(passphrase),
+        (true, None) => return Err(BackupError::PassphraseRequired),
+        (false, _) => None,
+    };
+
+    let db_path = state.get()?.path().to_path_buf();
+    let restore_path = sibling_path(&db_path, "restore");
+
+    if let Err(e) = extract_and_verify(&mut archive, &manifest, &restore_path, key) {
+        let _ = std::fs::remove_file(&restore_path);
+        return Err(e);
+    }
+
+    // Stop handing out connections so nothing is written after the rollback copy
+    let database = state.set(None).ok_or(DbError::Locked)?;
+    let rollback_path = rollback_path(&db_path);
+    if let Err(e) = save_rollback_copy(&database, &rollback_path) {
+        let _ = std::fs::remove_file(&restore_path);
+        state.set(Some(database));
+        return Err(e);
+    }
+
+    replace_database_file(state, database, &restore_path, key)?;
+    log::info!(
+        "Restored backup from {} (schema v{}), previous database saved to {:?}",
+        manifest.created_at,
+        manifest.schema_version,
+        rollback_path
+    );
+    Ok(manifest)
+}
+
+/// Where `restore_backup` keeps the database it replaced
+pub fn rollback_path(db_path: &Path) -> PathBuf {
+    let file_name = db_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "email_budget.db".to_string());
+    db_path.with_file_name(format!("{}.pre-restore.bak", file_name))
+}
+
+fn save_rollback_copy(database: &Database, rollback_path: &Path) -> BackupResult<()> {
+    if rollback_path.exists() {
+        std::fs::remove_file(rollback_path)?;
+    }
+    database.snapshot_to(rollback_path)?;
+    Ok(())
+}
+
+fn extract_and_verify(
+    archive: &mut ZipArchive<File>,
+    manifest: &BackupManifest,
+    target: &Path,
+    key: Option<&str>,
+) -> BackupResult<()> {
+    {
+        let mut entry = archive
+            .by_name(DATABASE_ENTRY)
+            .map_err(|_| BackupError::Invalid(format!("missing {}", DATABASE_ENTRY)))?;
+        std::io::copy(&mut entry, &mut File::create(target)?)?;
+    }
+
+    if sha256_file(target)? != manifest.database_sha256 {
+        return Err(BackupError::Invalid("database checksum does not match manifest".to_string()));
+    }
+
+    let conn = open_connection(target, key).map_err(|e| match e {
+        // Without a key an encrypted file reads as locked; with one it's the wrong key
+        DbError::Locked => BackupError::Invalid("database file is unreadable".to_string()),
+        e => e.into(),
+    })?;
+
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(BackupError::Invalid(format!("integrity check failed: {}", integrity)));
+    }
+
+    let schema_version = schema::get_schema_version(&conn)?;
+    if schema_version != manifest.schema_version {
+        return Err(BackupError::Invalid(format!(
+            "database is at schema v{} but manifest says v{}",
+            schema_version, manifest.schema_version
+        )));
+    }
+    if count_rows(&conn)? != manifest.counts {
+        return Err(BackupError::Invalid("row counts do not match manifest".to_string()));
+    }
+
+    // Older backups are brought up to date before they replace anything
+    schema::migrate_to(&conn, schema::SCHEMA_VERSION)?;
+    Ok(())
+}
+
+fn count_rows(conn: &Connection) -> BackupResult<BackupCounts> {
+    let count = |table: &str| -> BackupResult<i64> {
+        Ok(conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))?)
+    };
+
+    Ok(BackupCounts {
+        transactions: count("transactions")?,
+        transaction_items: count("transaction_items")?,
+        categories: count("categories")?,
+        budgets: count("budgets")?,
+        merchant_category_rules: count("merchant_category_rules")?,
+    })
+}
+
+fn sha256_file(path: &Path) -> BackupResult<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries;
+
+    #[test]
+    fn test_backup_and_restore_round_trip() {
+        let dir = std::env::temp_dir().join(format!("email_budget_backup_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("email_budget.db");
+        let archive_path = dir.join("backup.zip");
+
+        let database = Database::open(&db_path, None).unwrap();
+        schema::run_migrations(&database.writer().unwrap()).unwrap();
+        queries::create_category(&database.writer().unwrap(), "local", "Before", "tag", "#111", None)
+            .unwrap();
+
+        let manifest = create_backup(&database, &archive_path, true).unwrap();
+        assert_eq!(manifest.schema_version, schema::SCHEMA_VERSION);
+        assert_eq!(read_manifest(&archive_path).unwrap().counts, manifest.counts);
+
+        queries::create_category(&database.writer().unwrap(), "local", "After", "tag", "#222", None)
+            .unwrap();
+
+        let state = DbState::default();
+        state.set(Some(database));
+        restore_backup(&state, &archive_path, None).unwrap();
+
+        let categories = queries::get_all_categories(&state.get().unwrap().reader().unwrap(), "local")
+            .unwrap();
+        assert!(categories.iter().any(|c| c.name == "Before"));
+        assert!(!categories.iter().any(|c| c.name == "After"));
+        assert!(rollback_path(&db_path).exists());
+
+        drop(state);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
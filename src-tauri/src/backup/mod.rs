@@ -0,0 +1,46 @@
+pub mod types;
+pub mod archive;
+pub mod scheduler;
+
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+
+use crate::db::DbError;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Invalid manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("Invalid backup: {0}")]
+    Invalid(String),
+    /// The backup is encrypted and no passphrase was supplied
+    #[error("PASSPHRASE_REQUIRED")]
+    PassphraseRequired,
+}
+
+pub type BackupResult<T> = Result<T, BackupError>;
+
+/// Directory holding backups created without an explicit destination
+pub fn get_backups_dir(app_handle: &AppHandle) -> BackupResult<PathBuf> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|_| DbError::NotFound)?;
+
+    let dir = app_dir.join("backups");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+impl From<rusqlite::Error> for BackupError {
+    fn from(e: rusqlite::Error) -> Self {
+        BackupError::Db(e.into())
+    }
+}
@@ -0,0 +1,158 @@
+use std::path::Path;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use super::archive;
+use super::types::*;
+use super::{get_backups_dir, BackupResult};
+use crate::db::{self, queries, DbError};
+
+/// How often the scheduler checks whether a backup is due
+const CHECK_INTERVAL_SECS: u64 = 15 * 60;
+
+/// Automatic backups are named `auto-<timestamp>.zip`; only these are pruned
+const AUTO_BACKUP_PREFIX: &str = "auto-";
+
+const SCHEDULE_SETTING: &str = "backup_schedule";
+
+pub fn get_schedule(conn: &rusqlite::Connection) -> BackupResult<BackupSchedule> {
+    match queries::get_setting(conn, "local", SCHEDULE_SETTING)? {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(BackupSchedule::default()),
+    }
+}
+
+pub fn set_schedule(conn: &rusqlite::Connection, schedule: &BackupSchedule) -> BackupResult<()> {
+    if schedule.interval_hours == 0 || schedule.keep == 0 {
+        return Err(DbError::InvalidInput(
+            "Backup interval and retention must be at least 1".to_string(),
+        )
+        .into());
+    }
+    queries::set_setting(conn, "local", SCHEDULE_SETTING, &serde_json::to_string(schedule)?)?;
+    Ok(())
+}
+
+/// List the backups in `dir`, newest first. Files that aren't valid backups are skipped.
+pub fn list_backups(dir: &Path) -> BackupResult<Vec<BackupInfo>> {
+    let mut backups = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("zip") {
+            continue;
+        }
+
+        let manifest = match archive::read_manifest(&path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                log::warn!("Skipping unreadable backup {:?}: {}", path, e);
+                continue;
+            }
+        };
+
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        backups.push(BackupInfo {
+            path: path.to_string_lossy().to_string(),
+            is_automatic: file_name.starts_with(AUTO_BACKUP_PREFIX),
+            file_name,
+            file_size: std::fs::metadata(&path)?.len(),
+            manifest,
+        });
+    }
+
+    backups.sort_by(|a, b| b.manifest.created_at.cmp(&a.manifest.created_at));
+    Ok(backups)
+}
+
+/// Delete the automatic backups in `dir` that aren't encrypted, e.g. after the database
+/// is encrypted. Returns how many were removed.
+pub fn remove_unencrypted_backups(dir: &Path) -> BackupResult<usize> {
+    let mut removed = 0;
+    for backup in list_backups(dir)? {
+        if backup.is_automatic && !backup.manifest.encrypted {
+            std::fs::remove_file(&backup.path)?;
+            log::info!("Removed unencrypted automatic backup {}", backup.file_name);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Create an automatic backup if the schedule is enabled and one is due, then delete
+/// automatic backups beyond the retention count. Does nothing while the database is locked.
+pub fn run_scheduled_backup(app_handle: &AppHandle) -> BackupResult<Option<BackupInfo>> {
+    let database = match db::state(app_handle).get() {
+        Ok(database) => database,
+        Err(DbError::Locked) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let schedule = {
+        let conn = database.reader()?;
+        get_schedule(&conn)?
+    };
+    if !schedule.enabled {
+        return Ok(None);
+    }
+
+    let dir = get_backups_dir(app_handle)?;
+    let automatic: Vec<BackupInfo> = list_backups(&dir)?
+        .into_iter()
+        .filter(|b| b.is_automatic)
+        .collect();
+
+    let now = chrono::Utc::now();
+    let due = match automatic.first() {
+        Some(latest) => chrono::DateTime::parse_from_rfc3339(&latest.manifest.created_at)
+            .map(|created| {
+                now.signed_duration_since(created) >= chrono::Duration::hours(schedule.interval_hours as i64)
+            })
+            .unwrap_or(true),
+        None => true,
+    };
+    if !due {
+        return Ok(None);
+    }
+
+    let file_name = format!("{}{}.zip", AUTO_BACKUP_PREFIX, now.format("%Y%m%d-%H%M%S"));
+    let path = dir.join(&file_name);
+    let manifest = archive::create_backup(&database, &path, true)?;
+    log::info!("Automatic backup written to {:?}", path);
+
+    // The new backup plus the newest `keep - 1` existing ones survive
+    for old in automatic.iter().skip(schedule.keep.saturating_sub(1) as usize) {
+        match std::fs::remove_file(&old.path) {
+            Ok(()) => log::info!("Removed old automatic backup {}", old.file_name),
+            Err(e) => log::warn!("Failed to remove old backup {}: {}", old.file_name, e),
+        }
+    }
+
+    Ok(Some(BackupInfo {
+        path: path.to_string_lossy().to_string(),
+        file_name,
+        file_size: std::fs::metadata(&path)?.len(),
+        is_automatic: true,
+        manifest,
+    }))
+}
+
+/// Spawn the background task that runs scheduled backups. This runs for the lifetime of the app.
+pub fn spawn_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let app = app_handle.clone();
+            let result = tokio::task::spawn_blocking(move || run_scheduled_backup(&app)).await;
+            match result {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::error!("Scheduled backup failed: {}", e),
+                Err(e) => log::error!("Scheduled backup task panicked: {}", e),
+            }
+
+            tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/// Bumped when the archive layout changes incompatibly
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// `manifest.json` at the root of every backup archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub schema_version: i32,
+    /// RFC 3339 timestamp
+    pub created_at: String,
+    /// Whether the database inside is SQLCipher-encrypted
+    pub encrypted: bool,
+    pub compressed: bool,
+    /// SHA-256 of the database file inside the archive
+    pub database_sha256: String,
+    pub counts: BackupCounts,
+}
+
+/// Row counts recorded at backup time, checked again on restore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupCounts {
+    pub transactions: i64,
+    pub transaction_items: i64,
+    pub categories: i64,
+    pub budgets: i64,
+    pub merchant_category_rules: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub file_name: String,
+    pub file_size: u64,
+    /// Created by the backup schedule (and subject to its retention)
+    pub is_automatic: bool,
+    pub manifest: BackupManifest,
+}
+
+/// Automatic backup settings, stored in the `settings` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSchedule {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    /// Number of automatic backups to keep; older ones are deleted
+    pub keep: u32,
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use super::rule_engine::CategorizationInput;
+use crate::db::{queries, DbResult};
+use crate::parser::types::normalize_merchant;
+
+/// Predictions below this posterior probability aren't applied automatically
+pub const MIN_CONFIDENCE: f64 = 0.8;
+
+/// Categorized transactions needed before the classifier makes predictions
+const MIN_TRAINING_TRANSACTIONS: i64 = 10;
+
+/// Setting holding `training_fingerprint` as of the last training
+const TRAINED_ON_SETTING: &str = "classifier_trained_on";
+
+/// Category sources the classifier learns from. Provider defaults and the
+/// classifier's own guesses are left out so it doesn't reinforce itself.
+const TRAINING_SOURCES: &str =
+    "'manual', 'user_rule', 'merchant_default', 'previous_transaction', 'merchant_pattern'";
+
+/// A category predicted from the words of a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prediction {
+    pub category_id: String,
+    /// Posterior probability of the category, from 0.0 to 1.0
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrainingSummary {
+    pub transactions: i64,
+    pub categories: i64,
+    pub vocabulary: i64,
+}
+
+/// Tokens for the classifier: merchant and item words plus the provider, prefixed by
+/// where they came from so "apple" the merchant differs from "apple" the item
+pub fn tokenize(input: &CategorizationInput) -> Vec<String> {
+    fn words<'a>(text: &'a str, prefix: &'a str) -> impl Iterator<Item = String> + 'a {
+        text.split_whitespace()
+            .filter(|word| word.len() > 1 && !word.chars().all(|c| c.is_ascii_digit()))
+            .map(move |word| format!("{}:{}", prefix, word))
+    }
+
+    let mut tokens: Vec<String> = words(input.merchant_normalized, "m").collect();
+    for name in &input.item_names {
+        tokens.extend(words(&normalize_merchant(name), "i"));
+    }
+    if !input.provider.is_empty() {
+        tokens.push(format!("p:{}", input.provider.to_lowercase()));
+    }
+    tokens
+}
+
+/// Changes whenever a training transaction is added, removed or updated
+fn training_fingerprint(conn: &Connection, user_id: &str) -> DbResult<String> {
+    let sql = format!(
+        "SELECT COUNT(*), COALESCE(MAX(updated_at), '') FROM transactions
+         WHERE user_id = ?1 AND category_source IN ({})",
+        TRAINING_SOURCES
+    );
+    let (count, last_updated): (i64, String) =
+        conn.query_row(&sql, [user_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(format!("{}@{}", count, last_updated))
+}
+
+/// Retrain unless nothing the classifier learns from changed since the last training.
+/// Returns None when the model was already up to date.
+pub fn train_if_stale(conn: &Connection, user_id: &str) -> DbResult<Option<TrainingSummary>> {
+    let fingerprint = training_fingerprint(conn, user_id)?;
+    if queries::get_setting(conn, user_id, TRAINED_ON_SETTING)?.as_deref() == Some(fingerprint.as_str()) {
+        return Ok(None);
+    }
+    train(conn, user_id).map(Some)
+}
+
+/// Rebuild the token counts from the user's categorized transactions
+pub fn train(conn: &Connection, user_id: &str) -> DbResult<TrainingSummary> {
+    let fingerprint = training_fingerprint(conn, user_id)?;
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM classifier_tokens WHERE user_id = ?1", [user_id])?;
+    tx.execute("DELETE FROM classifier_categories WHERE user_id = ?1", [user_id])?;
+
+    let sql = format!(
+        "SELECT t.category_id, t.merchant_normalized, t.provider,
+                (SELECT group_concat(name, char(10)) FROM transaction_items WHERE transaction_id = t.id)
+         FROM transactions t
+         JOIN categories c ON c.id = t.category_id
+         WHERE t.user_id = ?1 AND t.category_source IN ({}) AND COALESCE(c.system_key, '') != 'Uncategorized'",
+        TRAINING_SOURCES
+    );
+    let mut stmt = tx.prepare(&sql)?;
+    let rows = stmt
+        .query_map([user_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut documents: HashMap<&str, i64> = HashMap::new();
+    let mut counts: HashMap<(&str, String), i64> = HashMap::new();
+    for (category_id, merchant_normalized, provider, items) in &rows {
+        let input = CategorizationInput {
+            merchant_normalized,
+            provider,
+            item_names: items.as_deref().map(|items| items.lines().collect()).unwrap_or_default(),
+            ..Default::default()
+        };
+        *documents.entry(category_id).or_default() += 1;
+        for token in tokenize(&input) {
+            *counts.entry((category_id, token)).or_default() += 1;
+        }
+    }
+
+    let mut token_totals: HashMap<&str, i64> = HashMap::new();
+    for ((category_id, token), count) in &counts {
+        tx.execute(
+            "INSERT INTO classifier_tokens (user_id, category_id, token, count) VALUES (?1, ?2, ?3, ?4)",
+            params![user_id, category_id, token, count],
+        )?;
+        *token_totals.entry(category_id).or_default() += count;
+    }
+    for (category_id, document_count) in &documents {
+        tx.execute(
+            "INSERT INTO classifier_categories (user_id, category_id, document_count, token_count)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![user_id, category_id, document_count, token_totals.get(category_id).unwrap_or(&0)],
+        )?;
+    }
+
+    let vocabulary: i64 = tx.query_row(
+        "SELECT COUNT(DISTINCT token) FROM classifier_tokens WHERE user_id = ?1",
+        [user_id],
+        |row| row.get(0),
+    )?;
+    queries::set_setting(&tx, user_id, TRAINED_ON_SETTING, &fingerprint)?;
+    tx.commit()?;
+
+    let summary = TrainingSummary {
+        transactions: rows.len() as i64,
+        categories: documents.len() as i64,
+        vocabulary,
+    };
+    log::info!(
+        "Trained classifier on {} transactions in {} categories",
+        summary.transactions,
+        summary.categories
+    );
+    Ok(summary)
+}
+
+/// The most likely category under a multinomial naive Bayes model with add-one
+/// smoothing. None until there's enough training data, or when none of the
+/// transaction's tokens have been seen before.
+pub fn classify(conn: &Connection, user_id: &str, input: &CategorizationInput) -> DbResult<Option<Prediction>> {
+    let tokens = tokenize(input);
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT cc.category_id, cc.document_count, cc.token_count
+         FROM classifier_categories cc
+         JOIN categories c ON c.id = cc.category_id
+         WHERE cc.user_id = ?1 AND c.is_hidden = 0",
+    )?;
+    let categories = stmt
+        .query_map([user_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let total_documents: i64 = categories.iter().map(|(_, documents, _)| documents).sum();
+    if categories.len() < 2 || total_documents < MIN_TRAINING_TRANSACTIONS {
+        return Ok(None);
+    }
+
+    let vocabulary: i64 = conn.query_row(
+        "SELECT COUNT(DISTINCT token) FROM classifier_tokens WHERE user_id = ?1",
+        [user_id],
+        |row| row.get(0),
+    )?;
+
+    let mut token_stmt = conn.prepare(
+        "SELECT category_id, count FROM classifier_tokens WHERE user_id = ?1 AND token = ?2",
+    )?;
+    let mut token_counts: Vec<HashMap<String, i64>> = Vec::with_capacity(tokens.len());
+    for token in &tokens {
+        let counts = token_stmt
+            .query_map(params![user_id, token], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        token_counts.push(counts);
+    }
+    if token_counts.iter().all(HashMap::is_empty) {
+        return Ok(None);
+    }
+
+    let log_scores: Vec<f64> = categories
+        .iter()
+        .map(|(category_id, documents, token_total)| {
+            let prior = (*documents as f64 / total_documents as f64).ln();
+            let denominator = (token_total + vocabulary) as f64;
+            token_counts.iter().fold(prior, |score, counts| {
+                let count = counts.get(category_id).copied().unwrap_or(0);
+                score + ((count + 1) as f64 / denominator).ln()
+            })
+        })
+        .collect();
+
+    // Normalize in log space to keep the exponentials in range
+    let max_score = log_scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let total: f64 = log_scores.iter().map(|score| (score - max_score).exp()).sum();
+    let best = log_scores
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(index, _)| index);
+
+    Ok(best.map(|index| Prediction {
+        category_id: categories[index].0.clone(),
+        confidence: 1.0 / total,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::NewTransaction;
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    #[test]
+    fn test_train_and_classify() {
+        let conn = open_seeded();
+        let travel = category(&conn, "Travel");
+        let groceries = category(&conn, "Groceries");
+
+        let examples = [
+            ("grand hotel paris", &travel),
+            ("hotel lumen", &travel),
+            ("seaside hotel and spa", &travel),
+            ("harbor inn hotel", &travel),
+            ("airport hotel suites", &travel),
+            ("green valley market", &groceries),
+            ("fresh market co", &groceries),
+            ("corner market", &groceries),
+            ("farmers market stand", &groceries),
+            ("village market", &groceries),
+        ];
+        for (i, (merchant, category_id)) in examples.iter().enumerate() {
+            insert(
+                &conn,
+                &NewTransaction {
+                    category_id: Some(category_id),
+                    category_source: Some("manual"),
+                    ..new_transaction(merchant, 5000, &i.to_string())
+                },
+            );
+        }
+
+        let summary = train(&conn, "local").unwrap();
+        assert_eq!(summary.transactions, 10);
+        assert_eq!(summary.categories, 2);
+
+        let input = |merchant| CategorizationInput {
+            merchant_normalized: merchant,
+            provider: "generic",
+            ..Default::default()
+        };
+        let prediction = classify(&conn, "local", &input("mountain hotel")).unwrap().unwrap();
+        assert_eq!(prediction.category_id, travel);
+        assert!(prediction.confidence > MIN_CONFIDENCE);
+
+        let prediction = classify(&conn, "local", &input("night market")).unwrap().unwrap();
+        assert_eq!(prediction.category_id, groceries);
+
+        // Only the provider is known, so the guess is a coin flip
+        let prediction = classify(&conn, "local", &input("zephyr")).unwrap().unwrap();
+        assert!(prediction.confidence < MIN_CONFIDENCE);
+        // Retrained only once the training data changes
+        assert!(train_if_stale(&conn, "local").unwrap().is_none());
+        let id = queries::get_transactions(&conn, "local", &Default::default()).unwrap()[0].id.clone();
+        queries::delete_transaction(&conn, &id).unwrap();
+        assert_eq!(train_if_stale(&conn, "local").unwrap().unwrap().transactions, 9);
+    }
+}
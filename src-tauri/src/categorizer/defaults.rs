@@ -1,144 +1,249 @@
 use std::collections::HashMap;
-use once_cell::sync::Lazy;
-
-/// Default category mappings for known providers
-pub static PROVIDER_CATEGORIES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert("amazon", "Shopping");
-    m.insert("doordash", "Food Delivery");
-    m.insert("uber_eats", "Food Delivery");
-    m.insert("uber", "Rideshare");
-    m.insert("venmo", "Peer Payment");
-    m
-});
 
-/// Default category mappings for common merchants
-pub static MERCHANT_PATTERNS: Lazy<Vec<(&'static str, &'static str)>> = Lazy::new(|| {
-    vec![
-        // Food Delivery
-        ("doordash", "Food Delivery"),
-        ("uber eats", "Food Delivery"),
-        ("grubhub", "Food Delivery"),
-        ("postmates", "Food Delivery"),
-        ("instacart", "Food Delivery"),
-
-        // Rideshare
-        ("uber", "Rideshare"),
-        ("lyft", "Rideshare"),
-
-        // Shopping
-        ("amazon", "Shopping"),
-        ("walmart", "Shopping"),
-        ("target", "Shopping"),
-        ("costco", "Shopping"),
-        ("best buy", "Shopping"),
-        ("home depot", "Shopping"),
-        ("lowes", "Shopping"),
-        ("ikea", "Shopping"),
-
-        // Food & Dining
-        ("starbucks", "Food & Dining"),
-        ("mcdonald", "Food & Dining"),
-        ("chipotle", "Food & Dining"),
-        ("subway", "Food & Dining"),
-        ("dunkin", "Food & Dining"),
-        ("restaurant", "Food & Dining"),
-        ("cafe", "Food & Dining"),
-        ("coffee", "Food & Dining"),
-        ("pizza", "Food & Dining"),
-        ("burger", "Food & Dining"),
-        ("taco", "Food & Dining"),
-        ("sushi", "Food & Dining"),
-
-        // Entertainment
-        ("netflix", "Subscriptions"),
-        ("spotify", "Subscriptions"),
-        ("hulu", "Subscriptions"),
-        ("disney", "Subscriptions"),
-        ("hbo", "Subscriptions"),
-        ("apple music", "Subscriptions"),
-        ("youtube premium", "Subscriptions"),
-        ("amc", "Entertainment"),
-        ("regal", "Entertainment"),
-        ("cinemark", "Entertainment"),
-        ("movie", "Entertainment"),
-        ("theater", "Entertainment"),
-        ("concert", "Entertainment"),
-        ("tickets", "Entertainment"),
-
-        // Utilities
-        ("electric", "Utilities"),
-        ("water", "Utilities"),
-        ("gas", "Utilities"),
-        ("internet", "Utilities"),
-        ("phone", "Utilities"),
-        ("verizon", "Utilities"),
-        ("att", "Utilities"),
-        ("t-mobile", "Utilities"),
-        ("comcast", "Utilities"),
-        ("spectrum", "Utilities"),
-
-        // Transportation
-        ("gas station", "Transportation"),
-        ("shell", "Transportation"),
-        ("chevron", "Transportation"),
-        ("exxon", "Transportation"),
-        ("bp", "Transportation"),
-        ("parking", "Transportation"),
-
-        // Healthcare
-        ("pharmacy", "Healthcare"),
-        ("cvs", "Healthcare"),
-        ("walgreens", "Healthcare"),
-        ("doctor", "Healthcare"),
-        ("hospital", "Healthcare"),
-        ("dental", "Healthcare"),
-        ("medical", "Healthcare"),
-
-        // Personal Care
-        ("salon", "Personal Care"),
-        ("barber", "Personal Care"),
-        ("spa", "Personal Care"),
-        ("gym", "Personal Care"),
-        ("fitness", "Personal Care"),
-
-        // Travel
-        ("airline", "Travel"),
-        ("hotel", "Travel"),
-        ("airbnb", "Travel"),
-        ("booking.com", "Travel"),
-        ("expedia", "Travel"),
-        ("marriott", "Travel"),
-        ("hilton", "Travel"),
-
-        // Education
-        ("university", "Education"),
-        ("college", "Education"),
-        ("school", "Education"),
-        ("tuition", "Education"),
-        ("textbook", "Education"),
-        ("coursera", "Education"),
-        ("udemy", "Education"),
-
-        // Peer Payments
-        ("venmo", "Peer Payment"),
-        ("paypal", "Peer Payment"),
-        ("zelle", "Peer Payment"),
-        ("cash app", "Peer Payment"),
-    ]
+use once_cell::sync::{Lazy, OnceCell};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager};
+
+use crate::db::queries::{find_category_by_name, get_merchant_pattern_overrides};
+use crate::db::DbResult;
+use crate::parser::types::normalize_merchant;
+
+/// Merchant knowledge base compiled into the binary, used when the resource copy
+/// can't be read. Bump `version` in the file whenever entries change.
+const KNOWLEDGE_BASE_JSON: &str = include_str!("merchant_kb.json");
+
+/// Name of the knowledge base in the app's resource directory
+const KNOWLEDGE_BASE_RESOURCE: &str = "merchant_kb.json";
+
+#[derive(Debug, Deserialize)]
+pub struct KnowledgeBase {
+    pub version: u32,
+    /// Provider ID -> category name
+    pub providers: HashMap<String, String>,
+    pub merchants: Vec<KnownMerchant>,
+    /// Generic words such as "pharmacy", used when no merchant matches
+    pub keywords: Vec<KnownKeyword>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KnownMerchant {
+    pub name: String,
+    pub category: String,
+    /// Other spellings seen on receipts and statements, e.g. "amzn mktp"
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KnownKeyword {
+    pub pattern: String,
+    pub category: String,
+}
+
+/// Summary of the bundled knowledge base
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeBaseInfo {
+    pub version: u32,
+    pub merchants: usize,
+    pub keywords: usize,
+}
+
+/// A normalized pattern from the knowledge base
+struct Pattern {
+    text: String,
+    /// `text` with a space on each side, for `contains_words`
+    padded: String,
+    category: &'static str,
+    /// Merchant name the pattern belongs to; None for keywords
+    merchant: Option<&'static str>,
+}
+
+static KNOWLEDGE_BASE: OnceCell<KnowledgeBase> = OnceCell::new();
+
+static PATTERNS: Lazy<Vec<Pattern>> = Lazy::new(|| {
+    let kb = knowledge_base();
+    let mut patterns = Vec::new();
+    for merchant in &kb.merchants {
+        for text in std::iter::once(&merchant.name).chain(&merchant.aliases) {
+            patterns.push(Pattern::new(text, &merchant.category, Some(&merchant.name)));
+        }
+    }
+    for keyword in &kb.keywords {
+        patterns.push(Pattern::new(&keyword.pattern, &keyword.category, None));
+    }
+    patterns
 });
 
+impl Pattern {
+    fn new(text: &str, category: &'static str, merchant: Option<&'static str>) -> Self {
+        let text = normalize_merchant(text);
+        Pattern {
+            padded: pad(&text),
+            text,
+            category,
+            merchant,
+        }
+    }
+}
+
+fn compiled_in_knowledge_base() -> KnowledgeBase {
+    serde_json::from_str(KNOWLEDGE_BASE_JSON).expect("bundled merchant knowledge base is invalid")
+}
+
+/// The knowledge base in use, falling back to the compiled-in copy if
+/// `load_knowledge_base` hasn't run
+pub fn knowledge_base() -> &'static KnowledgeBase {
+    KNOWLEDGE_BASE.get_or_init(compiled_in_knowledge_base)
+}
+
+/// Load the knowledge base shipped in the app's resources so it can be updated
+/// without a rebuild. Falls back to the compiled-in copy when the resource is
+/// missing or invalid. Call at startup, before anything categorizes.
+pub fn load_knowledge_base(app_handle: &AppHandle) -> &'static KnowledgeBase {
+    let from_resource = app_handle
+        .path()
+        .resolve(KNOWLEDGE_BASE_RESOURCE, BaseDirectory::Resource)
+        .map_err(|e| e.to_string())
+        .and_then(|path| std::fs::read_to_string(path).map_err(|e| e.to_string()))
+        .and_then(|json| serde_json::from_str::<KnowledgeBase>(&json).map_err(|e| e.to_string()));
+
+    let kb = match from_resource {
+        Ok(kb) => kb,
+        Err(e) => {
+            log::warn!("Using the built-in merchant knowledge base: {}", e);
+            compiled_in_knowledge_base()
+        }
+    };
+    if KNOWLEDGE_BASE.set(kb).is_err() {
+        log::warn!("Merchant knowledge base was already in use before it was loaded");
+    }
+    knowledge_base()
+}
+
+/// Version and size of the knowledge base in use
+pub fn knowledge_base_info() -> KnowledgeBaseInfo {
+    let kb = knowledge_base();
+    KnowledgeBaseInfo {
+        version: kb.version,
+        merchants: kb.merchants.len(),
+        keywords: kb.keywords.len(),
+    }
+}
+
 /// Get the default category for a provider
 pub fn get_provider_category(provider: &str) -> Option<&'static str> {
-    PROVIDER_CATEGORIES.get(provider).copied()
+    knowledge_base().providers.get(provider).map(String::as_str)
+}
+
+/// `text` with a space on each side, so whole words can be found with `contains`
+fn pad(text: &str) -> String {
+    format!(" {} ", text)
+}
+
+/// Whether `padded_pattern` occurs in `padded_merchant` as a run of whole words, so
+/// "spa" doesn't match "spanish" and "att" doesn't match "matt". Both are `pad`ded.
+fn contains_words(padded_merchant: &str, padded_pattern: &str) -> bool {
+    !padded_pattern.trim().is_empty() && padded_merchant.contains(padded_pattern)
+}
+
+/// The knowledge base entry for a merchant, skipping patterns `allowed` rejects.
+/// Named merchants win over keywords, and the longest matching pattern wins within each.
+fn best_pattern(merchant_normalized: &str, allowed: impl Fn(&str) -> bool) -> Option<&'static Pattern> {
+    let patterns: &'static [Pattern] = &PATTERNS;
+    let padded_merchant = pad(merchant_normalized);
+    patterns
+        .iter()
+        .filter(|pattern| allowed(&pattern.text) && contains_words(&padded_merchant, &pattern.padded))
+        .max_by_key(|pattern| (pattern.merchant.is_some(), pattern.text.len()))
 }
 
-/// Get the default category based on merchant name patterns
-pub fn get_merchant_category(merchant_normalized: &str) -> Option<&'static str> {
-    for (pattern, category) in MERCHANT_PATTERNS.iter() {
-        if merchant_normalized.contains(pattern) {
-            return Some(category);
+/// A category from the knowledge base or the user's overrides of it
+#[derive(Debug, Clone)]
+pub struct DefaultCategoryMatch {
+    pub category_id: String,
+    pub reason: String,
+}
+
+/// Match the merchant against the knowledge base with the user's overrides layered on
+/// top. Any matching override wins, longest first; an override without a category
+/// disables the bundled entry with the same pattern.
+pub fn find_default_category(
+    conn: &Connection,
+    user_id: &str,
+    merchant_normalized: &str,
+) -> DbResult<Option<DefaultCategoryMatch>> {
+    let overrides = get_merchant_pattern_overrides(conn, user_id)?;
+
+    let padded_merchant = pad(merchant_normalized);
+    let user_match = overrides
+        .iter()
+        .filter_map(|o| Some((o.pattern.as_str(), o.category_id.as_ref()?)))
+        .filter(|(pattern, _)| contains_words(&padded_merchant, &pad(pattern)))
+        .max_by_key(|(pattern, _)| pattern.len());
+    if let Some((pattern, category_id)) = user_match {
+        return Ok(Some(DefaultCategoryMatch {
+            category_id: category_id.clone(),
+            reason: format!("Matches your merchant default \"{}\"", pattern),
+        }));
+    }
+
+    let bundled_match = best_pattern(merchant_normalized, |text| !overrides.iter().any(|o| o.pattern == text));
+    let Some(pattern) = bundled_match else {
+        return Ok(None);
+    };
+    let Some(category_id) = find_category_by_name(conn, user_id, pattern.category)? else {
+        return Ok(None);
+    };
+    let reason = match pattern.merchant {
+        Some(name) => format!("Known merchant {}", name),
+        None => format!("Merchant name contains \"{}\"", pattern.text),
+    };
+
+    Ok(Some(DefaultCategoryMatch { category_id, reason }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries;
+    use crate::db::test_support::{category, open_seeded};
+
+    #[test]
+    fn test_knowledge_base_matching() {
+        let conn = open_seeded();
+
+        // Every category the knowledge base uses is seeded
+        let kb = knowledge_base();
+        let categories = kb
+            .providers
+            .values()
+            .chain(kb.merchants.iter().map(|m| &m.category))
+            .chain(kb.keywords.iter().map(|k| &k.category));
+        for category in categories {
+            assert!(
+                find_category_by_name(&conn, "local", category).unwrap().is_some(),
+                "unknown category {}",
+                category
+            );
         }
+
+        let category_of = |merchant: &str| best_pattern(merchant, |_| true).map(|pattern| pattern.category);
+        assert_eq!(category_of("uber eats order"), Some("Food Delivery"));
+        assert_eq!(category_of("uber trip"), Some("Rideshare"));
+        assert_eq!(category_of("costco gas 123"), Some("Transportation"));
+        assert_eq!(category_of("spanish tapas bistro"), Some("Food & Dining"));
+        assert_eq!(category_of("matts place"), None);
+        assert_eq!(category_of("starbucks coffee"), Some("Food & Dining"));
+
+        let groceries = category(&conn, "Groceries");
+        queries::set_merchant_pattern_override(&conn, "local", "Costco", Some(&groceries)).unwrap();
+        let found = find_default_category(&conn, "local", "costco whse 0042").unwrap().unwrap();
+        assert_eq!(found.category_id, groceries);
+
+        queries::set_merchant_pattern_override(&conn, "local", "starbucks", None).unwrap();
+        let found = find_default_category(&conn, "local", "starbucks coffee").unwrap().unwrap();
+        assert_eq!(found.reason, "Merchant name contains \"coffee\"");
     }
-    None
 }
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::classifier::{classify, MIN_CONFIDENCE};
+use super::rule_engine::{describe_rule, load_enabled_rules, CategorizationInput};
+use super::rules::CategorySource;
+use crate::db::queries::{self, TransactionSplit};
+use crate::db::{DbError, DbResult};
+
+/// The category of a single item on a receipt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemCategorization {
+    pub category_id: String,
+    pub source: CategorySource,
+    pub reason: String,
+}
+
+/// An item to categorize: its name and total price in cents
+pub struct ItemInput<'a> {
+    pub name: &'a str,
+    pub total_price: i64,
+}
+
+/// Categorize each item of a transaction on its own:
+/// 1. User rules with an item keyword, checked against the item alone with its
+///    total as the amount
+/// 2. Naive Bayes classifier on the item's words, when confident enough
+///
+/// Items neither tier recognizes get None and follow the transaction's category.
+pub fn categorize_items(
+    conn: &Connection,
+    user_id: &str,
+    transaction: &CategorizationInput,
+    items: &[ItemInput],
+) -> DbResult<Vec<Option<ItemCategorization>>> {
+    let item_rules: Vec<_> = load_enabled_rules(conn, user_id)?
+        .into_iter()
+        .filter(|rule| rule.rule.item_keyword.is_some())
+        .collect();
+
+    let mut categorizations = Vec::with_capacity(items.len());
+    for item in items {
+        let input = CategorizationInput {
+            amount: item.total_price,
+            item_names: vec![item.name],
+            ..transaction.clone()
+        };
+        if let Some(rule) = item_rules.iter().find(|rule| rule.matches(&input)).map(|rule| &rule.rule) {
+            categorizations.push(Some(ItemCategorization {
+                category_id: rule.category_id.clone(),
+                source: CategorySource::UserRule,
+                reason: format!("Matches your rule {}", describe_rule(rule)),
+            }));
+            continue;
+        }
+
+        // Only the item's own words, so the merchant doesn't pull every item its way
+        let words_only = CategorizationInput {
+            item_names: vec![item.name],
+            ..Default::default()
+        };
+        let prediction = classify(conn, user_id, &words_only)?.filter(|p| p.confidence >= MIN_CONFIDENCE);
+        categorizations.push(prediction.map(|prediction| ItemCategorization {
+            reason: format!("Similar items you categorized ({:.0}% confident)", prediction.confidence * 100.0),
+            category_id: prediction.category_id,
+            source: CategorySource::Classifier,
+        }));
+    }
+
+    Ok(categorizations)
+}
+
+/// How to split `amount` between the categories of its items, given each item's own
+/// category (None for the transaction's) and total. Items in the transaction's own
+/// category share its group. Tax, fees and discounts not covered by item totals go to
+/// the largest group, so the splits add up to `amount`. Empty when every item lands in
+/// one category.
+pub fn split_by_item_category(
+    transaction_category_id: Option<&str>,
+    amount: i64,
+    items: &[(Option<&str>, i64)],
+) -> Vec<(Option<String>, i64)> {
+    let mut totals: Vec<(Option<String>, i64)> = Vec::new();
+    let mut index: HashMap<Option<&str>, usize> = HashMap::new();
+    for (category_id, total_price) in items {
+        let group = category_id.filter(|id| Some(*id) != transaction_category_id);
+        let position = *index.entry(group).or_insert_with(|| {
+            totals.push((group.map(str::to_string), 0));
+            totals.len() - 1
+        });
+        totals[position].1 += total_price;
+    }
+    if totals.len() < 2 {
+        return Vec::new();
+    }
+
+    let remainder = amount - totals.iter().map(|(_, total)| total).sum::<i64>();
+    if let Some(largest) = totals.iter_mut().max_by_key(|(_, total)| *total) {
+        largest.1 += remainder;
+    }
+    totals
+}
+
+/// The fields of a stored transaction item categorization looks at
+struct StoredTransaction {
+    merchant_normalized: String,
+    provider: String,
+    amount: i64,
+    transaction_date: String,
+    sender: Option<String>,
+    category_id: Option<String>,
+}
+
+/// Categorize a stored transaction's items and rebuild its splits, in one
+/// database transaction
+pub fn categorize_transaction_items(
+    conn: &Connection,
+    user_id: &str,
+    transaction_id: &str,
+) -> DbResult<Vec<TransactionSplit>> {
+    let tx = conn.unchecked_transaction()?;
+    apply_item_categories(&tx, user_id, transaction_id)?;
+    tx.commit()?;
+    queries::get_transaction_splits(conn, transaction_id)
+}
+
+/// `categorize_transaction_items` for callers already inside a database transaction
+pub fn apply_item_categories(conn: &Connection, user_id: &str, transaction_id: &str) -> DbResult<()> {
+    let transaction = conn
+        .query_row(
+            "SELECT merchant_normalized, provider, amount, transaction_date, sender, category_id
+             FROM transactions WHERE user_id = ?1 AND id = ?2",
+            params![user_id, transaction_id],
+            |row| {
+                Ok(StoredTransaction {
+                    merchant_normalized: row.get(0)?,
+                    provider: row.get(1)?,
+                    amount: row.get(2)?,
+                    transaction_date: row.get(3)?,
+                    sender: row.get(4)?,
+                    category_id: row.get(5)?,
+                })
+            },
+        )
+        .optional()?
+        .ok_or(DbError::NotFound)?;
+
+    let items = queries::get_transaction_items(conn, transaction_id)?;
+    let input = CategorizationInput {
+        merchant_normalized: &transaction.merchant_normalized,
+        provider: &transaction.provider,
+        amount: transaction.amount,
+        transaction_date: &transaction.transaction_date,
+        sender: transaction.sender.as_deref(),
+        transaction_id: Some(transaction_id),
+        ..Default::default()
+    };
+    let item_inputs: Vec<ItemInput> = items
+        .iter()
+        .map(|item| ItemInput {
+            name: &item.name,
+            total_price: item.total_price,
+        })
+        .collect();
+    let categorizations = categorize_items(conn, user_id, &input, &item_inputs)?;
+
+    for (item, categorization) in items.iter().zip(&categorizations) {
+        queries::update_transaction_item_category(
+            conn,
+            &item.id,
+            categorization.as_ref().map(|c| c.category_id.as_str()),
+            categorization.as_ref().map(|c| c.source.as_str()),
+            categorization.as_ref().map(|c| c.reason.as_str()),
+        )?;
+    }
+
+    let item_categories: Vec<(Option<&str>, i64)> = items
+        .iter()
+        .zip(&categorizations)
+        .map(|(item, categorization)| {
+            (categorization.as_ref().map(|c| c.category_id.as_str()), item.total_price)
+        })
+        .collect();
+    let splits = split_by_item_category(transaction.category_id.as_deref(), transaction.amount, &item_categories);
+    if !splits.is_empty() {
+        log::debug!("Splitting transaction {} across {} categories", transaction_id, splits.len());
+    }
+    queries::replace_transaction_splits(conn, transaction_id, &splits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{NewTransaction, RuleInput};
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    #[test]
+    fn test_split_mixed_order() {
+        let conn = open_seeded();
+        let shopping = category(&conn, "Shopping");
+        let groceries = category(&conn, "Groceries");
+
+        let mut bananas = RuleInput::for_merchant("amazon", "contains", &groceries);
+        bananas.item_keyword = Some("banana".to_string());
+        queries::create_category_rule(&conn, "local", &bananas).unwrap();
+
+        let id = insert(
+            &conn,
+            &NewTransaction {
+                category_id: Some(&shopping),
+                category_source: Some("merchant_pattern"),
+                merchant: "Amazon",
+                provider: "amazon",
+                ..new_transaction("amazon", 2200, "a")
+            },
+        );
+        queries::insert_transaction_item(&conn, &id, "USB-C Cable", 1, 1500, 1500).unwrap();
+        queries::insert_transaction_item(&conn, &id, "Organic Bananas", 1, 500, 500).unwrap();
+
+        let splits = categorize_transaction_items(&conn, "local", &id).unwrap();
+        let amounts: Vec<(Option<&str>, i64)> =
+            splits.iter().map(|s| (s.category_id.as_deref(), s.amount)).collect();
+        assert_eq!(amounts, vec![(Some(shopping.as_str()), 1700), (Some(groceries.as_str()), 500)]);
+
+        let items = queries::get_transaction_items(&conn, &id).unwrap();
+        assert_eq!(items[0].category_id, None);
+        assert_eq!(items[1].category_id.as_deref(), Some(groceries.as_str()));
+
+        let spending = queries::get_category_spending(&conn, "local", "2024-03-01", "2024-03-31").unwrap();
+        let total_of = |id: &str| spending.iter().find(|s| s.category_id == id).map(|s| s.total);
+        assert_eq!(total_of(&shopping), Some(1700));
+        // Groceries rolls up into Food & Dining
+        assert_eq!(total_of(&category(&conn, "Food & Dining")), Some(500));
+
+        // A single-category order isn't split
+        let rule_id = queries::get_merchant_category_rules(&conn, "local").unwrap()[0].id.clone();
+        queries::delete_category_rule(&conn, "local", &rule_id).unwrap();
+        assert!(categorize_transaction_items(&conn, "local", &id).unwrap().is_empty());
+    }
+}
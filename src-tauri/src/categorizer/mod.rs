@@ -1,4 +1,9 @@
+pub mod classifier;
 pub mod defaults;
+pub mod items;
+pub mod recategorize;
+pub mod rule_engine;
 pub mod rules;
 
-pub use rules::categorize_transaction;
+pub use rule_engine::CategorizationInput;
+pub use rules::{categorize_transaction, learn_from_assignment, predict_category, CategoryPrediction, CategorySource};
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::rule_engine::{load_enabled_rules, CategorizationInput};
+use super::rules::{categorize_transaction_with_rules, CategorySource};
+use crate::db::queries::{self, append_transaction_filters, TransactionFilters};
+use crate::db::DbResult;
+
+/// A transaction whose category would change if categorized again with the current rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecategorizationChange {
+    pub transaction_id: String,
+    pub merchant: String,
+    pub transaction_date: String,
+    pub amount: i64,
+    pub old_category_id: Option<String>,
+    pub old_category_name: Option<String>,
+    pub old_source: Option<String>,
+    pub new_category_id: Option<String>,
+    pub new_category_name: Option<String>,
+    pub new_source: CategorySource,
+    pub new_reason: String,
+    pub new_rule_id: Option<String>,
+}
+
+/// Transactions as loaded for re-categorization
+struct Candidate {
+    id: String,
+    merchant: String,
+    merchant_normalized: String,
+    amount: i64,
+    transaction_date: String,
+    provider: String,
+    sender: Option<String>,
+    category_id: Option<String>,
+    category_name: Option<String>,
+    category_source: Option<String>,
+}
+
+/// Categorize the filtered transactions again and list those whose category would change.
+/// Manually categorized transactions are left alone unless `include_manual` is set.
+pub fn preview_recategorization(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+    include_manual: bool,
+) -> DbResult<Vec<RecategorizationChange>> {
+    let mut sql = String::from(
+        "SELECT t.id, t.merchant, t.merchant_normalized, t.amount, t.transaction_date, t.provider,
+                t.sender, t.category_id, c.name, t.category_source
+         FROM transactions t
+         LEFT JOIN categories c ON t.category_id = c.id
+         WHERE t.user_id = ?",
+    );
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(user_id.to_string())];
+    append_transaction_filters(&mut sql, &mut params, filters);
+    if !include_manual {
+        sql.push_str(" AND t.category_source IS NOT 'manual'");
+    }
+    sql.push_str(" ORDER BY t.transaction_date DESC, t.created_at DESC");
+
+    let mut stmt = conn.prepare(&sql)?;
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let candidates = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(Candidate {
+                id: row.get(0)?,
+                merchant: row.get(1)?,
+                merchant_normalized: row.get(2)?,
+                amount: row.get(3)?,
+                transaction_date: row.get(4)?,
+                provider: row.get(5)?,
+                sender: row.get(6)?,
+                category_id: row.get(7)?,
+                category_name: row.get(8)?,
+                category_source: row.get(9)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let rules = load_enabled_rules(conn, user_id)?;
+    let mut changes = Vec::new();
+    for candidate in candidates {
+        let items = queries::get_transaction_items(conn, &candidate.id)?;
+        let input = CategorizationInput {
+            merchant_normalized: &candidate.merchant_normalized,
+            provider: &candidate.provider,
+            amount: candidate.amount,
+            transaction_date: &candidate.transaction_date,
+            item_names: items.iter().map(|item| item.name.as_str()).collect(),
+            sender: candidate.sender.as_deref(),
+            transaction_id: Some(&candidate.id),
+        };
+        let categorization = categorize_transaction_with_rules(conn, user_id, &input, &rules)?;
+        if categorization.category_id == candidate.category_id {
+            continue;
+        }
+
+        let new_category_name = match categorization.category_id {
+            Some(ref id) => queries::get_category_by_id(conn, id)?.map(|c| c.name),
+            None => None,
+        };
+        changes.push(RecategorizationChange {
+            transaction_id: candidate.id,
+            merchant: candidate.merchant,
+            transaction_date: candidate.transaction_date,
+            amount: candidate.amount,
+            old_category_id: candidate.category_id,
+            old_category_name: candidate.category_name,
+            old_source: candidate.category_source,
+            new_category_id: categorization.category_id,
+            new_category_name,
+            new_source: categorization.source,
+            new_reason: categorization.reason,
+            new_rule_id: categorization.rule_id,
+        });
+    }
+
+    Ok(changes)
+}
+
+/// Re-categorize the filtered transactions in one database transaction. With
+/// `transaction_ids`, only those changes from the preview are applied.
+pub fn apply_recategorization(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+    include_manual: bool,
+    transaction_ids: Option<&[String]>,
+) -> DbResult<Vec<RecategorizationChange>> {
+    let tx = conn.unchecked_transaction()?;
+
+    let selected: Option<HashSet<&str>> = transaction_ids.map(|ids| ids.iter().map(String::as_str).collect());
+    let changes: Vec<RecategorizationChange> = preview_recategorization(&tx, user_id, filters, include_manual)?
+        .into_iter()
+        .filter(|change| {
+            selected
+                .as_ref()
+                .map_or(true, |ids| ids.contains(change.transaction_id.as_str()))
+        })
+        .collect();
+
+    for change in &changes {
+        queries::update_transaction_category(
+            &tx,
+            &change.transaction_id,
+            change.new_category_id.as_deref(),
+            change.new_source.as_str(),
+            &change.new_reason,
+            change.new_rule_id.as_deref(),
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{NewTransaction, RuleInput};
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    #[test]
+    fn test_recategorize_with_new_rule() {
+        let conn = open_seeded();
+        let shopping = category(&conn, "Shopping");
+        let travel = category(&conn, "Travel");
+
+        let mut ids = Vec::new();
+        for (hash, source) in [("a", "provider_default"), ("b", "provider_default"), ("c", "manual")] {
+            ids.push(insert(
+                &conn,
+                &NewTransaction {
+                    category_id: Some(&shopping),
+                    category_source: Some(source),
+                    merchant: "Hotel Lumen",
+                    ..new_transaction("hotel lumen", 20000, hash)
+                },
+            ));
+        }
+
+        queries::create_category_rule(
+            &conn,
+            "local",
+            &RuleInput::for_merchant("hotel", "contains", &travel),
+        )
+        .unwrap();
+
+        let filters = TransactionFilters::default();
+        let preview = preview_recategorization(&conn, "local", &filters, false).unwrap();
+        assert_eq!(preview.len(), 2);
+        assert!(preview.iter().all(|c| c.new_category_id.as_deref() == Some(travel.as_str())));
+        assert_eq!(preview[0].new_source, CategorySource::UserRule);
+
+        let applied = apply_recategorization(&conn, "local", &filters, false, Some(&ids[..1])).unwrap();
+        assert_eq!(applied.len(), 1);
+        let category_of = |id: &str| -> String {
+            conn.query_row("SELECT category_id FROM transactions WHERE id = ?1", [id], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(category_of(&ids[0]), travel);
+        assert_eq!(category_of(&ids[1]), shopping);
+        assert_eq!(category_of(&ids[2]), shopping);
+
+        assert_eq!(preview_recategorization(&conn, "local", &filters, true).unwrap().len(), 2);
+    }
+}
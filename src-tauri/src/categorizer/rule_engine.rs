@@ -0,0 +1,424 @@
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db::queries::{
+    self, get_merchant_category_rules, MerchantCategoryRule, RuleInput, TransactionWithCategory,
+};
+use crate::db::DbResult;
+use crate::parser::types::{format_cents, ParsedTransaction};
+
+/// What rules can look at when categorizing a transaction
+#[derive(Debug, Clone, Default)]
+pub struct CategorizationInput<'a> {
+    pub merchant_normalized: &'a str,
+    pub provider: &'a str,
+    /// Cents
+    pub amount: i64,
+    /// YYYY-MM-DD; rules with weekday conditions don't match if this doesn't parse
+    pub transaction_date: &'a str,
+    pub item_names: Vec<&'a str>,
+    /// Sender address of the receipt email
+    pub sender: Option<&'a str>,
+    /// Set when re-categorizing a stored transaction, so it isn't matched against itself
+    pub transaction_id: Option<&'a str>,
+}
+
+impl<'a> CategorizationInput<'a> {
+    pub fn from_parsed(transaction: &'a ParsedTransaction, merchant_normalized: &'a str) -> Self {
+        Self {
+            merchant_normalized,
+            provider: &transaction.provider,
+            amount: transaction.amount,
+            transaction_date: &transaction.transaction_date,
+            item_names: transaction.items.iter().map(|item| item.name.as_str()).collect(),
+            sender: transaction.sender.as_deref(),
+            transaction_id: None,
+        }
+    }
+}
+
+/// A rule with its conditions prepared once, for matching many transactions
+pub struct CompiledRule {
+    pub rule: MerchantCategoryRule,
+    merchant: Option<MerchantMatcher>,
+    /// Lowercased item keyword and sender
+    item_keyword: Option<String>,
+    sender: Option<String>,
+}
+
+/// A merchant pattern, lowercased or compiled according to its match type
+enum MerchantMatcher {
+    Exact(String),
+    Contains(String),
+    /// A glob or regex; None if it doesn't compile, which matches nothing
+    Regex(Option<Regex>),
+}
+
+impl MerchantMatcher {
+    fn new(pattern: &str, match_type: &str) -> Self {
+        match match_type {
+            "exact" => MerchantMatcher::Exact(pattern.to_lowercase()),
+            "glob" => MerchantMatcher::Regex(glob_to_regex(&pattern.to_lowercase())),
+            "regex" => MerchantMatcher::Regex(Regex::new(&format!("(?i){}", pattern)).ok()),
+            _ => MerchantMatcher::Contains(pattern.to_lowercase()),
+        }
+    }
+
+    fn matches(&self, merchant_normalized: &str) -> bool {
+        let merchant = merchant_normalized.to_lowercase();
+        match self {
+            MerchantMatcher::Exact(pattern) => merchant == *pattern,
+            MerchantMatcher::Contains(pattern) => merchant.contains(pattern.as_str()),
+            MerchantMatcher::Regex(re) => re.as_ref().is_some_and(|re| re.is_match(&merchant)),
+        }
+    }
+}
+
+impl CompiledRule {
+    pub fn new(rule: MerchantCategoryRule) -> Self {
+        Self {
+            merchant: rule
+                .merchant_pattern
+                .as_deref()
+                .map(|pattern| MerchantMatcher::new(pattern, &rule.match_type)),
+            item_keyword: rule.item_keyword.as_deref().map(str::to_lowercase),
+            sender: rule.sender.as_deref().map(str::to_lowercase),
+            rule,
+        }
+    }
+
+    /// Whether every condition the rule sets holds for `input`
+    pub fn matches(&self, input: &CategorizationInput) -> bool {
+        let rule = &self.rule;
+        if let Some(ref merchant) = self.merchant {
+            if !merchant.matches(input.merchant_normalized) {
+                return false;
+            }
+        }
+
+        if let Some(ref provider) = rule.provider {
+            if !provider.eq_ignore_ascii_case(input.provider) {
+                return false;
+            }
+        }
+
+        if rule.min_amount.is_some_and(|min| input.amount < min)
+            || rule.max_amount.is_some_and(|max| input.amount >= max)
+        {
+            return false;
+        }
+
+        if let Some(ref days) = rule.days_of_week {
+            let Ok(date) = NaiveDate::parse_from_str(input.transaction_date, "%Y-%m-%d") else {
+                return false;
+            };
+            let weekday = date.weekday().number_from_monday().to_string();
+            if !days.split(',').any(|d| d.trim() == weekday) {
+                return false;
+            }
+        }
+
+        if let Some(ref keyword) = self.item_keyword {
+            if !input.item_names.iter().any(|name| name.to_lowercase().contains(keyword.as_str())) {
+                return false;
+            }
+        }
+
+        if let Some(ref sender) = self.sender {
+            if !input.sender.is_some_and(|s| s.to_lowercase().contains(sender.as_str())) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// The user's enabled rules in priority order, compiled. Load them once when
+/// categorizing many transactions.
+pub fn load_enabled_rules(conn: &Connection, user_id: &str) -> DbResult<Vec<CompiledRule>> {
+    Ok(get_merchant_category_rules(conn, user_id)?
+        .into_iter()
+        .filter(|rule| rule.enabled)
+        .map(CompiledRule::new)
+        .collect())
+}
+
+/// The first rule, by priority, whose conditions all match
+pub fn find_matching_rule<'a>(
+    rules: &'a [CompiledRule],
+    input: &CategorizationInput,
+) -> Option<&'a MerchantCategoryRule> {
+    rules.iter().find(|rule| rule.matches(input)).map(|rule| &rule.rule)
+}
+
+/// Past transactions a rule would match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleMatchPreview {
+    pub match_count: usize,
+    /// The most recent matches, up to the requested limit
+    pub transactions: Vec<TransactionWithCategory>,
+}
+
+/// Run a rule, saved or not, against every stored transaction. Rule order is ignored,
+/// so matches an earlier rule would take are included.
+pub fn preview_rule_matches(
+    conn: &Connection,
+    user_id: &str,
+    input: &RuleInput,
+    limit: usize,
+) -> DbResult<RuleMatchPreview> {
+    let input = queries::normalize_rule_input(conn, user_id, input)?;
+    let rule = CompiledRule::new(MerchantCategoryRule {
+        id: String::new(),
+        name: input.name,
+        is_exact_match: input.match_type == "exact",
+        merchant_pattern: input.merchant_pattern,
+        match_type: input.match_type,
+        provider: input.provider,
+        min_amount: input.min_amount,
+        max_amount: input.max_amount,
+        days_of_week: input.days_of_week,
+        item_keyword: input.item_keyword,
+        sender: input.sender,
+        category_id: input.category_id,
+        priority: 0,
+        enabled: true,
+        created_at: String::new(),
+        updated_at: String::new(),
+    });
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.merchant_normalized, t.provider, t.amount, t.transaction_date, t.sender,
+                (SELECT group_concat(name, char(10)) FROM transaction_items WHERE transaction_id = t.id)
+         FROM transactions t
+         WHERE t.user_id = ?1
+         ORDER BY t.transaction_date DESC, t.created_at DESC",
+    )?;
+    let rows = stmt
+        .query_map([user_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let matched: Vec<&str> = rows
+        .iter()
+        .filter(|(_, merchant_normalized, provider, amount, transaction_date, sender, items)| {
+            let candidate = CategorizationInput {
+                merchant_normalized,
+                provider,
+                amount: *amount,
+                transaction_date,
+                item_names: items.as_deref().map(|items| items.lines().collect()).unwrap_or_default(),
+                sender: sender.as_deref(),
+                transaction_id: None,
+            };
+            rule.matches(&candidate)
+        })
+        .map(|(id, ..)| id.as_str())
+        .collect();
+
+    let shown = &matched[..matched.len().min(limit)];
+    let transactions = if shown.is_empty() {
+        Vec::new()
+    } else {
+        let sql = format!(
+            "SELECT {} FROM transactions t
+             LEFT JOIN categories c ON t.category_id = c.id
+             WHERE t.id IN ({})
+             ORDER BY t.transaction_date DESC, t.created_at DESC",
+            queries::TRANSACTION_WITH_CATEGORY_COLUMNS,
+            vec!["?"; shown.len()].join(", ")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let transactions = stmt
+            .query_map(rusqlite::params_from_iter(shown), queries::transaction_with_category_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        transactions
+    };
+
+    Ok(RuleMatchPreview {
+        match_count: matched.len(),
+        transactions,
+    })
+}
+
+/// Whether an enabled rule the user wrote has a merchant condition matching this merchant
+pub fn merchant_has_rule(conn: &Connection, user_id: &str, merchant_normalized: &str) -> DbResult<bool> {
+    let has_rule = load_enabled_rules(conn, user_id)?
+        .iter()
+        .any(|rule| rule.merchant.as_ref().is_some_and(|merchant| merchant.matches(merchant_normalized)));
+
+    Ok(has_rule)
+}
+
+/// Compile a glob (`*` any run of characters, `?` one character) matching the whole text
+fn glob_to_regex(glob: &str) -> Option<Regex> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).ok()
+}
+
+/// Human-readable summary of a rule, e.g. `merchant contains "uber" and amount under $15.00`.
+/// A named rule is described by its name.
+pub fn describe_rule(rule: &MerchantCategoryRule) -> String {
+    if let Some(ref name) = rule.name {
+        return format!("\"{}\"", name);
+    }
+
+    let mut conditions = Vec::new();
+    if let Some(ref pattern) = rule.merchant_pattern {
+        let verb = match rule.match_type.as_str() {
+            "exact" => "is",
+            "glob" => "matches",
+            "regex" => "matches regex",
+            _ => "contains",
+        };
+        conditions.push(format!("merchant {} \"{}\"", verb, pattern));
+    }
+    if let Some(ref provider) = rule.provider {
+        conditions.push(format!("provider is {}", provider));
+    }
+    match (rule.min_amount, rule.max_amount) {
+        (Some(min), Some(max)) => conditions.push(format!(
+            "amount from {} to under {}",
+            dollars(min),
+            dollars(max)
+        )),
+        (Some(min), None) => conditions.push(format!("amount at least {}", dollars(min))),
+        (None, Some(max)) => conditions.push(format!("amount under {}", dollars(max))),
+        (None, None) => {}
+    }
+    if let Some(ref days) = rule.days_of_week {
+        const NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+        let names: Vec<&str> = days
+            .split(',')
+            .filter_map(|d| d.trim().parse::<usize>().ok())
+            .filter_map(|d| NAMES.get(d.wrapping_sub(1)).copied())
+            .collect();
+        conditions.push(format!("on {}", names.join("/")));
+    }
+    if let Some(ref keyword) = rule.item_keyword {
+        conditions.push(format!("an item contains \"{}\"", keyword));
+    }
+    if let Some(ref sender) = rule.sender {
+        conditions.push(format!("sender contains \"{}\"", sender));
+    }
+
+    conditions.join(" and ")
+}
+
+fn dollars(cents: i64) -> String {
+    format!("${}", format_cents(cents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::NewTransaction;
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    fn rule_matches(rule: &MerchantCategoryRule, input: &CategorizationInput) -> bool {
+        CompiledRule::new(rule.clone()).matches(input)
+    }
+
+    fn rule(merchant_pattern: Option<&str>, match_type: &str) -> MerchantCategoryRule {
+        MerchantCategoryRule {
+            id: "r1".to_string(),
+            name: None,
+            merchant_pattern: merchant_pattern.map(str::to_string),
+            match_type: match_type.to_string(),
+            provider: None,
+            min_amount: None,
+            max_amount: None,
+            days_of_week: None,
+            item_keyword: None,
+            sender: None,
+            category_id: "c1".to_string(),
+            priority: 0,
+            enabled: true,
+            is_exact_match: false,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_rule_conditions() {
+        let input = CategorizationInput {
+            merchant_normalized: "uber trip",
+            provider: "uber",
+            amount: 1200,
+            // A Friday
+            transaction_date: "2024-03-01",
+            item_names: vec!["Whey Protein 2lb"],
+            sender: Some("noreply@uber.com"),
+            transaction_id: None,
+        };
+
+        let mut commute = rule(Some("uber"), "contains");
+        commute.max_amount = Some(1500);
+        assert!(rule_matches(&commute, &input));
+        assert_eq!(describe_rule(&commute), "merchant contains \"uber\" and amount under $15.00");
+
+        let mut travel = rule(Some("uber"), "contains");
+        travel.min_amount = Some(4000);
+        assert!(!rule_matches(&travel, &input));
+
+        assert!(rule_matches(&rule(Some("uber *"), "glob"), &input));
+        assert!(!rule_matches(&rule(Some("uber"), "glob"), &input));
+        assert!(rule_matches(&rule(Some("^UBER\\b"), "regex"), &input));
+        assert!(!rule_matches(&rule(Some("uber"), "exact"), &input));
+
+        let mut weekdays = rule(None, "contains");
+        weekdays.days_of_week = Some("1,2,3,4,5".to_string());
+        weekdays.item_keyword = Some("protein".to_string());
+        weekdays.sender = Some("@uber.com".to_string());
+        assert!(rule_matches(&weekdays, &input));
+        weekdays.days_of_week = Some("6,7".to_string());
+        assert!(!rule_matches(&weekdays, &input));
+    }
+
+    #[test]
+    fn test_preview_rule_matches() {
+        let conn = open_seeded();
+        let travel = category(&conn, "Travel");
+        for (merchant, date) in [
+            ("hotel lumen", "2024-03-01"),
+            ("harbor hotel", "2024-03-05"),
+            ("corner cafe", "2024-03-06"),
+            ("airport hotel", "2024-03-03"),
+        ] {
+            insert(
+                &conn,
+                &NewTransaction {
+                    transaction_date: date,
+                    ..new_transaction(merchant, 20000, merchant)
+                },
+            );
+        }
+
+        let rule = RuleInput::for_merchant("hotel", "contains", &travel);
+        let preview = preview_rule_matches(&conn, "local", &rule, 2).unwrap();
+        assert_eq!(preview.match_count, 3);
+        let merchants: Vec<&str> = preview.transactions.iter().map(|t| t.merchant.as_str()).collect();
+        assert_eq!(merchants, vec!["harbor hotel", "airport hotel"]);
+    }
+}
@@ -1,92 +1,464 @@
-use rusqlite::Connection;
+use std::collections::HashSet;
 
-use super::defaults::{get_merchant_category, get_provider_category};
-use crate::db::queries::{
-    get_category_from_previous_transaction, get_merchant_category_rule,
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::classifier::{classify, Prediction, MIN_CONFIDENCE};
+use super::defaults::{find_default_category, get_provider_category};
+use super::rule_engine::{
+    describe_rule, find_matching_rule, load_enabled_rules, merchant_has_rule, CategorizationInput, CompiledRule,
 };
+use crate::db::merchants::{find_merchant, get_merchant};
+use crate::db::queries::{find_category_by_name, get_category_by_id, get_category_from_previous_transaction};
+use crate::db::rule_suggestions::{upsert_rule_suggestion, RuleSuggestion};
 use crate::db::DbResult;
 
+/// Consistent manual assignments of a merchant before a rule is suggested
+pub const LEARN_THRESHOLD: i64 = 3;
+
+/// Which categorization tier assigned a transaction's category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CategorySource {
+    UserRule,
+    MerchantDefault,
+    PreviousTransaction,
+    MerchantPattern,
+    Classifier,
+    ProviderDefault,
+    Manual,
+    Uncategorized,
+}
+
+impl CategorySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CategorySource::UserRule => "user_rule",
+            CategorySource::MerchantDefault => "merchant_default",
+            CategorySource::PreviousTransaction => "previous_transaction",
+            CategorySource::MerchantPattern => "merchant_pattern",
+            CategorySource::Classifier => "classifier",
+            CategorySource::ProviderDefault => "provider_default",
+            CategorySource::Manual => "manual",
+            CategorySource::Uncategorized => "uncategorized",
+        }
+    }
+}
+
+/// The outcome of categorizing a transaction, with a human-readable reason
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Categorization {
+    pub category_id: Option<String>,
+    pub source: CategorySource,
+    pub reason: String,
+    /// The user rule that assigned the category, for `CategorySource::UserRule`
+    pub rule_id: Option<String>,
+    /// Classifier probability, for `CategorySource::Classifier`
+    pub confidence: Option<f64>,
+}
+
+/// The automatic tiers, in priority order
+const TIERS: [CategorySource; 6] = [
+    CategorySource::UserRule,
+    CategorySource::MerchantDefault,
+    CategorySource::PreviousTransaction,
+    CategorySource::MerchantPattern,
+    CategorySource::Classifier,
+    CategorySource::ProviderDefault,
+];
+
 /// Categorize a transaction based on priority rules:
-/// 1. User-defined merchant rules (merchant_category_rules table)
-/// 2. Exact merchant match from previous transactions
-/// 3. Pattern match from default merchant mappings
-/// 4. Provider-based default
-/// 5. Falls back to None (Uncategorized)
+/// 1. User-defined rules (merchant_category_rules table), in priority order
+/// 2. Default category of the merchant the text resolves to
+/// 3. Exact merchant match from previous transactions
+/// 4. Bundled merchant knowledge base, with the user's overrides
+/// 5. Naive Bayes classifier, when confident enough
+/// 6. Provider-based default
+/// 7. Falls back to Uncategorized
 pub fn categorize_transaction(
     conn: &Connection,
     user_id: &str,
-    merchant_normalized: &str,
-    provider: &str,
-) -> DbResult<Option<String>> {
-    // 1. Check user-defined merchant rules
-    if let Some(category_id) = get_merchant_category_rule(conn, user_id, merchant_normalized)? {
-        log::debug!("Category from user rule: {}", category_id);
-        return Ok(Some(category_id));
+    input: &CategorizationInput,
+) -> DbResult<Categorization> {
+    let rules = load_enabled_rules(conn, user_id)?;
+    categorize_transaction_with_rules(conn, user_id, input, &rules)
+}
+
+/// `categorize_transaction` with the user's rules already loaded, for categorizing
+/// many transactions
+pub fn categorize_transaction_with_rules(
+    conn: &Connection,
+    user_id: &str,
+    input: &CategorizationInput,
+    rules: &[CompiledRule],
+) -> DbResult<Categorization> {
+    for tier in TIERS {
+        if let Some(categorization) = categorize_with_tier(conn, user_id, input, rules, tier)? {
+            return Ok(categorization);
+        }
     }
+    uncategorized(conn, user_id, input)
+}
 
-    // 2. Check if we've categorized this merchant before
-    if let Some(category_id) = get_category_from_previous_transaction(conn, user_id, merchant_normalized)? {
-        log::debug!("Category from previous transaction: {}", category_id);
-        return Ok(Some(category_id));
+/// A category the transaction could also belong in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryAlternative {
+    pub category_id: String,
+    pub category_name: Option<String>,
+    pub source: CategorySource,
+    pub reason: String,
+    pub confidence: Option<f64>,
+}
+
+/// The category a transaction will be filed under, with the runners-up, for previews
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryPrediction {
+    #[serde(flatten)]
+    pub categorization: Categorization,
+    pub category_name: Option<String>,
+    /// Categories lower tiers or a less confident classifier would pick, best first
+    pub alternatives: Vec<CategoryAlternative>,
+}
+
+impl CategoryPrediction {
+    /// No category, shown when predicting failed. Saving the transaction categorizes it again.
+    pub fn unavailable() -> Self {
+        Self {
+            categorization: Categorization {
+                category_id: None,
+                source: CategorySource::Uncategorized,
+                reason: "No category could be suggested".to_string(),
+                rule_id: None,
+                confidence: None,
+            },
+            category_name: None,
+            alternatives: Vec::new(),
+        }
     }
+}
+
+/// Alternatives listed with a prediction
+const MAX_ALTERNATIVES: usize = 3;
+
+/// Categorize a transaction exactly as `categorize_transaction` would, and also run
+/// the tiers below the winning one to offer other categories. Tiers stop once there
+/// are enough alternatives, and the classifier runs only once.
+pub fn predict_category(
+    conn: &Connection,
+    user_id: &str,
+    input: &CategorizationInput,
+) -> DbResult<CategoryPrediction> {
+    let rules = load_enabled_rules(conn, user_id)?;
+    let classified = classify(conn, user_id, input)?;
 
-    // 3. Check pattern match from default merchant mappings
-    if let Some(category_name) = get_merchant_category(merchant_normalized) {
-        if let Some(category_id) = find_category_by_name(conn, user_id, category_name)? {
-            log::debug!("Category from merchant pattern: {} -> {}", category_name, category_id);
-            return Ok(Some(category_id));
+    let mut matches = Vec::new();
+    let mut categories = HashSet::new();
+    for tier in TIERS {
+        let found = match tier {
+            CategorySource::Classifier => classified
+                .as_ref()
+                .filter(|p| p.confidence >= MIN_CONFIDENCE)
+                .map(|p| classifier_categorization(conn, p))
+                .transpose()?,
+            _ => categorize_with_tier(conn, user_id, input, &rules, tier)?,
+        };
+        if let Some(categorization) = found {
+            categories.extend(categorization.category_id.clone());
+            matches.push(categorization);
         }
+        // The winner plus a full list of alternatives
+        if categories.len() > MAX_ALTERNATIVES {
+            break;
+        }
+    }
+    let mut matches = matches.into_iter();
+    let categorization = match matches.next() {
+        Some(categorization) => categorization,
+        None => uncategorized(conn, user_id, input)?,
+    };
+
+    // Below the auto-apply threshold the classifier's guess is still worth offering
+    let mut candidates: Vec<Categorization> = matches.collect();
+    if let Some(prediction) = classified.filter(|p| p.confidence < MIN_CONFIDENCE) {
+        candidates.push(classifier_categorization(conn, &prediction)?);
     }
 
-    // 4. Check provider-based default
-    if let Some(category_name) = get_provider_category(provider) {
-        if let Some(category_id) = find_category_by_name(conn, user_id, category_name)? {
-            log::debug!("Category from provider: {} -> {}", category_name, category_id);
-            return Ok(Some(category_id));
+    let mut alternatives: Vec<CategoryAlternative> = Vec::new();
+    for candidate in candidates {
+        let Some(category_id) = candidate.category_id else {
+            continue;
+        };
+        if categorization.category_id.as_deref() == Some(category_id.as_str())
+            || alternatives.iter().any(|a| a.category_id == category_id)
+        {
+            continue;
+        }
+        alternatives.push(CategoryAlternative {
+            category_name: get_category_by_id(conn, &category_id)?.map(|c| c.name),
+            category_id,
+            source: candidate.source,
+            reason: candidate.reason,
+            confidence: candidate.confidence,
+        });
+        if alternatives.len() == MAX_ALTERNATIVES {
+            break;
         }
     }
 
-    // 5. Return uncategorized
-    log::debug!("No category found for: {}", merchant_normalized);
-    find_category_by_name(conn, user_id, "Uncategorized")
+    let category_name = match categorization.category_id {
+        Some(ref id) => get_category_by_id(conn, id)?.map(|c| c.name),
+        None => None,
+    };
+    Ok(CategoryPrediction {
+        categorization,
+        category_name,
+        alternatives,
+    })
 }
 
-/// Find a category ID by its name
-fn find_category_by_name(
+/// Run a single automatic tier
+fn categorize_with_tier(
     conn: &Connection,
     user_id: &str,
-    name: &str,
-) -> DbResult<Option<String>> {
-    let result: Option<String> = conn
-        .query_row(
-            "SELECT id FROM categories WHERE user_id = ?1 AND name = ?2",
-            [user_id, name],
-            |row| row.get(0),
-        )
-        .ok();
+    input: &CategorizationInput,
+    rules: &[CompiledRule],
+    tier: CategorySource,
+) -> DbResult<Option<Categorization>> {
+    let merchant_normalized = input.merchant_normalized;
+    let provider = input.provider;
+
+    match tier {
+        // 1. Check user-defined rules
+        CategorySource::UserRule => {
+            if let Some(rule) = find_matching_rule(rules, input) {
+                log::debug!("Category from user rule {}: {}", rule.id, rule.category_id);
+                return Ok(Some(Categorization {
+                    category_id: Some(rule.category_id.clone()),
+                    source: CategorySource::UserRule,
+                    reason: format!("Matches your rule {}", describe_rule(rule)),
+                    rule_id: Some(rule.id.clone()),
+                    confidence: None,
+                }));
+            }
+        }
+
+        // 2. Check the merchant's default category
+        CategorySource::MerchantDefault => {
+            if let Some(merchant) = find_merchant(conn, user_id, merchant_normalized)?
+                .map(|id| get_merchant(conn, &id))
+                .transpose()?
+                .flatten()
+            {
+                if let Some(category_id) = merchant.default_category_id {
+                    log::debug!("Category from merchant default: {}", category_id);
+                    return Ok(Some(Categorization {
+                        category_id: Some(category_id),
+                        source: CategorySource::MerchantDefault,
+                        reason: format!("Default category for {}", merchant.name),
+                        rule_id: None,
+                        confidence: None,
+                    }));
+                }
+            }
+        }
+
+        // 3. Check if we've categorized this merchant before
+        CategorySource::PreviousTransaction => {
+            if let Some(category_id) =
+                get_category_from_previous_transaction(conn, user_id, merchant_normalized, input.transaction_id)?
+            {
+                log::debug!("Category from previous transaction: {}", category_id);
+                return Ok(Some(Categorization {
+                    category_id: Some(category_id),
+                    source: CategorySource::PreviousTransaction,
+                    reason: format!("Same category as your previous \"{}\" transaction", merchant_normalized),
+                    rule_id: None,
+                    confidence: None,
+                }));
+            }
+        }
+
+        // 4. Check the merchant knowledge base and the user's overrides of it
+        CategorySource::MerchantPattern => {
+            if let Some(found) = find_default_category(conn, user_id, merchant_normalized)? {
+                log::debug!("Category from merchant knowledge base: {}", found.category_id);
+                return Ok(Some(Categorization {
+                    category_id: Some(found.category_id),
+                    source: CategorySource::MerchantPattern,
+                    reason: found.reason,
+                    rule_id: None,
+                    confidence: None,
+                }));
+            }
+        }
+
+        // 5. Ask the classifier trained on the user's own transactions
+        CategorySource::Classifier => {
+            if let Some(prediction) = classify(conn, user_id, input)? {
+                if prediction.confidence >= MIN_CONFIDENCE {
+                    log::debug!(
+                        "Category from classifier: {} ({:.2})",
+                        prediction.category_id,
+                        prediction.confidence
+                    );
+                    return classifier_categorization(conn, &prediction).map(Some);
+                }
+            }
+        }
+
+        // 6. Check provider-based default
+        CategorySource::ProviderDefault => {
+            if let Some(category_name) = get_provider_category(provider) {
+                if let Some(category_id) = find_category_by_name(conn, user_id, category_name)? {
+                    log::debug!("Category from provider: {} -> {}", category_name, category_id);
+                    return Ok(Some(Categorization {
+                        category_id: Some(category_id),
+                        source: CategorySource::ProviderDefault,
+                        reason: format!("Default category for {} receipts", provider),
+                        rule_id: None,
+                        confidence: None,
+                    }));
+                }
+            }
+        }
+
+        CategorySource::Manual | CategorySource::Uncategorized => {}
+    }
+
+    Ok(None)
+}
+
+fn classifier_categorization(conn: &Connection, prediction: &Prediction) -> DbResult<Categorization> {
+    let category_name = get_category_by_id(conn, &prediction.category_id)?
+        .map(|c| c.name)
+        .unwrap_or_default();
+    Ok(Categorization {
+        category_id: Some(prediction.category_id.clone()),
+        source: CategorySource::Classifier,
+        reason: format!(
+            "Similar to your {} transactions ({:.0}% confident)",
+            category_name,
+            prediction.confidence * 100.0
+        ),
+        rule_id: None,
+        confidence: Some(prediction.confidence),
+    })
+}
 
-    Ok(result)
+/// 7. Return uncategorized
+fn uncategorized(conn: &Connection, user_id: &str, input: &CategorizationInput) -> DbResult<Categorization> {
+    log::debug!("No category found for: {}", input.merchant_normalized);
+    Ok(Categorization {
+        category_id: find_category_by_name(conn, user_id, "Uncategorized")?,
+        source: CategorySource::Uncategorized,
+        reason: "No rule or known merchant matched".to_string(),
+        rule_id: None,
+        confidence: None,
+    })
 }
 
-#[allow(dead_code)]
-/// Learn from a user's category assignment
-/// When a user manually assigns a category, we can optionally create a rule
+/// Learn from a user's manual category assignment. Once the merchant's last
+/// `LEARN_THRESHOLD` manual assignments all went to the same category, a merchant
+/// rule is suggested. Merchants already covered by a rule the user wrote are skipped.
 pub fn learn_from_assignment(
     conn: &Connection,
     user_id: &str,
-    merchant_normalized: &str,
-    category_id: &str,
-    create_rule: bool,
-) -> DbResult<()> {
-    if create_rule {
-        // Create an exact match rule
-        crate::db::queries::set_merchant_category_rule(
-            conn,
-            user_id,
-            merchant_normalized,
-            category_id,
-            true, // exact match
-        )?;
+    transaction_id: &str,
+) -> DbResult<Option<RuleSuggestion>> {
+    let transaction: Option<(String, Option<String>, Option<String>)> = conn
+        .query_row(
+            "SELECT merchant_normalized, category_id, category_source FROM transactions
+             WHERE user_id = ?1 AND id = ?2",
+            params![user_id, transaction_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let Some((merchant_normalized, Some(category_id), Some(source))) = transaction else {
+        return Ok(None);
+    };
+    if source != CategorySource::Manual.as_str() || merchant_has_rule(conn, user_id, &merchant_normalized)? {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT category_id FROM transactions
+         WHERE user_id = ?1 AND merchant_normalized = ?2 AND category_source = 'manual'
+         ORDER BY updated_at DESC
+         LIMIT ?3",
+    )?;
+    let recent = stmt
+        .query_map(params![user_id, &merchant_normalized, LEARN_THRESHOLD], |row| {
+            row.get::<_, Option<String>>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if recent.len() < LEARN_THRESHOLD as usize || recent.iter().any(|id| id.as_deref() != Some(&category_id)) {
+        return Ok(None);
+    }
+
+    let assignment_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM transactions
+         WHERE user_id = ?1 AND merchant_normalized = ?2 AND category_source = 'manual' AND category_id = ?3",
+        params![user_id, &merchant_normalized, &category_id],
+        |row| row.get(0),
+    )?;
+    log::debug!(
+        "Suggesting rule {} -> {} after {} manual assignments",
+        merchant_normalized,
+        category_id,
+        assignment_count
+    );
+
+    upsert_rule_suggestion(conn, user_id, &merchant_normalized, &category_id, assignment_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{self, NewTransaction, RuleInput};
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    #[test]
+    fn test_prediction_matches_categorization() {
+        let conn = open_seeded();
+        let shopping = category(&conn, "Shopping");
+        let dining = category(&conn, "Food & Dining");
+        let groceries = category(&conn, "Groceries");
+        queries::create_category_rule(&conn, "local", &RuleInput::for_merchant("starbucks", "contains", &shopping))
+            .unwrap();
+        insert(
+            &conn,
+            &NewTransaction {
+                category_id: Some(&groceries),
+                category_source: Some("manual"),
+                ..new_transaction("starbucks coffee", 1299, "beans")
+            },
+        );
+
+        let input = CategorizationInput {
+            merchant_normalized: "starbucks coffee",
+            provider: "generic",
+            amount: 650,
+            transaction_date: "2024-03-01",
+            ..Default::default()
+        };
+        let categorization = categorize_transaction(&conn, "local", &input).unwrap();
+        let prediction = predict_category(&conn, "local", &input).unwrap();
+        assert_eq!(prediction.categorization.category_id, categorization.category_id);
+        assert_eq!(prediction.categorization.source, CategorySource::UserRule);
+        assert_eq!(prediction.category_name.as_deref(), Some("Shopping"));
+
+        // Lower tiers' categories are offered instead of being lost, best first
+        let alternatives: Vec<(&str, CategorySource)> = prediction
+            .alternatives
+            .iter()
+            .map(|a| (a.category_id.as_str(), a.source))
+            .collect();
+        assert_eq!(
+            alternatives,
+            vec![
+                (groceries.as_str(), CategorySource::PreviousTransaction),
+                (dining.as_str(), CategorySource::MerchantPattern),
+            ]
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,80 @@
+use tauri::AppHandle;
+
+use crate::db::{self, queries};
+
+/// Get all accounts
+#[tauri::command]
+pub async fn get_accounts(app_handle: AppHandle) -> Result<Vec<queries::Account>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::get_accounts(&conn, user_id).map_err(|e| e.to_string())
+}
+
+/// Create an account, e.g. a card that receipts don't identify
+#[tauri::command]
+pub async fn create_account(
+    app_handle: AppHandle,
+    name: String,
+    account_type: String,
+    institution: Option<String>,
+    last4: Option<String>,
+) -> Result<queries::Account, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::create_account(
+        &conn,
+        user_id,
+        &name,
+        &account_type,
+        institution.as_deref(),
+        last4.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Update an account
+#[tauri::command]
+pub async fn update_account(
+    app_handle: AppHandle,
+    account_id: String,
+    name: String,
+    account_type: String,
+    institution: Option<String>,
+    last4: Option<String>,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    queries::update_account(
+        &conn,
+        &account_id,
+        &name,
+        &account_type,
+        institution.as_deref(),
+        last4.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Delete an account; its transactions are kept without an account
+#[tauri::command]
+pub async fn delete_account(app_handle: AppHandle, account_id: String) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    queries::delete_account(&conn, &account_id).map_err(|e| e.to_string())
+}
+
+/// Get spending per account for a date range
+#[tauri::command]
+pub async fn get_account_spending(
+    app_handle: AppHandle,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<queries::AccountSpending>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::get_account_spending(&conn, user_id, &start_date, &end_date)
+        .map_err(|e| e.to_string())
+}
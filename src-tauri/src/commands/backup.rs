@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::backup::{self, archive, scheduler, types::*};
+use crate::db;
+use crate::gmail::{poller::GmailPollerState, tokens};
+
+/// Back up the database to `destination`, or to the app's backups folder if omitted
+#[tauri::command]
+pub async fn create_backup(
+    app_handle: AppHandle,
+    destination: Option<String>,
+    compress: Option<bool>,
+) -> Result<BackupInfo, String> {
+    let path = match destination {
+        Some(destination) => PathBuf::from(destination),
+        None => {
+            let dir = backup::get_backups_dir(&app_handle).map_err(|e| e.to_string())?;
+            dir.join(format!(
+                "email_budget-{}.zip",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            ))
+        }
+    };
+
+    let database = db::state(&app_handle).get().map_err(|e| e.to_string())?;
+    let manifest = archive::create_backup(&database, &path, compress.unwrap_or(true))
+        .map_err(|e| e.to_string())?;
+
+    log::info!("Backup written to {:?}", path);
+    Ok(BackupInfo {
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        file_size: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+        path: path.to_string_lossy().to_string(),
+        is_automatic: false,
+        manifest,
+    })
+}
+
+/// Read a backup's manifest so the user can confirm before restoring
+#[tauri::command]
+pub async fn inspect_backup(path: String) -> Result<BackupManifest, String> {
+    archive::read_manifest(&PathBuf::from(path)).map_err(|e| e.to_string())
+}
+
+/// Replace the current database with a backup. The replaced database is kept as
+/// `email_budget.db.pre-restore.bak`. Fails with `PASSPHRASE_REQUIRED` if the
+/// backup is encrypted and no passphrase is given.
+#[tauri::command]
+pub async fn restore_backup(
+    app_handle: AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<BackupManifest, String> {
+    let poller = app_handle.state::<GmailPollerState>();
+    poller.stop();
+
+    let restored = archive::restore_backup(
+        &db::state(&app_handle),
+        &PathBuf::from(path),
+        passphrase.as_deref(),
+    );
+
+    // Restart polling whether or not the restore went through; the restored database
+    // may have different Gmail credentials
+    let has_tokens = db::reader(&app_handle)
+        .map(|conn| tokens::has_tokens(&conn).unwrap_or(false))
+        .unwrap_or(false);
+    if has_tokens {
+        poller.start();
+    }
+
+    restored.map_err(|e| e.to_string())
+}
+
+/// List backups in the app's backups folder, newest first
+#[tauri::command]
+pub async fn list_backups(app_handle: AppHandle) -> Result<Vec<BackupInfo>, String> {
+    let dir = backup::get_backups_dir(&app_handle).map_err(|e| e.to_string())?;
+    scheduler::list_backups(&dir).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_backup_schedule(app_handle: AppHandle) -> Result<BackupSchedule, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    scheduler::get_schedule(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_backup_schedule(
+    app_handle: AppHandle,
+    schedule: BackupSchedule,
+) -> Result<(), String> {
+    {
+        let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+        scheduler::set_schedule(&conn, &schedule).map_err(|e| e.to_string())?;
+    }
+
+    // Take a backup right away if enabling the schedule makes one due
+    scheduler::run_scheduled_backup(&app_handle).map_err(|e| e.to_string())?;
+    Ok(())
+}
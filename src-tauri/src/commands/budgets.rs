@@ -7,7 +7,7 @@ use crate::db::{self, queries};
 pub async fn get_budgets(
     app_handle: AppHandle,
 ) -> Result<Vec<queries::BudgetWithProgress>, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     queries::get_all_budgets(&conn, user_id).map_err(|e| e.to_string())
@@ -21,7 +21,7 @@ pub async fn set_budget(
     amount: i64,
     period: String,
 ) -> Result<queries::Budget, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     // Validate period
@@ -38,7 +38,7 @@ pub async fn delete_budget(
     app_handle: AppHandle,
     budget_id: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
 
     queries::delete_budget(&conn, &budget_id).map_err(|e| e.to_string())
 }
@@ -1,5 +1,6 @@
 use tauri::AppHandle;
 
+use crate::categorizer::classifier;
 use crate::db::{self, queries};
 
 /// Get all categories
@@ -7,27 +8,29 @@ use crate::db::{self, queries};
 pub async fn get_categories(
     app_handle: AppHandle,
 ) -> Result<Vec<queries::Category>, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     queries::get_all_categories(&conn, user_id).map_err(|e| e.to_string())
 }
 
-/// Create a new category
+/// Create a new category, optionally as a subcategory of `parent_id`
 #[tauri::command]
 pub async fn create_category(
     app_handle: AppHandle,
     name: String,
     icon: String,
     color: String,
+    parent_id: Option<String>,
 ) -> Result<queries::Category, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
-    queries::create_category(&conn, user_id, &name, &icon, &color).map_err(|e| e.to_string())
+    queries::create_category(&conn, user_id, &name, &icon, &color, parent_id.as_deref())
+        .map_err(|e| e.to_string())
 }
 
-/// Update a category
+/// Rename or restyle a category, including system categories
 #[tauri::command]
 pub async fn update_category(
     app_handle: AppHandle,
@@ -36,22 +39,73 @@ pub async fn update_category(
     icon: String,
     color: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
 
     queries::update_category(&conn, &category_id, &name, &icon, &color).map_err(|e| e.to_string())
 }
 
-/// Delete a category
+/// Move a category under another, or to the top level with no parent
+#[tauri::command]
+pub async fn set_category_parent(
+    app_handle: AppHandle,
+    category_id: String,
+    parent_id: Option<String>,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    queries::set_category_parent(&conn, &category_id, parent_id.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Hide or unhide a category
+#[tauri::command]
+pub async fn set_category_hidden(
+    app_handle: AppHandle,
+    category_id: String,
+    hidden: bool,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    queries::set_category_hidden(&conn, &category_id, hidden).map_err(|e| e.to_string())
+}
+
+/// Delete a category that has no transactions, rules or budgets
 #[tauri::command]
 pub async fn delete_category(
     app_handle: AppHandle,
     category_id: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
 
     queries::delete_category(&conn, &category_id).map_err(|e| e.to_string())
 }
 
+/// Delete a category after moving its transactions, rules and budgets to another
+#[tauri::command]
+pub async fn delete_category_with_reassign(
+    app_handle: AppHandle,
+    category_id: String,
+    target_category_id: String,
+) -> Result<queries::CategoryMergeResult, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::merge_categories(&conn, user_id, &[category_id], &target_category_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Merge several categories into one
+#[tauri::command]
+pub async fn merge_categories(
+    app_handle: AppHandle,
+    source_ids: Vec<String>,
+    target_id: String,
+) -> Result<queries::CategoryMergeResult, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::merge_categories(&conn, user_id, &source_ids, &target_id).map_err(|e| e.to_string())
+}
+
 /// Get category spending for a date range
 #[tauri::command]
 pub async fn get_category_spending(
@@ -59,9 +113,18 @@ pub async fn get_category_spending(
     start_date: String,
     end_date: String,
 ) -> Result<Vec<queries::CategorySpending>, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     queries::get_category_spending(&conn, user_id, &start_date, &end_date)
         .map_err(|e| e.to_string())
 }
+
+/// Retrain the local classifier on the current categories
+#[tauri::command]
+pub async fn train_classifier(app_handle: AppHandle) -> Result<classifier::TrainingSummary, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    classifier::train(&conn, user_id).map_err(|e| e.to_string())
+}
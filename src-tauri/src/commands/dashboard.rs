@@ -2,17 +2,21 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 
-use crate::db::{self, queries};
+use crate::db::{self, income, queries};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DashboardStats {
     pub total_spent: i64,
+    pub total_income: i64,
+    /// Share of income not spent, or None without income this month
+    pub savings_rate: Option<f64>,
     pub transaction_count: i64,
     pub category_count: i64,
     pub budget_health: String,
     pub category_spending: Vec<queries::CategorySpending>,
     pub recent_transactions: Vec<queries::TransactionWithCategory>,
     pub top_merchants: Vec<queries::MerchantTotal>,
+    pub account_spending: Vec<queries::AccountSpending>,
 }
 
 /// Get dashboard statistics for a given month
@@ -22,7 +26,7 @@ pub async fn get_dashboard_stats(
     month: u32,
     year: i32,
 ) -> Result<DashboardStats, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     // Calculate date range for the month
@@ -44,6 +48,11 @@ pub async fn get_dashboard_stats(
     let total_spent =
         queries::get_total_spent(&conn, user_id, &start_date, &end_date).map_err(|e| e.to_string())?;
 
+    // Get income and savings rate
+    let total_income =
+        queries::get_total_income(&conn, user_id, &start_date, &end_date).map_err(|e| e.to_string())?;
+    let savings_rate = income::savings_rate(total_income, total_spent);
+
     // Get transaction count
     let transaction_count =
         queries::get_transaction_count(&conn, user_id, &start_date, &end_date).map_err(|e| e.to_string())?;
@@ -63,22 +72,35 @@ pub async fn get_dashboard_stats(
         end_date: Some(end_date.clone()),
         ..Default::default()
     };
-    let all_transactions =
-        queries::get_transactions(&conn, user_id, &recent_filters).map_err(|e| e.to_string())?;
-    let recent_transactions: Vec<_> = all_transactions.into_iter().take(5).collect();
+    let recent_page = queries::TransactionPageRequest {
+        limit: 5,
+        include_totals: false,
+        ..Default::default()
+    };
+    let recent_transactions =
+        queries::get_transactions_page(&conn, user_id, &recent_filters, &recent_page)
+            .map_err(|e| e.to_string())?
+            .transactions;
 
     // Get top merchants
     let top_merchants =
         queries::get_top_merchants(&conn, user_id, &start_date, &end_date, 5).map_err(|e| e.to_string())?;
 
+    // Get spending per account
+    let account_spending =
+        queries::get_account_spending(&conn, user_id, &start_date, &end_date).map_err(|e| e.to_string())?;
+
     Ok(DashboardStats {
         total_spent,
+        total_income,
+        savings_rate,
         transaction_count,
         category_count,
         budget_health,
         category_spending,
         recent_transactions,
         top_merchants,
+        account_spending,
     })
 }
 
@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+use tauri::AppHandle;
+
+use super::transactions::TransactionFilters;
+use crate::db;
+use crate::export::accounting::{self, AccountMapping, AccountingFormat};
+use crate::export::dataset::{self, DatasetImportResult};
+use crate::export::records;
+use crate::export::rules::{self, RuleSetImportResult};
+use crate::export::{ExportFormat, ExportSummary};
+
+/// Export the transactions matching `filters`, with items and fees, as CSV or JSON
+#[tauri::command]
+pub async fn export_transactions(
+    app_handle: AppHandle,
+    path: String,
+    format: ExportFormat,
+    filters: Option<TransactionFilters>,
+) -> Result<ExportSummary, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let row_count = records::export_transactions(
+        &conn,
+        user_id,
+        &filters.unwrap_or_default().into(),
+        format,
+        &PathBuf::from(&path),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(ExportSummary { path, row_count })
+}
+
+/// Export one row per purchased item for the transactions matching `filters`
+#[tauri::command]
+pub async fn export_items(
+    app_handle: AppHandle,
+    path: String,
+    format: ExportFormat,
+    filters: Option<TransactionFilters>,
+) -> Result<ExportSummary, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let row_count = records::export_items(
+        &conn,
+        user_id,
+        &filters.unwrap_or_default().into(),
+        format,
+        &PathBuf::from(&path),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(ExportSummary { path, row_count })
+}
+
+#[tauri::command]
+pub async fn export_budgets(
+    app_handle: AppHandle,
+    path: String,
+    format: ExportFormat,
+) -> Result<ExportSummary, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let row_count = records::export_budgets(&conn, user_id, format, &PathBuf::from(&path))
+        .map_err(|e| e.to_string())?;
+
+    Ok(ExportSummary { path, row_count })
+}
+
+/// Export categories, merchant rules, budgets and sender filters as JSON
+#[tauri::command]
+pub async fn export_dataset(app_handle: AppHandle, path: String) -> Result<ExportSummary, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let dataset = dataset::export_dataset(&conn, user_id, &PathBuf::from(&path))
+        .map_err(|e| e.to_string())?;
+
+    Ok(ExportSummary {
+        path,
+        row_count: dataset.categories.len()
+            + dataset.merchant_category_rules.len()
+            + dataset.budgets.len()
+            + dataset.sender_filters.len(),
+    })
+}
+
+/// Merge a file written by `export_dataset` into the database
+#[tauri::command]
+pub async fn import_dataset(
+    app_handle: AppHandle,
+    path: String,
+) -> Result<DatasetImportResult, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    dataset::import_dataset_file(&conn, user_id, &PathBuf::from(path)).map_err(|e| e.to_string())
+}
+
+/// Export the categorization rules as JSON for sharing
+#[tauri::command]
+pub async fn export_rules(app_handle: AppHandle, path: String) -> Result<ExportSummary, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let rule_set = rules::export_rules(&conn, user_id, &PathBuf::from(&path)).map_err(|e| e.to_string())?;
+
+    Ok(ExportSummary {
+        path,
+        row_count: rule_set.rules.len(),
+    })
+}
+
+/// Add the rules from a shared rule set file after the existing rules
+#[tauri::command]
+pub async fn import_rules(app_handle: AppHandle, path: String) -> Result<RuleSetImportResult, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    rules::import_rules_file(&conn, user_id, &PathBuf::from(path)).map_err(|e| e.to_string())
+}
+
+/// Export transactions as OFX, QIF, ledger or beancount using the saved account mapping
+#[tauri::command]
+pub async fn export_accounting(
+    app_handle: AppHandle,
+    path: String,
+    format: AccountingFormat,
+    filters: Option<TransactionFilters>,
+) -> Result<ExportSummary, String> {
+    // Writer, because default accounts for new categories are saved to the mapping
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let row_count = accounting::export_accounting(
+        &conn,
+        user_id,
+        &filters.unwrap_or_default().into(),
+        format,
+        &PathBuf::from(&path),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(ExportSummary { path, row_count })
+}
+
+/// Get the category-to-account mapping, with defaults for categories not yet mapped
+#[tauri::command]
+pub async fn get_account_mapping(app_handle: AppHandle) -> Result<AccountMapping, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    accounting::complete_account_mapping(&conn, user_id)
+        .map(|(mapping, _)| mapping)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_account_mapping(
+    app_handle: AppHandle,
+    mapping: AccountMapping,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    accounting::set_account_mapping(&conn, user_id, &mapping).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries;
+
+    #[test]
+    fn test_filters_from_frontend() {
+        // As sent by exportTransactions in src/lib/tauri.ts
+        let json = r#"{
+            "search": "coffee",
+            "categoryId": "cat-1",
+            "accountId": "acct-1",
+            "categorySource": "manual",
+            "startDate": "2024-01-01",
+            "endDate": "2024-01-31",
+            "minAmount": 100,
+            "maxAmount": 5000
+        }"#;
+        let filters: Option<TransactionFilters> = serde_json::from_str(json).unwrap();
+        let filters: queries::TransactionFilters = filters.unwrap_or_default().into();
+
+        assert_eq!(filters.search.as_deref(), Some("coffee"));
+        assert_eq!(filters.category_id.as_deref(), Some("cat-1"));
+        assert_eq!(filters.account_id.as_deref(), Some("acct-1"));
+        assert_eq!(filters.category_source.as_deref(), Some("manual"));
+        assert_eq!(filters.start_date.as_deref(), Some("2024-01-01"));
+        assert_eq!(filters.end_date.as_deref(), Some("2024-01-31"));
+        assert_eq!(filters.min_amount, Some(100));
+        assert_eq!(filters.max_amount, Some(5000));
+    }
+}
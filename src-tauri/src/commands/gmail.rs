@@ -19,13 +19,12 @@ pub async fn gmail_connect(app_handle: AppHandle) -> Result<String, String> {
         oauth::run_oauth_flow().await?
     };
 
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
-
     // Get user email from profile
     let gmail_client = crate::gmail::client::GmailClient::new(&oauth_tokens.access_token);
     let profile = gmail_client.get_profile().await?;
 
     // Save tokens
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     tokens::save_tokens(
         &conn,
         &oauth_tokens.access_token,
@@ -52,13 +51,12 @@ pub async fn gmail_exchange_code(
 ) -> Result<String, String> {
     let oauth_tokens = oauth_mobile::exchange_code_mobile(&code, &code_verifier).await?;
 
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
-
     // Get user email from profile
     let gmail_client = crate::gmail::client::GmailClient::new(&oauth_tokens.access_token);
     let profile = gmail_client.get_profile().await?;
 
     // Save tokens
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     tokens::save_tokens(
         &conn,
         &oauth_tokens.access_token,
@@ -78,18 +76,21 @@ pub async fn gmail_exchange_code(
 
 #[tauri::command]
 pub async fn gmail_disconnect(app_handle: AppHandle) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
-
     // Stop polling
     let poller = app_handle.state::<GmailPollerState>();
     poller.stop();
 
     // Revoke token (best effort)
-    if let Ok(Some((access_token, _, _, _))) = tokens::get_tokens(&conn) {
+    let token_info = {
+        let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+        tokens::get_tokens(&conn)
+    };
+    if let Ok(Some((access_token, _, _, _))) = token_info {
         oauth::revoke_token(&access_token).await;
     }
 
     // Clear tokens and sync state
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     tokens::delete_tokens(&conn).map_err(|e| e.to_string())?;
     tokens::reset_sync_state(&conn).map_err(|e| e.to_string())?;
     tokens::clear_processed_messages(&conn).map_err(|e| e.to_string())?;
@@ -104,7 +105,7 @@ pub async fn gmail_disconnect(app_handle: AppHandle) -> Result<(), String> {
 
 #[tauri::command]
 pub async fn gmail_get_status(app_handle: AppHandle) -> Result<GmailConnectionStatus, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
 
     let token_info = tokens::get_tokens(&conn).map_err(|e| e.to_string())?;
     let sync_state = tokens::get_sync_state(&conn).map_err(|e| e.to_string())?;
@@ -157,7 +158,7 @@ pub async fn gmail_sync_now(app_handle: AppHandle) -> Result<SyncCycleResult, St
 
 #[tauri::command]
 pub async fn gmail_get_sender_filters(app_handle: AppHandle) -> Result<Vec<SenderFilter>, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
     tokens::get_sender_filters(&conn).map_err(|e| e.to_string())
 }
 
@@ -167,7 +168,7 @@ pub async fn gmail_add_sender_filter(
     email: String,
     label: String,
 ) -> Result<SenderFilter, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     tokens::add_sender_filter(&conn, &email, &label).map_err(|e| e.to_string())
 }
 
@@ -176,7 +177,7 @@ pub async fn gmail_remove_sender_filter(
     app_handle: AppHandle,
     filter_id: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     tokens::remove_sender_filter(&conn, &filter_id).map_err(|e| e.to_string())
 }
 
@@ -185,6 +186,6 @@ pub async fn gmail_toggle_sender_filter(
     app_handle: AppHandle,
     filter_id: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     tokens::toggle_sender_filter(&conn, &filter_id).map_err(|e| e.to_string())
 }
@@ -1,14 +1,20 @@
 use std::collections::HashMap;
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 
-use crate::categorizer::categorize_transaction;
-use crate::db::{self, queries};
+use crate::categorizer::rules::Categorization;
+use crate::categorizer::{
+    categorize_transaction, items, predict_category, CategorizationInput, CategoryPrediction, CategorySource,
+};
+use crate::db::{self, merchants, queries, DbResult};
 use crate::parser::{self, ParsedTransaction};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImportPreview {
     pub transactions: Vec<ParsedTransaction>,
+    /// Category each transaction will be filed under, by index
+    pub predictions: Vec<CategoryPrediction>,
     pub duplicates: i32,
     pub errors: Vec<String>,
 }
@@ -20,27 +26,39 @@ pub struct ImportResult {
     pub errors: Vec<String>,
 }
 
-/// Parse HTML receipts and return preview of transactions
+/// Parse HTML receipts and return preview of transactions.
+/// `file_names`, if given, are recorded as each transaction's source.
 #[tauri::command]
 pub async fn import_receipts(
     app_handle: AppHandle,
     html_contents: Vec<String>,
+    file_names: Option<Vec<String>>,
 ) -> Result<ImportPreview, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
 
+    let user_id = "local";
     let mut transactions = Vec::new();
+    let mut predictions = Vec::new();
     let mut duplicates = 0;
     let mut errors = Vec::new();
 
     for (i, html) in html_contents.iter().enumerate() {
         match parser::parse_html(html) {
-            parser::types::ParseResult::Success(transaction) => {
+            parser::types::ParseResult::Success(mut transaction) => {
+                transaction.source_ref = file_names.as_ref().and_then(|names| names.get(i).cloned());
+
                 // Check for duplicates
                 let hash = transaction.source_hash();
                 if queries::transaction_exists(&conn, &hash).unwrap_or(false) {
                     duplicates += 1;
                     log::info!("Skipping duplicate transaction: {}", transaction.merchant);
                 } else {
+                    let merchant_normalized = transaction.merchant_normalized();
+                    let input = CategorizationInput::from_parsed(&transaction, &merchant_normalized);
+                    predictions.push(predict_category(&conn, user_id, &input).unwrap_or_else(|e| {
+                        log::warn!("Failed to predict a category for {}: {}", transaction.merchant, e);
+                        CategoryPrediction::unavailable()
+                    }));
                     transactions.push(transaction);
                 }
             }
@@ -55,19 +73,22 @@ pub async fn import_receipts(
 
     Ok(ImportPreview {
         transactions,
+        predictions,
         duplicates,
         errors,
     })
 }
 
-/// Confirm and save imported transactions
+/// Confirm and save imported transactions. Each is filed under the category the user
+/// chose, else the prediction shown in the preview, else categorized now.
 #[tauri::command]
 pub async fn confirm_import(
     app_handle: AppHandle,
     transactions: Vec<ParsedTransaction>,
     category_assignments: HashMap<usize, String>,
+    predictions: Option<Vec<CategoryPrediction>>,
 ) -> Result<ImportResult, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     let mut imported = 0;
@@ -75,60 +96,17 @@ pub async fn confirm_import(
     let mut errors = Vec::new();
 
     for (i, transaction) in transactions.iter().enumerate() {
-        let source_hash = transaction.source_hash();
-
-        // Skip if already exists
-        if queries::transaction_exists(&conn, &source_hash).unwrap_or(false) {
-            skipped += 1;
-            continue;
-        }
-
-        // Determine category
-        let category_id = if let Some(assigned_id) = category_assignments.get(&i) {
-            Some(assigned_id.clone())
-        } else {
-            // Auto-categorize
-            categorize_transaction(
-                &conn,
-                user_id,
-                &transaction.merchant_normalized(),
-                &transaction.provider,
-            )
-            .ok()
-            .flatten()
+        let categorization = match category_assignments.get(&i) {
+            Some(category_id) => Some(chosen_during_import(category_id)),
+            None => predictions
+                .as_ref()
+                .and_then(|predictions| predictions.get(i))
+                .map(|prediction| prediction.categorization.clone()),
         };
-
-        // Insert transaction
-        match queries::insert_transaction(
-            &conn,
-            user_id,
-            category_id.as_deref(),
-            &transaction.merchant,
-            &transaction.merchant_normalized(),
-            transaction.amount,
-            &transaction.transaction_date,
-            &transaction.provider,
-            &source_hash,
-        ) {
-            Ok(transaction_id) => {
-                // Insert items
-                for item in &transaction.items {
-                    if let Err(e) = queries::insert_transaction_item(
-                        &conn,
-                        &transaction_id,
-                        &item.name,
-                        item.quantity,
-                        item.unit_price,
-                        item.total_price,
-                    ) {
-                        log::warn!("Failed to insert item: {}", e);
-                    }
-                }
-                imported += 1;
-            }
-            Err(e) => {
-                errors.push(format!("{}: {}", transaction.merchant, e));
-            }
+        match save_parsed_transaction(&conn, user_id, transaction, "import", categorization) {
+            Ok(Some(_)) => imported += 1,
+            Ok(None) => skipped += 1,
+            Err(e) => errors.push(format!("{}: {}", transaction.merchant, e)),
         }
     }
 
@@ -138,3 +116,208 @@ pub async fn confirm_import(
         errors,
     })
 }
+
+/// A category the user picked in the import preview
+pub(crate) fn chosen_during_import(category_id: &str) -> Categorization {
+    Categorization {
+        category_id: Some(category_id.to_string()),
+        source: CategorySource::Manual,
+        reason: "Chosen during import".to_string(),
+        rule_id: None,
+        confidence: None,
+    }
+}
+
+/// Whether the category and rule of a categorization made at preview time still exist
+fn still_applies(conn: &Connection, categorization: &Categorization) -> DbResult<bool> {
+    if let Some(ref id) = categorization.category_id {
+        if queries::get_category_by_id(conn, id)?.is_none() {
+            return Ok(false);
+        }
+    }
+    if let Some(ref id) = categorization.rule_id {
+        if queries::get_merchant_category_rule_by_id(conn, id)?.is_none() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Save a parsed receipt the way every import path does: skip it if the same receipt
+/// is already stored, categorize it unless given the categorization accepted in the
+/// preview, link its account and merchant, and store its items, fees, raw text and
+/// parser confidence. Everything is written in one database transaction, so a failure
+/// leaves nothing behind. Returns the new transaction's ID, or None for a duplicate.
+pub(crate) fn save_parsed_transaction(
+    conn: &Connection,
+    user_id: &str,
+    transaction: &ParsedTransaction,
+    source_type: &str,
+    categorization: Option<Categorization>,
+) -> DbResult<Option<String>> {
+    let mut tx = conn.unchecked_transaction()?;
+    let conn: &Connection = &tx;
+    let source_hash = transaction.source_hash();
+    if queries::transaction_exists(conn, &source_hash)? {
+        return Ok(None);
+    }
+
+    let merchant_normalized = transaction.merchant_normalized();
+
+    let accepted = match categorization {
+        Some(c) if c.category_id.is_some() && still_applies(conn, &c)? => Some(c),
+        _ => None,
+    };
+    let categorization = accepted.or_else(|| {
+        // Auto-categorize
+        let input = CategorizationInput::from_parsed(transaction, &merchant_normalized);
+        categorize_transaction(conn, user_id, &input)
+            .map_err(|e| log::warn!("Failed to categorize {}: {}", transaction.merchant, e))
+            .ok()
+    });
+
+    // Link the card or balance it was paid with
+    let account_id = transaction.payment_method.as_ref().and_then(|method| {
+        queries::resolve_account(
+            conn,
+            user_id,
+            method.institution.as_deref(),
+            &method.account_type,
+            method.last4.as_deref(),
+        )
+        .map_err(|e| log::warn!("Failed to resolve account: {}", e))
+        .ok()
+    });
+
+    let merchant_id = merchants::resolve_merchant(conn, user_id, &transaction.merchant, &merchant_normalized)
+        .map_err(|e| log::warn!("Failed to resolve merchant: {}", e))
+        .ok();
+
+    // Insert transaction
+    let new_transaction = queries::NewTransaction {
+        category_id: categorization.as_ref().and_then(|c| c.category_id.as_deref()),
+        account_id: account_id.as_deref(),
+        merchant_id: merchant_id.as_deref(),
+        category_source: categorization.as_ref().map(|c| c.source.as_str()),
+        category_reason: categorization.as_ref().map(|c| c.reason.as_str()),
+        category_rule_id: categorization.as_ref().and_then(|c| c.rule_id.as_deref()),
+        merchant: &transaction.merchant,
+        merchant_normalized: &merchant_normalized,
+        amount: transaction.amount,
+        transaction_date: &transaction.transaction_date,
+        provider: &transaction.provider,
+        source_hash: &source_hash,
+        source_type,
+        source_ref: transaction.source_ref.as_deref(),
+        receipt_text: transaction.raw_text.as_deref(),
+        parse_confidence: Some(transaction.confidence),
+        sender: transaction.sender.as_deref(),
+        is_income: transaction.is_income,
+    };
+    let transaction_id = queries::insert_transaction(conn, user_id, &new_transaction)?;
+
+    // Insert items
+    for item in &transaction.items {
+        queries::insert_transaction_item(
+            conn,
+            &transaction_id,
+            &item.name,
+            item.quantity,
+            item.unit_price,
+            item.total_price,
+        )?;
+    }
+    for fee in &transaction.fees {
+        queries::insert_transaction_fee(conn, &transaction_id, &fee.kind, &fee.label, fee.amount)?;
+    }
+
+    // Item categories are a refinement; the transaction is saved without them if they fail
+    if transaction.items.len() > 1 {
+        let savepoint = tx.savepoint()?;
+        match items::apply_item_categories(&savepoint, user_id, &transaction_id) {
+            Ok(()) => savepoint.commit()?,
+            Err(e) => log::warn!("Failed to categorize items: {}", e),
+        }
+    }
+
+    tx.commit()?;
+    Ok(Some(transaction_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{category, open_seeded};
+    use crate::parser::types::{ParsedFee, ParsedItem};
+
+    fn receipt() -> ParsedTransaction {
+        let mut transaction =
+            ParsedTransaction::new("Amazon".to_string(), 2200, "2024-03-01".to_string(), "amazon".to_string());
+        transaction.source_ref = Some("msg-1".to_string());
+        for (name, price) in [("USB-C Cable", 1500), ("Phone Case", 500)] {
+            transaction.add_item(ParsedItem {
+                name: name.to_string(),
+                quantity: 1,
+                unit_price: price,
+                total_price: price,
+            });
+        }
+        transaction.fees.push(ParsedFee {
+            kind: "tax".to_string(),
+            label: "Sales tax".to_string(),
+            amount: 200,
+        });
+        transaction
+    }
+
+    #[test]
+    fn test_save_parsed_transaction() {
+        let conn = open_seeded();
+        let transaction = receipt();
+
+        let id = save_parsed_transaction(&conn, "local", &transaction, "gmail", None).unwrap().unwrap();
+        let detail = queries::get_transaction_detail(&conn, "local", &id).unwrap().unwrap();
+        assert_eq!(detail.source_type.as_deref(), Some("gmail"));
+        assert_eq!(detail.source_ref.as_deref(), Some("msg-1"));
+        assert_eq!(detail.items.len(), 2);
+        assert_eq!(detail.fees.len(), 1);
+        assert!(detail.category_source.is_some());
+
+        // The same receipt again, e.g. from a manual import, is a duplicate
+        assert!(save_parsed_transaction(&conn, "local", &transaction, "import", None).unwrap().is_none());
+
+        // A category accepted in the preview is kept
+        let mut other = receipt();
+        other.amount = 3000;
+        let travel = category(&conn, "Travel");
+        let id = save_parsed_transaction(&conn, "local", &other, "import", Some(chosen_during_import(&travel)))
+            .unwrap()
+            .unwrap();
+        let detail = queries::get_transaction_detail(&conn, "local", &id).unwrap().unwrap();
+        assert_eq!(detail.transaction.category_id.as_deref(), Some(travel.as_str()));
+        // A preview whose prediction failed is categorized on save
+        other.amount = 4000;
+        let unavailable = CategoryPrediction::unavailable().categorization;
+        let id = save_parsed_transaction(&conn, "local", &other, "import", Some(unavailable)).unwrap().unwrap();
+        let detail = queries::get_transaction_detail(&conn, "local", &id).unwrap().unwrap();
+        assert!(detail.transaction.category_id.is_some());
+    }
+
+    #[test]
+    fn test_failed_save_leaves_nothing_behind() {
+        let conn = open_seeded();
+        conn.execute_batch(
+            "CREATE TEMP TRIGGER reject_fees BEFORE INSERT ON transaction_fees
+             BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+        )
+        .unwrap();
+
+        assert!(save_parsed_transaction(&conn, "local", &receipt(), "gmail", None).is_err());
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("transactions"), 0);
+        assert_eq!(count("transaction_items"), 0);
+    }
+}
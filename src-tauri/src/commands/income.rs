@@ -0,0 +1,99 @@
+use tauri::AppHandle;
+
+use crate::db::{self, income};
+
+/// Record income entered by hand. Returns the new transaction ID.
+#[tauri::command]
+pub async fn add_income(
+    app_handle: AppHandle,
+    source: String,
+    amount: i64,
+    date: String,
+    category_id: Option<String>,
+    account_id: Option<String>,
+) -> Result<String, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    income::insert_manual_income(
+        &conn,
+        user_id,
+        &source,
+        amount,
+        &date,
+        category_id.as_deref(),
+        account_id.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Get all recurring income definitions
+#[tauri::command]
+pub async fn get_recurring_incomes(
+    app_handle: AppHandle,
+) -> Result<Vec<income::RecurringIncome>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    income::get_recurring_incomes(&conn, user_id).map_err(|e| e.to_string())
+}
+
+/// Create a recurring income and record any occurrences already due
+#[tauri::command]
+pub async fn create_recurring_income(
+    app_handle: AppHandle,
+    input: income::RecurringIncomeInput,
+) -> Result<income::RecurringIncome, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let created = income::create_recurring_income(&conn, user_id, &input).map_err(|e| e.to_string())?;
+    income::record_due_recurring_income(&conn, user_id, chrono::Local::now().date_naive())
+        .map_err(|e| e.to_string())?;
+
+    income::get_recurring_income(&conn, &created.id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Recurring income not found".to_string())
+}
+
+/// Update a recurring income and record any occurrences now due
+#[tauri::command]
+pub async fn update_recurring_income(
+    app_handle: AppHandle,
+    recurring_income_id: String,
+    input: income::RecurringIncomeInput,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    income::update_recurring_income(&conn, &recurring_income_id, &input).map_err(|e| e.to_string())?;
+    income::record_due_recurring_income(&conn, user_id, chrono::Local::now().date_naive())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a recurring income; transactions it already recorded are kept
+#[tauri::command]
+pub async fn delete_recurring_income(
+    app_handle: AppHandle,
+    recurring_income_id: String,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    income::delete_recurring_income(&conn, &recurring_income_id).map_err(|e| e.to_string())
+}
+
+/// Get income, expenses and net per week, month or year
+#[tauri::command]
+pub async fn get_cash_flow(
+    app_handle: AppHandle,
+    start_date: String,
+    end_date: String,
+    period: Option<income::CashFlowPeriod>,
+) -> Result<Vec<income::CashFlowEntry>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    income::get_cash_flow(&conn, user_id, &start_date, &end_date, period.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,97 @@
+use tauri::AppHandle;
+
+use crate::db::{self, merchants};
+
+/// Get all merchants with their transaction counts
+#[tauri::command]
+pub async fn get_merchants(app_handle: AppHandle) -> Result<Vec<merchants::Merchant>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    merchants::get_merchants(&conn, user_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_merchant_aliases(
+    app_handle: AppHandle,
+    merchant_id: String,
+) -> Result<Vec<merchants::MerchantAlias>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+
+    merchants::get_merchant_aliases(&conn, &merchant_id).map_err(|e| e.to_string())
+}
+
+/// Rename a merchant or set its default category, icon and logo
+#[tauri::command]
+pub async fn update_merchant(
+    app_handle: AppHandle,
+    merchant_id: String,
+    name: String,
+    default_category_id: Option<String>,
+    icon: Option<String>,
+    logo_url: Option<String>,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    merchants::update_merchant(
+        &conn,
+        &merchant_id,
+        &name,
+        default_category_id.as_deref(),
+        icon.as_deref(),
+        logo_url.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Add an alias so matching receipt text resolves to the merchant
+#[tauri::command]
+pub async fn add_merchant_alias(
+    app_handle: AppHandle,
+    merchant_id: String,
+    pattern: String,
+    match_type: String,
+) -> Result<merchants::MerchantAlias, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    merchants::add_merchant_alias(&conn, user_id, &merchant_id, &pattern, &match_type)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_merchant_alias(app_handle: AppHandle, alias_id: String) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    merchants::remove_merchant_alias(&conn, &alias_id).map_err(|e| e.to_string())
+}
+
+/// Merge merchants into `target_id`. Returns the number of transactions moved.
+#[tauri::command]
+pub async fn merge_merchants(
+    app_handle: AppHandle,
+    source_ids: Vec<String>,
+    target_id: String,
+) -> Result<i32, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let moved = merchants::merge_merchants(&conn, user_id, &source_ids, &target_id)
+        .map_err(|e| e.to_string())?;
+    Ok(moved as i32)
+}
+
+/// Move some of a merchant's transactions to a new merchant
+#[tauri::command]
+pub async fn split_merchant(
+    app_handle: AppHandle,
+    merchant_id: String,
+    transaction_ids: Vec<String>,
+    new_name: String,
+) -> Result<merchants::Merchant, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    merchants::split_merchant(&conn, user_id, &merchant_id, &transaction_ids, &new_name)
+        .map_err(|e| e.to_string())
+}
@@ -1,8 +1,14 @@
+pub mod accounts;
+pub mod backup;
 pub mod budgets;
 pub mod categories;
 pub mod dashboard;
+pub mod export;
 pub mod gmail;
 pub mod import;
+pub mod income;
+pub mod merchants;
 pub mod ocr;
 pub mod settings;
+pub mod statements;
 pub mod transactions;
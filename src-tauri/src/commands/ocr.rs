@@ -1,34 +1,81 @@
+use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 
-use crate::db;
+use super::import::{chosen_during_import, save_parsed_transaction, ImportResult};
+use crate::categorizer::{predict_category, CategorizationInput, CategoryPrediction};
+use crate::db::{self, queries};
 use crate::ocr;
 use crate::parser::types::ParsedTransaction;
-use crate::categorizer::categorize_transaction;
 
-/// Import a receipt from OCR text, returning parsed transaction for preview
+/// A receipt read by OCR, with the category it would be saved under
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OcrImportPreview {
+    pub transaction: ParsedTransaction,
+    pub prediction: CategoryPrediction,
+    /// The same receipt was already imported
+    pub is_duplicate: bool,
+}
+
+/// Import a receipt from OCR text, returning the parsed transaction and its
+/// suggested category for preview
 #[tauri::command]
 pub async fn import_receipt_from_ocr(
     app_handle: AppHandle,
     ocr_text: String,
     confidence: f64,
-) -> Result<ParsedTransaction, String> {
+) -> Result<OcrImportPreview, String> {
     let ocr_result = ocr::OcrResult::new(ocr_text, confidence);
 
     let transaction = ocr::parse_receipt_text(&ocr_result)
         .map_err(|e| format!("Failed to parse receipt text: {}", e))?;
 
-    // Try auto-categorization (best-effort, don't fail if it doesn't work)
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+    let is_duplicate = queries::transaction_exists(&conn, &transaction.source_hash()).map_err(|e| e.to_string())?;
+
     let merchant_normalized = transaction.merchant_normalized();
-    if let Ok(Some(category_id)) =
-        categorize_transaction(&conn, "local", &merchant_normalized, &transaction.provider)
-    {
-        log::info!(
-            "Auto-categorized OCR receipt '{}' -> {}",
-            transaction.merchant,
-            category_id
-        );
+    let prediction = predict_category(
+        &conn,
+        user_id,
+        &CategorizationInput::from_parsed(&transaction, &merchant_normalized),
+    )
+    .unwrap_or_else(|e| {
+        log::warn!("Failed to predict a category for {}: {}", transaction.merchant, e);
+        CategoryPrediction::unavailable()
+    });
+    if let Some(ref name) = prediction.category_name {
+        log::info!("Auto-categorized OCR receipt '{}' -> {}", transaction.merchant, name);
     }
 
-    Ok(transaction)
+    Ok(OcrImportPreview {
+        transaction,
+        prediction,
+        is_duplicate,
+    })
+}
+
+/// Save a previewed OCR receipt with its raw text and confidence. It's filed under
+/// `category_id` if the user picked one, else the previewed prediction.
+#[tauri::command]
+pub async fn confirm_ocr_import(
+    app_handle: AppHandle,
+    transaction: ParsedTransaction,
+    category_id: Option<String>,
+    prediction: Option<CategoryPrediction>,
+) -> Result<ImportResult, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let categorization = match category_id {
+        Some(ref id) => Some(chosen_during_import(id)),
+        None => prediction.map(|p| p.categorization),
+    };
+    let saved = save_parsed_transaction(&conn, user_id, &transaction, "ocr", categorization)
+        .map_err(|e| e.to_string())?;
+
+    Ok(ImportResult {
+        imported: if saved.is_some() { 1 } else { 0 },
+        skipped: if saved.is_some() { 0 } else { 1 },
+        errors: Vec::new(),
+    })
 }
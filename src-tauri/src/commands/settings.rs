@@ -1,11 +1,119 @@
-use tauri::AppHandle;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
 
-use crate::db::{self, queries};
+use crate::backup;
+use crate::categorizer::{defaults, rule_engine};
+use crate::db::{self, encryption, queries, rule_suggestions};
+use crate::gmail::{poller::GmailPollerState, tokens};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseStatus {
+    pub is_encrypted: bool,
+    /// True until `unlock_database` succeeds; every other database command fails with
+    /// `DATABASE_LOCKED` in the meantime
+    pub is_locked: bool,
+}
 
 /// Initialize the database (called on app startup)
 #[tauri::command]
 pub async fn initialize_database(app_handle: AppHandle) -> Result<(), String> {
-    db::initialize(&app_handle).await.map_err(|e| e.to_string())
+    db::initialize(&app_handle).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_database_status(app_handle: AppHandle) -> Result<DatabaseStatus, String> {
+    match db::state(&app_handle).get() {
+        Ok(database) => Ok(DatabaseStatus {
+            is_encrypted: database.is_encrypted(),
+            is_locked: false,
+        }),
+        Err(_) => {
+            let path = db::get_db_path(&app_handle).map_err(|e| e.to_string())?;
+            let is_encrypted = encryption::is_encrypted(&path).map_err(|e| e.to_string())?;
+            Ok(DatabaseStatus {
+                is_encrypted,
+                is_locked: is_encrypted,
+            })
+        }
+    }
+}
+
+/// Unlock an encrypted database. Fails with `WRONG_PASSPHRASE` if the passphrase is wrong.
+#[tauri::command]
+pub async fn unlock_database(app_handle: AppHandle, passphrase: String) -> Result<(), String> {
+    db::unlock(&app_handle, &passphrase).map_err(|e| e.to_string())?;
+
+    // Resume Gmail polling that couldn't start while locked
+    let has_tokens = {
+        let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+        tokens::has_tokens(&conn).unwrap_or(false)
+    };
+    if has_tokens {
+        app_handle.state::<GmailPollerState>().start();
+    }
+
+    log::info!("Database unlocked");
+    Ok(())
+}
+
+/// Close an encrypted database until it's unlocked again
+#[tauri::command]
+pub async fn lock_database(app_handle: AppHandle) -> Result<(), String> {
+    let is_encrypted = db::state(&app_handle)
+        .get()
+        .map(|database| database.is_encrypted())
+        .unwrap_or(true);
+    if !is_encrypted {
+        return Err("Database is not encrypted".to_string());
+    }
+
+    app_handle.state::<GmailPollerState>().stop();
+    db::lock(&app_handle).map_err(|e| e.to_string())?;
+
+    log::info!("Database locked");
+    Ok(())
+}
+
+/// Encrypt the plaintext database with a key derived from `passphrase`
+#[tauri::command]
+pub async fn enable_database_encryption(
+    app_handle: AppHandle,
+    passphrase: String,
+) -> Result<(), String> {
+    let state = db::state(&app_handle);
+    if state.get().map_err(|e| e.to_string())?.is_encrypted() {
+        return Err("Database is already encrypted".to_string());
+    }
+
+    encryption::rekey(&state, Some(&passphrase)).map_err(|e| e.to_string())?;
+    log::info!("Database encrypted");
+
+    // Automatic backups taken so far hold the plaintext data
+    let backups_dir = backup::get_backups_dir(&app_handle).map_err(|e| e.to_string())?;
+    backup::scheduler::remove_unencrypted_backups(&backups_dir).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Decrypt the database back to plaintext. Requires the current passphrase even
+/// though the database is unlocked, so an unattended session can't remove encryption.
+#[tauri::command]
+pub async fn disable_database_encryption(
+    app_handle: AppHandle,
+    passphrase: String,
+) -> Result<(), String> {
+    let state = db::state(&app_handle);
+    let database = state.get().map_err(|e| e.to_string())?;
+    if !database.is_encrypted() {
+        return Err("Database is not encrypted".to_string());
+    }
+
+    // Verify the passphrase against the file rather than trusting the open handle
+    drop(db::Database::open(database.path(), Some(&passphrase)).map_err(|e| e.to_string())?);
+    drop(database);
+
+    encryption::rekey(&state, None).map_err(|e| e.to_string())?;
+    log::info!("Database decrypted");
+    Ok(())
 }
 
 /// Get the current platform (desktop, ios, android)
@@ -28,9 +136,135 @@ pub async fn set_merchant_category_rule(
     category_id: String,
     is_exact_match: bool,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     queries::set_merchant_category_rule(&conn, user_id, &merchant_pattern, &category_id, is_exact_match)
         .map_err(|e| e.to_string())
 }
+
+/// Version and size of the bundled merchant knowledge base
+#[tauri::command]
+pub async fn get_merchant_knowledge_base_info() -> Result<defaults::KnowledgeBaseInfo, String> {
+    Ok(defaults::knowledge_base_info())
+}
+
+/// The user's changes to the bundled merchant knowledge base
+#[tauri::command]
+pub async fn get_merchant_pattern_overrides(
+    app_handle: AppHandle,
+) -> Result<Vec<queries::MerchantPatternOverride>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::get_merchant_pattern_overrides(&conn, user_id).map_err(|e| e.to_string())
+}
+
+/// Recategorize a knowledge base pattern, add a new one, or disable one with no category
+#[tauri::command]
+pub async fn set_merchant_pattern_override(
+    app_handle: AppHandle,
+    pattern: String,
+    category_id: Option<String>,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::set_merchant_pattern_override(&conn, user_id, &pattern, category_id.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Remove an override, restoring the bundled entry
+#[tauri::command]
+pub async fn delete_merchant_pattern_override(app_handle: AppHandle, override_id: String) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::delete_merchant_pattern_override(&conn, user_id, &override_id).map_err(|e| e.to_string())
+}
+
+/// Rules suggested from repeated manual recategorizations, awaiting a decision
+#[tauri::command]
+pub async fn get_rule_suggestions(app_handle: AppHandle) -> Result<Vec<rule_suggestions::RuleSuggestion>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    rule_suggestions::get_pending_rule_suggestions(&conn, user_id).map_err(|e| e.to_string())
+}
+
+/// Turn a suggestion into a rule, placed after the rules the user wrote
+#[tauri::command]
+pub async fn accept_rule_suggestion(
+    app_handle: AppHandle,
+    suggestion_id: String,
+) -> Result<queries::MerchantCategoryRule, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    rule_suggestions::accept_rule_suggestion(&conn, user_id, &suggestion_id).map_err(|e| e.to_string())
+}
+
+/// Dismiss a suggestion so it isn't offered again
+#[tauri::command]
+pub async fn reject_rule_suggestion(app_handle: AppHandle, suggestion_id: String) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    rule_suggestions::reject_rule_suggestion(&conn, user_id, &suggestion_id).map_err(|e| e.to_string())
+}
+
+/// All categorization rules in the order they're tried
+#[tauri::command]
+pub async fn get_category_rules(app_handle: AppHandle) -> Result<Vec<queries::MerchantCategoryRule>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::get_merchant_category_rules(&conn, user_id).map_err(|e| e.to_string())
+}
+
+/// Replace a rule's conditions, category, priority or enabled flag
+#[tauri::command]
+pub async fn update_category_rule(
+    app_handle: AppHandle,
+    rule_id: String,
+    rule: queries::RuleInput,
+) -> Result<queries::MerchantCategoryRule, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::update_category_rule(&conn, user_id, &rule_id, &rule).map_err(|e| e.to_string())
+}
+
+/// Delete a rule
+#[tauri::command]
+pub async fn delete_category_rule(app_handle: AppHandle, rule_id: String) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::delete_category_rule(&conn, user_id, &rule_id).map_err(|e| e.to_string())
+}
+
+/// Count the past transactions a rule would match and return the most recent ones
+#[tauri::command]
+pub async fn test_category_rule(
+    app_handle: AppHandle,
+    rule: queries::RuleInput,
+    limit: Option<usize>,
+) -> Result<rule_engine::RuleMatchPreview, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    rule_engine::preview_rule_matches(&conn, user_id, &rule, limit.unwrap_or(50)).map_err(|e| e.to_string())
+}
+
+/// Create a rule with any combination of merchant, provider, amount, weekday, item and sender conditions
+#[tauri::command]
+pub async fn create_category_rule(
+    app_handle: AppHandle,
+    rule: queries::RuleInput,
+) -> Result<queries::MerchantCategoryRule, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::create_category_rule(&conn, user_id, &rule).map_err(|e| e.to_string())
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::db::{self, statements as statement_queries, statements::match_status};
+use crate::statements::csv_import::{self, normalize_description};
+use crate::statements::matcher::{self, Reconciliation};
+use crate::statements::presets::{self, ColumnMapping, StatementPreset};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementImportResult {
+    pub import_id: String,
+    pub imported: i32,
+    /// Rows already imported from an earlier statement
+    pub duplicates: i32,
+    pub errors: Vec<String>,
+    pub detected_preset: Option<String>,
+    /// Rows matched to a receipt right after import
+    pub auto_matched: i32,
+}
+
+#[tauri::command]
+pub async fn list_statement_presets() -> Result<Vec<StatementPreset>, String> {
+    Ok(presets::statement_presets())
+}
+
+/// Import a bank or card statement CSV, then auto-match its rows to receipts.
+/// Columns come from `mapping`, else the `preset` ID, else are detected from the header.
+#[tauri::command]
+pub async fn import_statement_csv(
+    app_handle: AppHandle,
+    csv_content: String,
+    preset: Option<String>,
+    mapping: Option<ColumnMapping>,
+    account_label: Option<String>,
+) -> Result<StatementImportResult, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let mapping = match (mapping, preset) {
+        (Some(mapping), _) => Some(mapping),
+        (None, Some(id)) => Some(
+            presets::get_preset(&id)
+                .ok_or_else(|| format!("Unknown statement preset: {}", id))?
+                .mapping,
+        ),
+        (None, None) => None,
+    };
+    let parsed = csv_import::parse_statement(&csv_content, mapping.as_ref()).map_err(|e| e.to_string())?;
+
+    let import_id = uuid::Uuid::new_v4().to_string();
+    let mut imported = 0;
+    let mut duplicates = 0;
+    let mut occurrences: HashMap<&csv_import::StatementRow, usize> = HashMap::new();
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    for row in &parsed.rows {
+        let occurrence = occurrences.entry(row).or_insert(0);
+        let new_row = statement_queries::NewStatementTransaction {
+            import_id: &import_id,
+            account_label: account_label.as_deref(),
+            posted_date: &row.posted_date,
+            description: &row.description,
+            description_normalized: &normalize_description(&row.description),
+            amount: row.amount,
+            occurrence: *occurrence,
+        };
+        *occurrence += 1;
+
+        match statement_queries::insert_statement_transaction(&tx, user_id, &new_row)
+            .map_err(|e| e.to_string())?
+        {
+            Some(_) => imported += 1,
+            None => duplicates += 1,
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    let auto_matched = matcher::auto_match(&conn, user_id).map_err(|e| e.to_string())?;
+    log::info!(
+        "Imported {} statement rows ({} duplicates, {} auto-matched)",
+        imported,
+        duplicates,
+        auto_matched
+    );
+
+    Ok(StatementImportResult {
+        import_id,
+        imported,
+        duplicates,
+        errors: parsed.errors,
+        detected_preset: parsed.detected_preset,
+        auto_matched: auto_matched as i32,
+    })
+}
+
+/// Re-run matching for all unmatched statement rows, e.g. after new receipts sync
+#[tauri::command]
+pub async fn auto_match_statements(app_handle: AppHandle) -> Result<i32, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let matched = matcher::auto_match(&conn, user_id).map_err(|e| e.to_string())?;
+    Ok(matched as i32)
+}
+
+#[tauri::command]
+pub async fn get_reconciliation(
+    app_handle: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Reconciliation, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    matcher::get_reconciliation(&conn, user_id, start_date.as_deref(), end_date.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Accept an automatic match
+#[tauri::command]
+pub async fn confirm_statement_match(
+    app_handle: AppHandle,
+    statement_transaction_id: String,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    let statement = statement_queries::get_statement_transaction(&conn, &statement_transaction_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Statement transaction not found".to_string())?;
+    let Some(transaction_id) = statement.matched_transaction_id else {
+        return Err("Statement transaction has no match to confirm".to_string());
+    };
+
+    statement_queries::set_statement_match(
+        &conn,
+        &statement.id,
+        Some(&transaction_id),
+        match_status::CONFIRMED,
+        statement.match_score,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Manually link a statement row to a receipt, or mark it as having no receipt
+/// with `transaction_id: None`
+#[tauri::command]
+pub async fn set_statement_match(
+    app_handle: AppHandle,
+    statement_transaction_id: String,
+    transaction_id: Option<String>,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    let status = match transaction_id {
+        Some(_) => match_status::CONFIRMED,
+        None => match_status::NO_MATCH,
+    };
+    statement_queries::set_statement_match(
+        &conn,
+        &statement_transaction_id,
+        transaction_id.as_deref(),
+        status,
+        None,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Clear a match or a "no receipt" mark so the row is considered by auto-matching again
+#[tauri::command]
+pub async fn reset_statement_match(
+    app_handle: AppHandle,
+    statement_transaction_id: String,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    statement_queries::set_statement_match(
+        &conn,
+        &statement_transaction_id,
+        None,
+        match_status::UNMATCHED,
+        None,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Record an unmatched statement row as a transaction. Returns the new transaction ID.
+#[tauri::command]
+pub async fn create_transaction_from_statement(
+    app_handle: AppHandle,
+    statement_transaction_id: String,
+    category_id: Option<String>,
+) -> Result<String, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    matcher::create_transaction_from_statement(
+        &conn,
+        user_id,
+        &statement_transaction_id,
+        category_id.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 
-use crate::db::{self, queries};
+use crate::categorizer::{items, learn_from_assignment, recategorize, CategorySource};
+use crate::db::{self, queries, rule_suggestions::RuleSuggestion, search};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TransactionFilters {
@@ -9,6 +10,11 @@ pub struct TransactionFilters {
     #[serde(rename = "categoryId")]
     pub category_id: Option<String>,
     pub provider: Option<String>,
+    #[serde(rename = "accountId")]
+    pub account_id: Option<String>,
+    pub direction: Option<String>,
+    #[serde(rename = "categorySource")]
+    pub category_source: Option<String>,
     #[serde(rename = "startDate")]
     pub start_date: Option<String>,
     #[serde(rename = "endDate")]
@@ -25,6 +31,9 @@ impl From<TransactionFilters> for queries::TransactionFilters {
             search: f.search,
             category_id: f.category_id,
             provider: f.provider,
+            account_id: f.account_id,
+            direction: f.direction,
+            category_source: f.category_source,
             start_date: f.start_date,
             end_date: f.end_date,
             min_amount: f.min_amount,
@@ -33,39 +42,214 @@ impl From<TransactionFilters> for queries::TransactionFilters {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TransactionPageParams {
+    #[serde(rename = "sortBy")]
+    pub sort_by: Option<queries::TransactionSortKey>,
+    #[serde(rename = "sortDirection")]
+    pub sort_direction: Option<queries::SortDirection>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl From<TransactionPageParams> for queries::TransactionPageRequest {
+    fn from(p: TransactionPageParams) -> Self {
+        queries::TransactionPageRequest {
+            sort: p.sort_by.unwrap_or_default(),
+            direction: p.sort_direction.unwrap_or_default(),
+            cursor: p.cursor,
+            limit: p.limit.unwrap_or(queries::DEFAULT_PAGE_SIZE),
+            include_totals: true,
+        }
+    }
+}
+
 /// Get all transactions with optional filters
 #[tauri::command]
 pub async fn get_transactions(
     app_handle: AppHandle,
     filters: Option<TransactionFilters>,
 ) -> Result<Vec<queries::TransactionWithCategory>, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
     let user_id = "local";
 
     let db_filters = filters.unwrap_or_default().into();
     queries::get_transactions(&conn, user_id, &db_filters).map_err(|e| e.to_string())
 }
 
-/// Update a transaction's category
+/// Get one page of transactions plus count and sum for the whole filter
+#[tauri::command]
+pub async fn get_transactions_page(
+    app_handle: AppHandle,
+    filters: Option<TransactionFilters>,
+    page: Option<TransactionPageParams>,
+) -> Result<queries::TransactionPage, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let db_filters = filters.unwrap_or_default().into();
+    let page = page.unwrap_or_default().into();
+    queries::get_transactions_page(&conn, user_id, &db_filters, &page).map_err(|e| e.to_string())
+}
+
+/// Full-text search over merchant, notes, item names and receipt text, ranked by relevance
+#[tauri::command]
+pub async fn search_transactions(
+    app_handle: AppHandle,
+    query: String,
+    filters: Option<TransactionFilters>,
+    limit: Option<i64>,
+) -> Result<Vec<search::TransactionSearchHit>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let db_filters = filters.unwrap_or_default().into();
+    search::search_transactions(&conn, user_id, &query, &db_filters, limit.unwrap_or(50))
+        .map_err(|e| e.to_string())
+}
+
+/// Update a transaction's category. Returns a rule suggestion once the merchant has
+/// been moved to the same category consistently.
 #[tauri::command]
 pub async fn update_transaction_category(
     app_handle: AppHandle,
     transaction_id: String,
     category_id: Option<String>,
+) -> Result<Option<RuleSuggestion>, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::update_transaction_category(
+        &conn,
+        &transaction_id,
+        category_id.as_deref(),
+        CategorySource::Manual.as_str(),
+        "Set manually",
+        None,
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Learning is best-effort; the category change already succeeded
+    Ok(learn_from_assignment(&conn, user_id, &transaction_id)
+        .map_err(|e| log::warn!("Failed to learn from category change: {}", e))
+        .ok()
+        .flatten())
+}
+
+/// Categorize the filtered transactions again and show which categories would change
+#[tauri::command]
+pub async fn preview_recategorization(
+    app_handle: AppHandle,
+    filters: Option<TransactionFilters>,
+    include_manual: Option<bool>,
+) -> Result<Vec<recategorize::RecategorizationChange>, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let db_filters = filters.unwrap_or_default().into();
+    recategorize::preview_recategorization(&conn, user_id, &db_filters, include_manual.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+/// Re-categorize the filtered transactions, optionally only the given ones from the preview
+#[tauri::command]
+pub async fn apply_recategorization(
+    app_handle: AppHandle,
+    filters: Option<TransactionFilters>,
+    include_manual: Option<bool>,
+    transaction_ids: Option<Vec<String>>,
+) -> Result<Vec<recategorize::RecategorizationChange>, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let db_filters = filters.unwrap_or_default().into();
+    recategorize::apply_recategorization(
+        &conn,
+        user_id,
+        &db_filters,
+        include_manual.unwrap_or(false),
+        transaction_ids.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Set or clear the account a transaction was paid with
+#[tauri::command]
+pub async fn update_transaction_account(
+    app_handle: AppHandle,
+    transaction_id: String,
+    account_id: Option<String>,
+) -> Result<(), String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+
+    queries::update_transaction_account(&conn, &transaction_id, account_id.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Mark a transaction as income or expense
+#[tauri::command]
+pub async fn update_transaction_direction(
+    app_handle: AppHandle,
+    transaction_id: String,
+    direction: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
 
-    queries::update_transaction_category(&conn, &transaction_id, category_id.as_deref())
+    queries::update_transaction_direction(&conn, &transaction_id, &direction)
         .map_err(|e| e.to_string())
 }
 
+/// Get a transaction with its items, fees, source and categorization reason
+#[tauri::command]
+pub async fn get_transaction_detail(
+    app_handle: AppHandle,
+    transaction_id: String,
+) -> Result<queries::TransactionDetail, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    queries::get_transaction_detail(&conn, user_id, &transaction_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Transaction not found".to_string())
+}
+
+/// Categorize a transaction's items again and re-split it by category, returning
+/// the updated detail
+#[tauri::command]
+pub async fn recategorize_transaction_items(
+    app_handle: AppHandle,
+    transaction_id: String,
+) -> Result<queries::TransactionDetail, String> {
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    items::categorize_transaction_items(&conn, user_id, &transaction_id).map_err(|e| e.to_string())?;
+    queries::get_transaction_detail(&conn, user_id, &transaction_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Transaction not found".to_string())
+}
+
+/// Search purchased items by name, with totals across all matching orders
+#[tauri::command]
+pub async fn search_items(
+    app_handle: AppHandle,
+    query: String,
+    filters: Option<TransactionFilters>,
+) -> Result<search::ItemSearchResult, String> {
+    let conn = db::reader(&app_handle).map_err(|e| e.to_string())?;
+    let user_id = "local";
+
+    let db_filters = filters.unwrap_or_default().into();
+    search::search_items(&conn, user_id, &query, &db_filters).map_err(|e| e.to_string())
+}
+
 /// Delete a transaction
 #[tauri::command]
 pub async fn delete_transaction(
     app_handle: AppHandle,
     transaction_id: String,
 ) -> Result<(), String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(&app_handle).map_err(|e| e.to_string())?;
 
     queries::delete_transaction(&conn, &transaction_id).map_err(|e| e.to_string())
 }
@@ -0,0 +1,214 @@
+use std::io::Read;
+use std::path::Path;
+
+use rusqlite::params;
+
+use super::{replace_database_file, sibling_path, Database, DbError, DbResult, DbState};
+
+/// Every plaintext SQLite file starts with this header; SQLCipher files don't
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether the database file at `path` is encrypted. A missing or empty file is not.
+pub fn is_encrypted(path: &Path) -> DbResult<bool> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(&header != SQLITE_HEADER),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Rewrite the open database with a new passphrase and reopen it in `state`.
+///
+/// `passphrase: None` produces a plaintext database. SQLCipher derives the encryption
+/// key from the passphrase (PBKDF2-HMAC-SHA512), so the passphrase itself is never stored.
+/// The copy is written next to the database and only swapped in once complete, so a
+/// failed export leaves the original open and untouched.
+pub fn rekey(state: &DbState, passphrase: Option<&str>) -> DbResult<()> {
+    if passphrase.is_some_and(|p| p.is_empty()) {
+        return Err(DbError::InvalidInput("Passphrase cannot be empty".to_string()));
+    }
+
+    // Stop handing out connections and wait for the ones checked out, so nothing is
+    // written after the export
+    let database = state.set(None).ok_or(DbError::Locked)?;
+    if let Err(e) = database.wait_until_unused() {
+        state.set(Some(database));
+        return Err(e);
+    }
+
+    let path = database.path().to_path_buf();
+    let rekeyed_path = sibling_path(&path, "rekey");
+    let exported = export(&database, &rekeyed_path, passphrase.unwrap_or(""));
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&rekeyed_path);
+        state.set(Some(database));
+        return Err(e);
+    }
+
+    replace_database_file(state, database, &rekeyed_path, passphrase)?;
+
+    if passphrase.is_some() {
+        remove_plaintext_backups(&path)?;
+    }
+    Ok(())
+}
+
+fn export(database: &Database, target: &Path, key: &str) -> DbResult<()> {
+    if target.exists() {
+        std::fs::remove_file(target)?;
+    }
+
+    let conn = database.writer()?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+        params![target.to_string_lossy(), key],
+    )?;
+
+    let result = conn
+        .query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+        .map_err(DbError::from);
+
+    conn.execute("DETACH DATABASE rekeyed", [])?;
+    result
+}
+
+/// Copies of a plaintext database kept next to it (pre-migration and pre-restore
+/// backups, e.g. `email_budget.db.pre-v3-migration.bak`) would leak everything
+/// encryption is meant to protect, so they're deleted once the encrypted copy is in place
+fn remove_plaintext_backups(db_path: &Path) -> DbResult<()> {
+    let (Some(dir), Some(file_name)) = (db_path.parent(), db_path.file_name()) else {
+        return Ok(());
+    };
+    let prefix = format!("{}.pre-", file_name.to_string_lossy());
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry_path = entry?.path();
+        let name = entry_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if name.starts_with(&prefix) && name.ends_with(".bak") && !is_encrypted(&entry_path)? {
+            std::fs::remove_file(&entry_path)?;
+            log::info!("Removed plaintext backup {:?}", entry_path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_decrypt_round_trip() {
+        let dir = std::env::temp_dir().join(format!("email_budget_enc_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("email_budget.db");
+
+        let database = Database::open(&path, None).unwrap();
+        super::super::schema::run_migrations(&database.writer().unwrap()).unwrap();
+        database
+            .writer()
+            .unwrap()
+            .execute(
+                "INSERT INTO categories (id, name, icon, color) VALUES ('c1', 'Secret', 'lock', '#000')",
+                [],
+            )
+            .unwrap();
+
+        let state = DbState::default();
+        state.set(Some(database));
+        rekey(&state, Some("correct horse")).unwrap();
+        assert!(is_encrypted(&path).unwrap());
+        state.set(None).unwrap().wait_until_unused().unwrap();
+
+        assert!(matches!(Database::open(&path, None), Err(DbError::Locked)));
+        assert!(matches!(
+            Database::open(&path, Some("wrong")),
+            Err(DbError::WrongPassphrase)
+        ));
+
+        state.set(Some(Database::open(&path, Some("correct horse")).unwrap()));
+        rekey(&state, None).unwrap();
+        assert!(!is_encrypted(&path).unwrap());
+
+        let database = state.set(None).unwrap();
+        let name: String = database
+            .reader()
+            .unwrap()
+            .query_row("SELECT name FROM categories WHERE id = 'c1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "Secret");
+
+        drop(database);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encrypting_removes_plaintext_copies() {
+        let dir = std::env::temp_dir().join(format!("email_budget_enc_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("email_budget.db");
+        let database = Database::open(&path, None).unwrap();
+        for copy in ["email_budget.db.pre-v3-migration.bak", "email_budget.db.pre-restore.bak"] {
+            database.snapshot_to(&dir.join(copy)).unwrap();
+        }
+        std::fs::write(dir.join("notes.bak"), "unrelated").unwrap();
+
+        let state = DbState::default();
+        state.set(Some(database));
+        rekey(&state, Some("correct horse")).unwrap();
+
+        let mut remaining: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".bak"))
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["notes.bak"]);
+
+        state.set(None).unwrap().wait_until_unused().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_replace_reopens_original() {
+        let dir = std::env::temp_dir().join(format!("email_budget_enc_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("email_budget.db");
+        let database = Database::open(&path, None).unwrap();
+        database
+            .writer()
+            .unwrap()
+            .execute_batch("CREATE TABLE kept (id INTEGER); INSERT INTO kept VALUES (1);")
+            .unwrap();
+
+        // An encrypted replacement can't be opened without its key
+        let replacement = dir.join("replacement.db");
+        export(&database, &replacement, "secret").unwrap();
+
+        let state = DbState::default();
+        let result = replace_database_file(&state, database, &replacement, None);
+        assert!(matches!(result, Err(DbError::Locked)));
+        assert!(!replacement.exists());
+
+        let count: i64 = state
+            .get()
+            .unwrap()
+            .reader()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM kept", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        state.set(None).unwrap().wait_until_unused().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
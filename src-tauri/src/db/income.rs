@@ -0,0 +1,542 @@
+use chrono::{Datelike, Months, NaiveDate};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::queries::{self, NewTransaction};
+use super::{merchants, DbError, DbResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringIncome {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// Cents per occurrence
+    pub amount: i64,
+    /// 'weekly', 'biweekly', 'semimonthly' or 'monthly'
+    pub frequency: String,
+    pub start_date: String,
+    /// Next occurrence not yet recorded as a transaction
+    pub next_date: String,
+    pub category_id: Option<String>,
+    pub account_id: Option<String>,
+    pub enabled: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Fields for creating or updating a recurring income
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringIncomeInput {
+    pub name: String,
+    pub amount: i64,
+    pub frequency: String,
+    /// First occurrence (YYYY-MM-DD). Past dates are backfilled.
+    pub start_date: String,
+    pub category_id: Option<String>,
+    pub account_id: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CashFlowPeriod {
+    Weekly,
+    #[default]
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashFlowEntry {
+    /// First day of the period (weeks start on Monday)
+    pub period_start: String,
+    pub income: i64,
+    pub expenses: i64,
+    /// Income minus expenses
+    pub net: i64,
+    /// Share of income not spent, or None without income
+    pub savings_rate: Option<f64>,
+}
+
+const FREQUENCIES: [&str; 4] = ["weekly", "biweekly", "semimonthly", "monthly"];
+
+/// Recurring income occurrences are recorded with this source type
+const RECURRING_SOURCE_TYPE: &str = "recurring";
+
+pub fn savings_rate(income: i64, expenses: i64) -> Option<f64> {
+    if income > 0 {
+        Some((income - expenses) as f64 / income as f64)
+    } else {
+        None
+    }
+}
+
+// ============================================================================
+// Manual income
+// ============================================================================
+
+/// Record income entered by hand, e.g. a paycheck or a cash gift.
+/// Without `category_id` it goes in the "Income" category.
+pub fn insert_manual_income(
+    conn: &Connection,
+    user_id: &str,
+    source: &str,
+    amount: i64,
+    date: &str,
+    category_id: Option<&str>,
+    account_id: Option<&str>,
+) -> DbResult<String> {
+    if amount <= 0 {
+        return Err(DbError::InvalidInput("Income amount must be positive".to_string()));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| DbError::InvalidInput(format!("Invalid date: {}", date)))?;
+
+    let category_id = match category_id {
+        Some(id) => Some(id.to_string()),
+        None => income_category_id(conn, user_id)?,
+    };
+    let merchant_normalized = crate::parser::types::normalize_merchant(source);
+    let source_hash = format!("manual:{}", uuid::Uuid::new_v4());
+    let merchant_id = merchants::resolve_merchant(conn, user_id, source, &merchant_normalized)?;
+
+    queries::insert_transaction(
+        conn,
+        user_id,
+        &NewTransaction {
+            category_id: category_id.as_deref(),
+            account_id,
+            merchant_id: Some(&merchant_id),
+            category_source: Some("manual"),
+            category_reason: Some("Entered manually"),
+            category_rule_id: None,
+            merchant: source,
+            merchant_normalized: &merchant_normalized,
+            amount,
+            transaction_date: date,
+            provider: "manual",
+            source_hash: &source_hash,
+            source_type: "manual",
+            source_ref: None,
+            receipt_text: None,
+            parse_confidence: None,
+            sender: None,
+            is_income: true,
+        },
+    )
+}
+
+fn income_category_id(conn: &Connection, user_id: &str) -> DbResult<Option<String>> {
+    queries::find_category_by_name(conn, user_id, "Income")
+}
+
+// ============================================================================
+// Recurring income
+// ============================================================================
+
+const RECURRING_COLUMNS: &str = "id, user_id, name, amount, frequency, start_date, next_date,
+     category_id, account_id, enabled, created_at, updated_at";
+
+fn recurring_from_row(row: &rusqlite::Row) -> rusqlite::Result<RecurringIncome> {
+    Ok(RecurringIncome {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        amount: row.get(3)?,
+        frequency: row.get(4)?,
+        start_date: row.get(5)?,
+        next_date: row.get(6)?,
+        category_id: row.get(7)?,
+        account_id: row.get(8)?,
+        enabled: row.get::<_, i32>(9)? != 0,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+pub fn get_recurring_incomes(conn: &Connection, user_id: &str) -> DbResult<Vec<RecurringIncome>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM recurring_income WHERE user_id = ?1 ORDER BY name",
+        RECURRING_COLUMNS
+    ))?;
+
+    let incomes = stmt
+        .query_map([user_id], recurring_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(incomes)
+}
+
+pub fn get_recurring_income(conn: &Connection, id: &str) -> DbResult<Option<RecurringIncome>> {
+    let income = conn
+        .query_row(
+            &format!("SELECT {} FROM recurring_income WHERE id = ?1", RECURRING_COLUMNS),
+            [id],
+            recurring_from_row,
+        )
+        .optional()?;
+
+    Ok(income)
+}
+
+fn validate_recurring(input: &RecurringIncomeInput) -> DbResult<NaiveDate> {
+    if input.amount <= 0 {
+        return Err(DbError::InvalidInput("Income amount must be positive".to_string()));
+    }
+    if !FREQUENCIES.contains(&input.frequency.as_str()) {
+        return Err(DbError::InvalidInput(format!(
+            "Unknown frequency: {}",
+            input.frequency
+        )));
+    }
+    NaiveDate::parse_from_str(&input.start_date, "%Y-%m-%d")
+        .map_err(|_| DbError::InvalidInput(format!("Invalid date: {}", input.start_date)))
+}
+
+pub fn create_recurring_income(
+    conn: &Connection,
+    user_id: &str,
+    input: &RecurringIncomeInput,
+) -> DbResult<RecurringIncome> {
+    validate_recurring(input)?;
+    let id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO recurring_income
+         (id, user_id, name, amount, frequency, start_date, next_date, category_id, account_id, enabled)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7, ?8, ?9)",
+        params![
+            &id,
+            user_id,
+            &input.name,
+            input.amount,
+            &input.frequency,
+            &input.start_date,
+            &input.category_id,
+            &input.account_id,
+            input.enabled
+        ],
+    )?;
+
+    get_recurring_income(conn, &id)?.ok_or(DbError::NotFound)
+}
+
+/// Update a recurring income. Changing the start date or frequency restarts the
+/// schedule from the start date; occurrences already recorded are kept.
+pub fn update_recurring_income(
+    conn: &Connection,
+    id: &str,
+    input: &RecurringIncomeInput,
+) -> DbResult<()> {
+    validate_recurring(input)?;
+    let existing = get_recurring_income(conn, id)?.ok_or(DbError::NotFound)?;
+
+    let next_date = if existing.start_date != input.start_date || existing.frequency != input.frequency {
+        first_unrecorded_date(conn, &existing.id, &input.start_date, &input.frequency)?
+    } else {
+        existing.next_date
+    };
+
+    conn.execute(
+        "UPDATE recurring_income
+         SET name = ?2, amount = ?3, frequency = ?4, start_date = ?5, next_date = ?6,
+             category_id = ?7, account_id = ?8, enabled = ?9, updated_at = datetime('now')
+         WHERE id = ?1",
+        params![
+            id,
+            &input.name,
+            input.amount,
+            &input.frequency,
+            &input.start_date,
+            &next_date,
+            &input.category_id,
+            &input.account_id,
+            input.enabled
+        ],
+    )?;
+    Ok(())
+}
+
+/// First occurrence on the schedule after the last one already recorded
+fn first_unrecorded_date(
+    conn: &Connection,
+    recurring_id: &str,
+    start_date: &str,
+    frequency: &str,
+) -> DbResult<String> {
+    let last_recorded: Option<String> = conn.query_row(
+        "SELECT MAX(transaction_date) FROM transactions
+         WHERE source_type = ?1 AND source_ref = ?2",
+        params![RECURRING_SOURCE_TYPE, recurring_id],
+        |row| row.get(0),
+    )?;
+
+    let start = parse_date(start_date)?;
+    let mut date = start;
+    if let Some(last) = last_recorded {
+        let last = parse_date(&last)?;
+        while date <= last {
+            date = next_occurrence(date, start, frequency);
+        }
+    }
+    Ok(date.format("%Y-%m-%d").to_string())
+}
+
+/// Delete a recurring income. Occurrences already recorded are kept.
+pub fn delete_recurring_income(conn: &Connection, id: &str) -> DbResult<()> {
+    conn.execute("DELETE FROM recurring_income WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+/// Record every enabled recurring income occurrence dated on or before `today`.
+/// Returns the number of transactions created.
+pub fn record_due_recurring_income(
+    conn: &Connection,
+    user_id: &str,
+    today: NaiveDate,
+) -> DbResult<usize> {
+    let due: Vec<RecurringIncome> = get_recurring_incomes(conn, user_id)?
+        .into_iter()
+        .filter(|r| r.enabled)
+        .collect();
+
+    let tx = conn.unchecked_transaction()?;
+    let default_category = income_category_id(&tx, user_id)?;
+    let mut recorded = 0;
+
+    for income in due {
+        let start = parse_date(&income.start_date)?;
+        let mut date = parse_date(&income.next_date)?;
+        if date > today {
+            continue;
+        }
+
+        let merchant_normalized = crate::parser::types::normalize_merchant(&income.name);
+        let merchant_id = merchants::resolve_merchant(&tx, user_id, &income.name, &merchant_normalized)?;
+        let category_id = income.category_id.clone().or_else(|| default_category.clone());
+        while date <= today {
+            let date_str = date.format("%Y-%m-%d").to_string();
+            queries::insert_transaction(
+                &tx,
+                user_id,
+                &NewTransaction {
+                    category_id: category_id.as_deref(),
+                    account_id: income.account_id.as_deref(),
+                    merchant_id: Some(&merchant_id),
+                    category_source: Some("manual"),
+                    category_reason: Some("Recurring income"),
+                    category_rule_id: None,
+                    merchant: &income.name,
+                    merchant_normalized: &merchant_normalized,
+                    amount: income.amount,
+                    transaction_date: &date_str,
+                    provider: "manual",
+                    source_hash: &format!("{}:{}:{}", RECURRING_SOURCE_TYPE, income.id, date_str),
+                    source_type: RECURRING_SOURCE_TYPE,
+                    source_ref: Some(&income.id),
+                    receipt_text: None,
+                    parse_confidence: None,
+                    sender: None,
+                    is_income: true,
+                },
+            )?;
+            recorded += 1;
+            date = next_occurrence(date, start, &income.frequency);
+        }
+
+        tx.execute(
+            "UPDATE recurring_income SET next_date = ?2, updated_at = datetime('now') WHERE id = ?1",
+            params![&income.id, date.format("%Y-%m-%d").to_string()],
+        )?;
+    }
+
+    tx.commit()?;
+    if recorded > 0 {
+        log::info!("Recorded {} recurring income transactions", recorded);
+    }
+    Ok(recorded)
+}
+
+/// The occurrence after `date`. Monthly income keeps `start`'s day of month, clamped
+/// to short months; semimonthly income falls on the 15th and the last day of the month.
+fn next_occurrence(date: NaiveDate, start: NaiveDate, frequency: &str) -> NaiveDate {
+    match frequency {
+        "weekly" => date + chrono::Duration::days(7),
+        "biweekly" => date + chrono::Duration::days(14),
+        "semimonthly" => {
+            let month_end = last_day_of_month(date);
+            if date.day() < 15 {
+                date.with_day(15).unwrap()
+            } else if date < month_end {
+                month_end
+            } else {
+                (date + chrono::Duration::days(1)).with_day(15).unwrap()
+            }
+        }
+        _ => {
+            let first_of_next = date.with_day(1).unwrap() + Months::new(1);
+            let day = start.day().min(last_day_of_month(first_of_next).day());
+            first_of_next.with_day(day).unwrap()
+        }
+    }
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap() + Months::new(1) - chrono::Duration::days(1)
+}
+
+fn parse_date(date: &str) -> DbResult<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| DbError::InvalidInput(format!("Invalid date: {}", date)))
+}
+
+// ============================================================================
+// Cash flow
+// ============================================================================
+
+/// Income, expenses and net per period between `start_date` and `end_date`.
+/// Periods with no transactions are included with zeros.
+pub fn get_cash_flow(
+    conn: &Connection,
+    user_id: &str,
+    start_date: &str,
+    end_date: &str,
+    period: CashFlowPeriod,
+) -> DbResult<Vec<CashFlowEntry>> {
+    let period_expr = match period {
+        CashFlowPeriod::Weekly => {
+            "date(transaction_date, '-' || ((CAST(strftime('%w', transaction_date) AS INTEGER) + 6) % 7) || ' days')"
+        }
+        CashFlowPeriod::Monthly => "strftime('%Y-%m-01', transaction_date)",
+        CashFlowPeriod::Yearly => "strftime('%Y-01-01', transaction_date)",
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} as period_start,
+                COALESCE(SUM(CASE WHEN direction = 'income' THEN amount ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN direction = 'expense' THEN amount ELSE 0 END), 0)
+         FROM transactions
+         WHERE user_id = ?1 AND transaction_date >= ?2 AND transaction_date <= ?3
+         GROUP BY period_start",
+        period_expr
+    ))?;
+
+    let totals: std::collections::HashMap<String, (i64, i64)> = stmt
+        .query_map(params![user_id, start_date, end_date], |row| {
+            Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let start = parse_date(start_date)?;
+    let end = parse_date(end_date)?;
+    let mut period_start = match period {
+        CashFlowPeriod::Weekly => {
+            start - chrono::Duration::days(start.weekday().num_days_from_monday() as i64)
+        }
+        CashFlowPeriod::Monthly => start.with_day(1).unwrap(),
+        CashFlowPeriod::Yearly => NaiveDate::from_ymd_opt(start.year(), 1, 1).unwrap(),
+    };
+
+    let mut entries = Vec::new();
+    while period_start <= end {
+        let key = period_start.format("%Y-%m-%d").to_string();
+        let (income, expenses) = totals.get(&key).copied().unwrap_or((0, 0));
+        entries.push(CashFlowEntry {
+            period_start: key,
+            income,
+            expenses,
+            net: income - expenses,
+            savings_rate: savings_rate(income, expenses),
+        });
+        period_start = match period {
+            CashFlowPeriod::Weekly => period_start + chrono::Duration::days(7),
+            CashFlowPeriod::Monthly => period_start + Months::new(1),
+            CashFlowPeriod::Yearly => period_start + Months::new(12),
+        };
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let start = date("2024-01-31");
+        assert_eq!(next_occurrence(start, start, "monthly"), date("2024-02-29"));
+        assert_eq!(next_occurrence(date("2024-02-29"), start, "monthly"), date("2024-03-31"));
+        assert_eq!(next_occurrence(date("2024-02-01"), start, "semimonthly"), date("2024-02-15"));
+        assert_eq!(next_occurrence(date("2024-02-15"), start, "semimonthly"), date("2024-02-29"));
+        assert_eq!(next_occurrence(date("2024-02-29"), start, "semimonthly"), date("2024-03-15"));
+        assert_eq!(next_occurrence(start, start, "biweekly"), date("2024-02-14"));
+    }
+
+    #[test]
+    fn test_recurring_income_and_cash_flow() {
+        let conn = Connection::open_in_memory().unwrap();
+        schema::run_migrations(&conn).unwrap();
+        schema::seed_default_categories(&conn).unwrap();
+
+        let paycheck = create_recurring_income(
+            &conn,
+            "local",
+            &RecurringIncomeInput {
+                name: "Acme payroll".to_string(),
+                amount: 250000,
+                frequency: "semimonthly".to_string(),
+                start_date: "2024-01-15".to_string(),
+                category_id: None,
+                account_id: None,
+                enabled: true,
+            },
+        )
+        .unwrap();
+
+        // Jan 15, Jan 31, Feb 15
+        assert_eq!(record_due_recurring_income(&conn, "local", date("2024-02-20")).unwrap(), 3);
+        assert_eq!(record_due_recurring_income(&conn, "local", date("2024-02-20")).unwrap(), 0);
+        let paycheck = get_recurring_income(&conn, &paycheck.id).unwrap().unwrap();
+        assert_eq!(paycheck.next_date, "2024-02-29");
+
+        insert_manual_income(&conn, "local", "Tax refund", 10000, "2024-02-02", None, None).unwrap();
+        queries::insert_transaction(
+            &conn,
+            "local",
+            &NewTransaction {
+                merchant: "Grocer",
+                merchant_normalized: "grocer",
+                amount: 300000,
+                transaction_date: "2024-01-20",
+                provider: "generic",
+                source_hash: "grocer",
+                source_type: "import",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let flow = get_cash_flow(&conn, "local", "2024-01-01", "2024-03-31", CashFlowPeriod::Monthly).unwrap();
+        assert_eq!(flow.len(), 3);
+        assert_eq!(flow[0].period_start, "2024-01-01");
+        assert_eq!((flow[0].income, flow[0].expenses, flow[0].net), (500000, 300000, 200000));
+        assert_eq!(flow[0].savings_rate, Some(0.4));
+        assert_eq!(flow[1].income, 260000);
+        assert_eq!(flow[2].savings_rate, None);
+
+        // Income doesn't count as spending
+        assert_eq!(queries::get_total_spent(&conn, "local", "2024-01-01", "2024-03-31").unwrap(), 300000);
+        assert_eq!(queries::get_total_income(&conn, "local", "2024-01-01", "2024-03-31").unwrap(), 760000);
+    }
+}
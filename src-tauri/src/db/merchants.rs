@@ -0,0 +1,491 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::{DbError, DbResult};
+use crate::parser::types::{canonical_merchant_name, normalize_merchant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Merchant {
+    pub id: String,
+    pub user_id: String,
+    /// Canonical display name
+    pub name: String,
+    pub name_normalized: String,
+    pub default_category_id: Option<String>,
+    pub icon: Option<String>,
+    pub logo_url: Option<String>,
+    /// Number of transactions linked to the merchant
+    pub transaction_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantAlias {
+    pub id: String,
+    pub merchant_id: String,
+    /// Normalized merchant text
+    pub pattern: String,
+    /// 'exact', 'prefix' or 'contains'
+    pub match_type: String,
+    pub created_at: String,
+}
+
+const MATCH_TYPES: [&str; 3] = ["exact", "prefix", "contains"];
+
+const MERCHANT_COLUMNS: &str = "m.id, m.user_id, m.name, m.name_normalized, m.default_category_id,
+     m.icon, m.logo_url,
+     (SELECT COUNT(*) FROM transactions t WHERE t.merchant_id = m.id),
+     m.created_at, m.updated_at";
+
+fn merchant_from_row(row: &rusqlite::Row) -> rusqlite::Result<Merchant> {
+    Ok(Merchant {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        name_normalized: row.get(3)?,
+        default_category_id: row.get(4)?,
+        icon: row.get(5)?,
+        logo_url: row.get(6)?,
+        transaction_count: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+pub fn get_merchants(conn: &Connection, user_id: &str) -> DbResult<Vec<Merchant>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM merchants m WHERE m.user_id = ?1 ORDER BY m.name COLLATE NOCASE",
+        MERCHANT_COLUMNS
+    ))?;
+
+    let merchants = stmt
+        .query_map([user_id], merchant_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(merchants)
+}
+
+pub fn get_merchant(conn: &Connection, id: &str) -> DbResult<Option<Merchant>> {
+    let merchant = conn
+        .query_row(
+            &format!("SELECT {} FROM merchants m WHERE m.id = ?1", MERCHANT_COLUMNS),
+            [id],
+            merchant_from_row,
+        )
+        .optional()?;
+
+    Ok(merchant)
+}
+
+pub fn get_merchant_aliases(conn: &Connection, merchant_id: &str) -> DbResult<Vec<MerchantAlias>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, merchant_id, pattern, match_type, created_at
+         FROM merchant_aliases WHERE merchant_id = ?1
+         ORDER BY match_type, pattern",
+    )?;
+
+    let aliases = stmt
+        .query_map([merchant_id], |row| {
+            Ok(MerchantAlias {
+                id: row.get(0)?,
+                merchant_id: row.get(1)?,
+                pattern: row.get(2)?,
+                match_type: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(aliases)
+}
+
+/// Find the merchant for a transaction's normalized merchant text without creating one.
+///
+/// Aliases are tried first (exact, then the longest prefix, then the longest contained
+/// pattern), then a merchant whose normalized name equals the text or is its leading
+/// words, so "chipotle mexican grill" finds "Chipotle".
+pub fn find_merchant(
+    conn: &Connection,
+    user_id: &str,
+    merchant_normalized: &str,
+) -> DbResult<Option<String>> {
+    if merchant_normalized.is_empty() {
+        return Ok(None);
+    }
+
+    let by_alias = conn
+        .query_row(
+            "SELECT merchant_id FROM merchant_aliases
+             WHERE user_id = ?1
+               AND ((match_type = 'exact' AND pattern = ?2)
+                 OR (match_type = 'prefix' AND substr(?2, 1, length(pattern)) = pattern)
+                 OR (match_type = 'contains' AND instr(?2, pattern) > 0))
+             ORDER BY CASE match_type WHEN 'exact' THEN 0 WHEN 'prefix' THEN 1 ELSE 2 END,
+                      length(pattern) DESC
+             LIMIT 1",
+            params![user_id, merchant_normalized],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    if by_alias.is_some() {
+        return Ok(by_alias);
+    }
+
+    let by_name = conn
+        .query_row(
+            "SELECT id FROM merchants
+             WHERE user_id = ?1
+               AND (name_normalized = ?2 OR substr(?2, 1, length(name_normalized) + 1) = name_normalized || ' ')
+             ORDER BY length(name_normalized) DESC
+             LIMIT 1",
+            params![user_id, merchant_normalized],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+
+    Ok(by_name)
+}
+
+/// Find the merchant for a transaction, creating one named after the receipt's
+/// merchant text if none matches. Returns the merchant ID.
+pub fn resolve_merchant(
+    conn: &Connection,
+    user_id: &str,
+    merchant: &str,
+    merchant_normalized: &str,
+) -> DbResult<String> {
+    if let Some(id) = find_merchant(conn, user_id, merchant_normalized)? {
+        return Ok(id);
+    }
+
+    let name = canonical_merchant_name(merchant);
+    let name_normalized = normalize_merchant(&name);
+    if let Some(id) = find_merchant(conn, user_id, &name_normalized)? {
+        return Ok(id);
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO merchants (id, user_id, name, name_normalized) VALUES (?1, ?2, ?3, ?4)",
+        params![&id, user_id, &name, &name_normalized],
+    )?;
+    Ok(id)
+}
+
+/// Link every transaction without a merchant, e.g. after upgrading from a schema
+/// without merchants. Returns the number linked.
+pub fn link_unassigned(conn: &Connection, user_id: &str) -> DbResult<usize> {
+    let mut stmt = conn.prepare(
+        "SELECT id, merchant, merchant_normalized FROM transactions
+         WHERE user_id = ?1 AND merchant_id IS NULL
+         ORDER BY transaction_date, created_at",
+    )?;
+    let unassigned = stmt
+        .query_map([user_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if unassigned.is_empty() {
+        return Ok(0);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for (transaction_id, merchant, merchant_normalized) in &unassigned {
+        let merchant_id = resolve_merchant(&tx, user_id, merchant, merchant_normalized)?;
+        tx.execute(
+            "UPDATE transactions SET merchant_id = ?2 WHERE id = ?1",
+            params![transaction_id, &merchant_id],
+        )?;
+    }
+    tx.commit()?;
+
+    log::info!("Linked {} transactions to merchants", unassigned.len());
+    Ok(unassigned.len())
+}
+
+pub fn update_merchant(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    default_category_id: Option<&str>,
+    icon: Option<&str>,
+    logo_url: Option<&str>,
+) -> DbResult<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DbError::InvalidInput("Merchant name can't be empty".to_string()));
+    }
+
+    conn.execute(
+        "UPDATE merchants
+         SET name = ?2, name_normalized = ?3, default_category_id = ?4, icon = ?5, logo_url = ?6,
+             updated_at = datetime('now')
+         WHERE id = ?1",
+        params![id, name, normalize_merchant(name), default_category_id, icon, logo_url],
+    )?;
+    Ok(())
+}
+
+/// Add an alias and link existing unmatched-by-alias transactions it now covers
+pub fn add_merchant_alias(
+    conn: &Connection,
+    user_id: &str,
+    merchant_id: &str,
+    pattern: &str,
+    match_type: &str,
+) -> DbResult<MerchantAlias> {
+    if !MATCH_TYPES.contains(&match_type) {
+        return Err(DbError::InvalidInput(format!("Unknown match type: {}", match_type)));
+    }
+    let pattern = normalize_merchant(pattern);
+    if pattern.is_empty() {
+        return Err(DbError::InvalidInput("Alias can't be empty".to_string()));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let id = uuid::Uuid::new_v4().to_string();
+    // An alias belongs to one merchant; re-adding it moves it
+    tx.execute(
+        "INSERT INTO merchant_aliases (id, user_id, merchant_id, pattern, match_type)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (user_id, pattern, match_type) DO UPDATE SET merchant_id = excluded.merchant_id",
+        params![&id, user_id, merchant_id, &pattern, match_type],
+    )?;
+    relink_transactions(&tx, user_id)?;
+    tx.commit()?;
+
+    let alias = conn.query_row(
+        "SELECT id, merchant_id, pattern, match_type, created_at FROM merchant_aliases
+         WHERE user_id = ?1 AND pattern = ?2 AND match_type = ?3",
+        params![user_id, &pattern, match_type],
+        |row| {
+            Ok(MerchantAlias {
+                id: row.get(0)?,
+                merchant_id: row.get(1)?,
+                pattern: row.get(2)?,
+                match_type: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        },
+    )?;
+    Ok(alias)
+}
+
+pub fn remove_merchant_alias(conn: &Connection, alias_id: &str) -> DbResult<()> {
+    conn.execute("DELETE FROM merchant_aliases WHERE id = ?1", [alias_id])?;
+    Ok(())
+}
+
+/// Move transactions whose text matches an alias onto that alias's merchant
+fn relink_transactions(conn: &Connection, user_id: &str) -> DbResult<()> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT merchant_normalized FROM transactions t
+         WHERE t.user_id = ?1
+           AND EXISTS (
+               SELECT 1 FROM merchant_aliases a
+               WHERE a.user_id = ?1
+                 AND ((a.match_type = 'exact' AND a.pattern = t.merchant_normalized)
+                   OR (a.match_type = 'prefix'
+                       AND substr(t.merchant_normalized, 1, length(a.pattern)) = a.pattern)
+                   OR (a.match_type = 'contains' AND instr(t.merchant_normalized, a.pattern) > 0))
+           )",
+    )?;
+    let texts = stmt
+        .query_map([user_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for text in texts {
+        if let Some(merchant_id) = find_merchant(conn, user_id, &text)? {
+            conn.execute(
+                "UPDATE transactions SET merchant_id = ?3
+                 WHERE user_id = ?1 AND merchant_normalized = ?2",
+                params![user_id, &text, &merchant_id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Merge `source_ids` into `target_id`: their transactions and aliases move to the
+/// target, their names become exact aliases of it, and the sources are deleted
+pub fn merge_merchants(
+    conn: &Connection,
+    user_id: &str,
+    source_ids: &[String],
+    target_id: &str,
+) -> DbResult<usize> {
+    let target = get_merchant(conn, target_id)?.ok_or(DbError::NotFound)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut moved = 0;
+    for source_id in source_ids.iter().filter(|id| id.as_str() != target.id) {
+        let Some(source) = get_merchant(&tx, source_id)? else {
+            continue;
+        };
+
+        moved += tx.execute(
+            "UPDATE transactions SET merchant_id = ?2 WHERE merchant_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+        // Aliases the target already has would conflict; drop those duplicates
+        tx.execute(
+            "DELETE FROM merchant_aliases
+             WHERE merchant_id = ?1
+               AND EXISTS (
+                   SELECT 1 FROM merchant_aliases o
+                   WHERE o.merchant_id = ?2 AND o.pattern = merchant_aliases.pattern
+                     AND o.match_type = merchant_aliases.match_type
+               )",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "UPDATE merchant_aliases SET merchant_id = ?2 WHERE merchant_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "INSERT INTO merchant_aliases (id, user_id, merchant_id, pattern, match_type)
+             VALUES (?1, ?2, ?3, ?4, 'exact')
+             ON CONFLICT (user_id, pattern, match_type) DO UPDATE SET merchant_id = excluded.merchant_id",
+            params![uuid::Uuid::new_v4().to_string(), user_id, &target.id, &source.name_normalized],
+        )?;
+        if target.default_category_id.is_none() && source.default_category_id.is_some() {
+            tx.execute(
+                "UPDATE merchants SET default_category_id = ?2 WHERE id = ?1 AND default_category_id IS NULL",
+                params![&target.id, &source.default_category_id],
+            )?;
+        }
+        tx.execute("DELETE FROM merchants WHERE id = ?1", [&source.id])?;
+    }
+    tx.commit()?;
+
+    Ok(moved)
+}
+
+/// Split transactions off a merchant into a new merchant called `new_name`. The
+/// transactions' merchant texts become exact aliases of the new merchant so later
+/// imports follow. Returns the new merchant.
+pub fn split_merchant(
+    conn: &Connection,
+    user_id: &str,
+    merchant_id: &str,
+    transaction_ids: &[String],
+    new_name: &str,
+) -> DbResult<Merchant> {
+    let new_name = new_name.trim();
+    let name_normalized = normalize_merchant(new_name);
+    if name_normalized.is_empty() {
+        return Err(DbError::InvalidInput("Merchant name can't be empty".to_string()));
+    }
+    if transaction_ids.is_empty() {
+        return Err(DbError::InvalidInput("Choose transactions to split off".to_string()));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM merchants WHERE user_id = ?1 AND name_normalized = ?2)",
+        params![user_id, &name_normalized],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Err(DbError::InvalidInput(format!(
+            "A merchant named \"{}\" already exists; merge into it instead",
+            new_name
+        )));
+    }
+
+    let new_id = uuid::Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO merchants (id, user_id, name, name_normalized) VALUES (?1, ?2, ?3, ?4)",
+        params![&new_id, user_id, new_name, &name_normalized],
+    )?;
+
+    for transaction_id in transaction_ids {
+        let text: Option<String> = tx
+            .query_row(
+                "SELECT merchant_normalized FROM transactions WHERE id = ?1 AND merchant_id = ?2",
+                params![transaction_id, merchant_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(text) = text else {
+            continue;
+        };
+
+        tx.execute(
+            "UPDATE transactions SET merchant_id = ?2 WHERE id = ?1",
+            params![transaction_id, &new_id],
+        )?;
+        tx.execute(
+            "INSERT INTO merchant_aliases (id, user_id, merchant_id, pattern, match_type)
+             VALUES (?1, ?2, ?3, ?4, 'exact')
+             ON CONFLICT (user_id, pattern, match_type) DO UPDATE SET merchant_id = excluded.merchant_id",
+            params![uuid::Uuid::new_v4().to_string(), user_id, &new_id, &text],
+        )?;
+    }
+    tx.commit()?;
+
+    get_merchant(conn, &new_id)?.ok_or(DbError::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::NewTransaction;
+    use crate::db::test_support::{insert, new_transaction, open_seeded};
+
+    fn add_transaction(conn: &Connection, merchant: &str) -> String {
+        let merchant_normalized = normalize_merchant(merchant);
+        let merchant_id = resolve_merchant(conn, "local", merchant, &merchant_normalized).unwrap();
+        insert(
+            conn,
+            &NewTransaction {
+                merchant_id: Some(&merchant_id),
+                merchant_normalized: &merchant_normalized,
+                ..new_transaction(merchant, 1000, merchant)
+            },
+        )
+    }
+
+    fn merchant_of(conn: &Connection, transaction_id: &str) -> String {
+        conn.query_row(
+            "SELECT m.name FROM transactions t JOIN merchants m ON m.id = t.merchant_id WHERE t.id = ?1",
+            [transaction_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_merge_and_split() {
+        let conn = open_seeded();
+
+        let trip1 = add_transaction(&conn, "Uber - Home to Airport");
+        let trip2 = add_transaction(&conn, "Uber - Office to Home");
+        let chipotle = add_transaction(&conn, "Chipotle");
+        let grill = add_transaction(&conn, "CHIPOTLE MEXICAN GRILL");
+        let target = add_transaction(&conn, "Target #1234");
+        let tgt = add_transaction(&conn, "TGT STORE");
+
+        assert_eq!(merchant_of(&conn, &trip1), "Uber");
+        assert_eq!(merchant_of(&conn, &trip2), "Uber");
+        assert_eq!(merchant_of(&conn, &chipotle), "Chipotle");
+        assert_eq!(merchant_of(&conn, &grill), "Chipotle");
+        assert_eq!(merchant_of(&conn, &target), "Target");
+        assert_eq!(merchant_of(&conn, &tgt), "TGT STORE");
+
+        // Merging makes the old name an alias, so future imports resolve to the target
+        let target_id = find_merchant(&conn, "local", "target").unwrap().unwrap();
+        let tgt_id = find_merchant(&conn, "local", "tgt store").unwrap().unwrap();
+        assert_eq!(merge_merchants(&conn, "local", &[tgt_id], &target_id).unwrap(), 1);
+        assert_eq!(merchant_of(&conn, &tgt), "Target");
+        assert_eq!(find_merchant(&conn, "local", "tgt store").unwrap(), Some(target_id));
+
+        // Splitting moves the chosen transactions and keeps them apart on re-import
+        let chipotle_id = find_merchant(&conn, "local", "chipotle").unwrap().unwrap();
+        let split = split_merchant(&conn, "local", &chipotle_id, std::slice::from_ref(&grill), "Chipotle Mexican Grill").unwrap();
+        assert_eq!(split.transaction_count, 1);
+        assert_eq!(merchant_of(&conn, &grill), "Chipotle Mexican Grill");
+        assert_eq!(find_merchant(&conn, "local", "chipotle mexican grill").unwrap(), Some(split.id));
+    }
+}
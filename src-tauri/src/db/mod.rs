@@ -1,11 +1,22 @@
 pub mod schema;
+pub mod encryption;
+pub mod income;
+pub mod merchants;
+pub mod pool;
 pub mod queries;
+pub mod rule_suggestions;
+pub mod search;
+pub mod statements;
+#[cfg(test)]
+pub mod test_support;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use tauri::{AppHandle, Manager};
-use rusqlite::Connection;
 use thiserror::Error;
 
+pub use pool::{Database, PooledConnection};
+
 #[derive(Error, Debug)]
 pub enum DbError {
     #[error("SQLite error: {0}")]
@@ -14,9 +25,21 @@ pub enum DbError {
     Io(#[from] std::io::Error),
     #[error("Database not found")]
     NotFound,
+    #[error("Database is not initialized")]
+    NotInitialized,
+    #[error("Database is busy, try again")]
+    Busy,
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
     #[error("Migration failed: {0}")]
-    #[allow(dead_code)]
     Migration(String),
+    #[error("Database schema v{found} is newer than this app supports (v{supported}); please update the app")]
+    SchemaTooNew { found: i32, supported: i32 },
+    /// The database is encrypted and hasn't been unlocked yet
+    #[error("DATABASE_LOCKED")]
+    Locked,
+    #[error("WRONG_PASSPHRASE")]
+    WrongPassphrase,
 }
 
 pub type DbResult<T> = Result<T, DbError>;
@@ -32,10 +55,149 @@ pub fn get_db_path(app_handle: &AppHandle) -> DbResult<PathBuf> {
     Ok(app_dir.join("email_budget.db"))
 }
 
-/// Initialize the database with schema
-pub async fn initialize(app_handle: &AppHandle) -> DbResult<()> {
-    let db_path = get_db_path(app_handle)?;
-    let conn = Connection::open(&db_path)?;
+/// Tauri managed state holding the shared database handle.
+/// Empty while an encrypted database is locked.
+#[derive(Default)]
+pub struct DbState {
+    database: RwLock<Option<Database>>,
+}
+
+impl DbState {
+    /// The open database, or `DbError::Locked` if it hasn't been unlocked
+    pub fn get(&self) -> DbResult<Database> {
+        self.database
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or(DbError::Locked)
+    }
+
+    /// Replace the open database, returning the previous one
+    pub fn set(&self, database: Option<Database>) -> Option<Database> {
+        std::mem::replace(
+            &mut *self.database.write().unwrap_or_else(|e| e.into_inner()),
+            database,
+        )
+    }
+}
+
+/// Get the registered `DbState`, registering an empty one on first use
+pub fn state(app_handle: &AppHandle) -> tauri::State<'_, DbState> {
+    if app_handle.try_state::<DbState>().is_none() {
+        app_handle.manage(DbState::default());
+    }
+    app_handle.state::<DbState>()
+}
+
+/// Open the database, run migrations and register the shared handle in Tauri state.
+/// Safe to call more than once. An encrypted database is left locked and this returns
+/// `DbError::Locked`; `unlock` opens it once the user supplies the passphrase.
+pub fn initialize(app_handle: &AppHandle) -> DbResult<()> {
+    let state = state(app_handle);
+    let database = match state.get() {
+        Ok(database) => database,
+        Err(_) => {
+            let path = get_db_path(app_handle)?;
+            if encryption::is_encrypted(&path)? {
+                return Err(DbError::Locked);
+            }
+            let database = Database::open(&path, None)?;
+            state.set(Some(database.clone()));
+            database
+        }
+    };
+
+    prepare(&database)
+}
+
+/// Open an encrypted database with `passphrase`, run migrations and register it
+pub fn unlock(app_handle: &AppHandle, passphrase: &str) -> DbResult<()> {
+    let state = state(app_handle);
+    if state.get().is_ok() {
+        return Ok(());
+    }
+
+    let database = Database::open(&get_db_path(app_handle)?, Some(passphrase))?;
+    prepare(&database)?;
+    state.set(Some(database));
+    Ok(())
+}
+
+/// Close the database and forget the key until the next `unlock`
+pub fn lock(app_handle: &AppHandle) -> DbResult<()> {
+    match state(app_handle).set(None) {
+        Some(database) => database.wait_until_unused(),
+        None => Ok(()),
+    }
+}
+
+/// Replace the file behind `database` (already removed from `state`) with `replacement`
+/// and register the reopened database. If connections are still checked out, or the
+/// replacement can't be swapped in and opened, the original is put back and
+/// `replacement` is discarded.
+pub fn replace_database_file(
+    state: &DbState,
+    database: Database,
+    replacement: &Path,
+    key: Option<&str>,
+) -> DbResult<()> {
+    if let Err(e) = database.wait_until_unused() {
+        let _ = std::fs::remove_file(replacement);
+        state.set(Some(database));
+        return Err(e);
+    }
+
+    let path = database.path().to_path_buf();
+    let original_key = database.key().map(str::to_string);
+    drop(database);
+
+    // Keep the original until the replacement has opened, so it can be put back
+    let displaced = sibling_path(&path, "replaced");
+    let reopened = swap_database_file(&path, replacement, &displaced).and_then(|()| Database::open(&path, key));
+    match reopened {
+        Ok(database) => {
+            let _ = std::fs::remove_file(&displaced);
+            state.set(Some(database));
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to swap in {:?}, reopening the original database: {}", replacement, e);
+            let _ = std::fs::remove_file(replacement);
+            if displaced.exists() {
+                std::fs::rename(&displaced, &path)?;
+                remove_wal_files(&path);
+            }
+            state.set(Some(Database::open(&path, original_key.as_deref())?));
+            Err(e)
+        }
+    }
+}
+
+/// Move the closed database at `path` to `displaced` and `replacement` into its place
+fn swap_database_file(path: &Path, replacement: &Path, displaced: &Path) -> DbResult<()> {
+    std::fs::rename(path, displaced)?;
+    std::fs::rename(replacement, path)?;
+    remove_wal_files(path);
+    Ok(())
+}
+
+/// Remove the WAL and shared-memory files left next to a closed database
+fn remove_wal_files(path: &Path) {
+    for suffix in ["wal", "shm"] {
+        let _ = std::fs::remove_file(sibling_path(path, suffix));
+    }
+}
+
+/// `path` with `-suffix` appended, e.g. `email_budget.db-wal`
+pub fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push("-");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn prepare(database: &Database) -> DbResult<()> {
+    let conn = database.writer()?;
 
     // Run migrations
     schema::run_migrations(&conn)?;
@@ -43,14 +205,51 @@ pub async fn initialize(app_handle: &AppHandle) -> DbResult<()> {
     // Seed default categories if needed
     schema::seed_default_categories(&conn)?;
 
-    log::info!("Database initialized at {:?}", db_path);
+    // Housekeeping below is retried on every launch, so a failure shouldn't keep the
+    // database from opening
+
+    // Link transactions recorded before merchants existed
+    if let Err(e) = merchants::link_unassigned(&conn, "local") {
+        log::error!("Failed to link transactions to merchants: {}", e);
+    }
+
+    // Record paychecks that came due while the app was closed
+    if let Err(e) = income::record_due_recurring_income(&conn, "local", chrono::Local::now().date_naive()) {
+        log::error!("Failed to record due recurring income: {}", e);
+    }
+
+    log::info!("Database initialized at {:?}", database.path());
+    drop(conn);
+
+    // Refresh the classifier with categories assigned since the last launch, off the
+    // startup path
+    let database = database.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = database
+            .writer()
+            .and_then(|conn| crate::categorizer::classifier::train_if_stale(&conn, "local"));
+        if let Err(e) = result {
+            log::error!("Failed to train the category classifier: {}", e);
+        }
+    });
     Ok(())
 }
 
-/// Get a database connection
-pub fn get_connection(app_handle: &AppHandle) -> DbResult<Connection> {
-    let db_path = get_db_path(app_handle)?;
-    let conn = Connection::open(&db_path)?;
-    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
-    Ok(conn)
+/// Check out a read-only connection from the shared database handle
+pub fn reader(app_handle: &AppHandle) -> DbResult<PooledConnection> {
+    app_handle
+        .try_state::<DbState>()
+        .ok_or(DbError::NotInitialized)?
+        .get()?
+        .reader()
+}
+
+/// Check out the single write connection from the shared database handle.
+/// Don't hold it across `.await` points.
+pub fn writer(app_handle: &AppHandle) -> DbResult<PooledConnection> {
+    app_handle
+        .try_state::<DbState>()
+        .ok_or(DbError::NotInitialized)?
+        .get()?
+        .writer()
 }
@@ -0,0 +1,313 @@
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use rusqlite::backup::StepResult;
+use rusqlite::Connection;
+
+use super::{DbError, DbResult};
+
+/// How long a connection waits on a lock held by another connection (and how long
+/// `writer()` waits for the write handle) before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Idle reader connections kept open for reuse
+const MAX_IDLE_READERS: usize = 4;
+
+/// Shared database handle, stored in Tauri managed state.
+///
+/// Concurrency model:
+/// - There is exactly one write connection. `writer()` checks it out exclusively, so
+///   writes from commands and the Gmail poller are serialized in-process rather than
+///   racing for SQLite's write lock. Hold it only for synchronous DB work, never
+///   across network calls.
+/// - Reads go through `reader()`, which hands out `query_only` connections from a
+///   small pool. In WAL mode readers see the last committed state and never block,
+///   or are blocked by, the writer.
+#[derive(Clone)]
+pub struct Database {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    path: PathBuf,
+    /// SQLCipher passphrase, or None for a plaintext database
+    key: Option<String>,
+    writer: Mutex<Option<Connection>>,
+    writer_returned: Condvar,
+    readers: Mutex<Vec<Connection>>,
+}
+
+impl Database {
+    /// Open the database at `path`, switching it to WAL mode. `key` is the passphrase
+    /// for an encrypted database; a wrong key fails with `DbError::WrongPassphrase`.
+    pub fn open(path: &Path, key: Option<&str>) -> DbResult<Self> {
+        let writer = open_connection(path, key)?;
+        writer.pragma_update(None, "journal_mode", "WAL")?;
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                path: path.to_path_buf(),
+                key: key.map(|k| k.to_string()),
+                writer: Mutex::new(Some(writer)),
+                writer_returned: Condvar::new(),
+                readers: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.inner.key.is_some()
+    }
+
+    /// The passphrase this database was opened with
+    pub(crate) fn key(&self) -> Option<&str> {
+        self.inner.key.as_deref()
+    }
+
+    /// Wait until this is the last handle and every checked-out connection has been
+    /// returned, so dropping it closes the database file. Callers must first stop
+    /// handing out this `Database` (e.g. remove it from `DbState`).
+    pub fn wait_until_unused(&self) -> DbResult<()> {
+        let deadline = std::time::Instant::now() + BUSY_TIMEOUT;
+        while Arc::strong_count(&self.inner) > 1 {
+            if std::time::Instant::now() > deadline {
+                return Err(DbError::Busy);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Ok(())
+    }
+
+    /// Write a consistent copy of the database to `target` with the SQLite online
+    /// backup API. The copy is encrypted with the same key as this database.
+    pub fn snapshot_to(&self, target: &Path) -> DbResult<()> {
+        let source = self.reader()?;
+        let mut destination = self.open_copy(target)?;
+
+        // Copy every page in a single step so concurrent writes can't restart the backup
+        let backup = rusqlite::backup::Backup::new(&source, &mut destination)?;
+        let deadline = std::time::Instant::now() + BUSY_TIMEOUT;
+        loop {
+            match backup.step(-1)? {
+                StepResult::Done => return Ok(()),
+                _ => {
+                    if std::time::Instant::now() > deadline {
+                        return Err(DbError::Busy);
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+        }
+    }
+
+    /// Open another database file (e.g. a snapshot) with this database's key
+    pub fn open_copy(&self, path: &Path) -> DbResult<Connection> {
+        open_connection(path, self.inner.key.as_deref())
+    }
+
+    /// Check out the write connection, waiting if another task is using it
+    pub fn writer(&self) -> DbResult<PooledConnection> {
+        self.writer_within(BUSY_TIMEOUT)
+    }
+
+    fn writer_within(&self, timeout: Duration) -> DbResult<PooledConnection> {
+        let mut slot = self.inner.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.is_none() {
+            let wait = || {
+                self.inner
+                    .writer_returned
+                    .wait_timeout_while(slot, timeout, |conn| conn.is_none())
+                    .unwrap_or_else(|e| e.into_inner())
+            };
+            // Commands call this from async tasks; let the runtime move this worker's
+            // other tasks elsewhere while it blocks
+            let on_worker = tokio::runtime::Handle::try_current()
+                .is_ok_and(|handle| handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread);
+            let (waited, result) = if on_worker {
+                tokio::task::block_in_place(wait)
+            } else {
+                wait()
+            };
+            if result.timed_out() {
+                return Err(DbError::Busy);
+            }
+            slot = waited;
+        }
+
+        Ok(PooledConnection {
+            conn: slot.take(),
+            kind: ConnectionKind::Writer,
+            pool: self.inner.clone(),
+        })
+    }
+
+    /// Check out a read-only connection
+    pub fn reader(&self) -> DbResult<PooledConnection> {
+        let idle = self
+            .inner
+            .readers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop();
+
+        let conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let conn = open_connection(&self.inner.path, self.inner.key.as_deref())?;
+                conn.pragma_update(None, "query_only", true)?;
+                conn
+            }
+        };
+
+        Ok(PooledConnection {
+            conn: Some(conn),
+            kind: ConnectionKind::Reader,
+            pool: self.inner.clone(),
+        })
+    }
+}
+
+pub(crate) fn open_connection(path: &Path, key: Option<&str>) -> DbResult<Connection> {
+    let conn = Connection::open(path)?;
+
+    // The key must be set before anything reads the file
+    if let Some(key) = key {
+        conn.pragma_update(None, "key", key)?;
+    }
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => {
+            return Err(if key.is_some() {
+                DbError::WrongPassphrase
+            } else {
+                DbError::Locked
+            });
+        }
+        result => {
+            result?;
+        }
+    }
+
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.execute_batch(
+        "PRAGMA foreign_keys = ON;
+         PRAGMA synchronous = NORMAL;",
+    )?;
+    Ok(conn)
+}
+
+#[derive(Clone, Copy)]
+enum ConnectionKind {
+    Writer,
+    Reader,
+}
+
+/// A connection checked out of the `Database`, returned to it on drop
+pub struct PooledConnection {
+    conn: Option<Connection>,
+    kind: ConnectionKind,
+    pool: Arc<PoolInner>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection already returned")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection already returned")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+
+        match self.kind {
+            ConnectionKind::Writer => {
+                // Never hand back a connection stuck inside a transaction
+                if !conn.is_autocommit() {
+                    let _ = conn.execute_batch("ROLLBACK");
+                }
+                *self.pool.writer.lock().unwrap_or_else(|e| e.into_inner()) = Some(conn);
+                self.pool.writer_returned.notify_one();
+            }
+            ConnectionKind::Reader => {
+                let mut readers = self.pool.readers.lock().unwrap_or_else(|e| e.into_inner());
+                if readers.len() < MAX_IDLE_READERS {
+                    readers.push(conn);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn open_temp() -> (Database, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("email_budget_pool_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        (Database::open(&dir.join("email_budget.db"), None).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_single_writer() {
+        let (database, dir) = open_temp();
+        let writer = database.writer().unwrap();
+        assert!(matches!(database.writer_within(Duration::from_millis(50)), Err(DbError::Busy)));
+
+        let waiting = database.clone();
+        let started = Instant::now();
+        let handle = std::thread::spawn(move || waiting.writer().map(|_| started.elapsed()));
+        std::thread::sleep(Duration::from_millis(100));
+        drop(writer);
+        assert!(handle.join().unwrap().unwrap() >= Duration::from_millis(100));
+
+        drop(database);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_readers_are_query_only() {
+        let (database, dir) = open_temp();
+        database.writer().unwrap().execute_batch("CREATE TABLE kept (id INTEGER)").unwrap();
+
+        let reader = database.reader().unwrap();
+        assert!(reader.execute("INSERT INTO kept VALUES (1)", []).is_err());
+        let count: i64 = reader.query_row("SELECT COUNT(*) FROM kept", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+
+        drop(reader);
+        drop(database);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_waiting_writer_in_async_task() {
+        let (database, dir) = open_temp();
+        let writer = database.writer().unwrap();
+
+        let waiting = database.clone();
+        let task = tokio::spawn(async move { waiting.writer().map(|_| ()) });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(writer);
+        task.await.unwrap().unwrap();
+
+        drop(database);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::{HashMap, HashSet};
+
 use chrono::NaiveDate;
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
@@ -8,6 +10,12 @@ use super::DbResult;
 // Models
 // ============================================================================
 
+/// Whether a transaction is money spent or received. Amounts are positive either way.
+pub mod direction {
+    pub const EXPENSE: &str = "expense";
+    pub const INCOME: &str = "income";
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Category {
     pub id: String,
@@ -15,7 +23,16 @@ pub struct Category {
     pub name: String,
     pub icon: String,
     pub color: String,
+    /// Parent category; spending and budgets roll up into it
+    #[serde(default)]
+    pub parent_id: Option<String>,
     pub is_system: bool,
+    /// Stable name of a seeded category, used to find it after the user renames it
+    #[serde(default)]
+    pub system_key: Option<String>,
+    /// Hidden from pickers and skipped by automatic categorization
+    #[serde(default)]
+    pub is_hidden: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -36,6 +53,20 @@ pub struct Transaction {
     pub updated_at: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// 'credit_card', 'debit_card', 'bank', 'wallet', 'cash' or 'other'
+    pub account_type: String,
+    /// Card network or institution, e.g. "Visa", "Venmo"
+    pub institution: Option<String>,
+    pub last4: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionWithCategory {
     pub id: String,
@@ -53,6 +84,11 @@ pub struct TransactionWithCategory {
     pub category_name: Option<String>,
     pub category_color: Option<String>,
     pub category_icon: Option<String>,
+    pub account_id: Option<String>,
+    pub account_name: Option<String>,
+    /// 'expense' or 'income'
+    pub direction: String,
+    pub merchant_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,6 +99,82 @@ pub struct TransactionItem {
     pub quantity: i32,
     pub unit_price: i64,
     pub total_price: i64,
+    /// Set when the item belongs in a different category than the transaction
+    #[serde(default)]
+    pub category_id: Option<String>,
+    #[serde(default)]
+    pub category_source: Option<String>,
+    #[serde(default)]
+    pub category_reason: Option<String>,
+}
+
+/// A share of a transaction's amount counted toward one category, created when its
+/// items span several categories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionSplit {
+    pub id: String,
+    pub transaction_id: String,
+    /// The transaction's own category for the share not claimed by other categories
+    pub category_id: Option<String>,
+    pub category_name: Option<String>,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionFee {
+    pub id: String,
+    pub transaction_id: String,
+    pub kind: String,
+    pub label: String,
+    pub amount: i64,
+}
+
+/// A transaction with everything known about it, for the detail view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionDetail {
+    pub transaction: TransactionWithCategory,
+    pub items: Vec<TransactionItem>,
+    pub fees: Vec<TransactionFee>,
+    /// Empty unless the items span several categories
+    pub splits: Vec<TransactionSplit>,
+    /// 'gmail', 'import' or 'ocr'; None for transactions imported before this was tracked
+    pub source_type: Option<String>,
+    /// Gmail message ID or imported file name
+    pub source_ref: Option<String>,
+    pub receipt_text: Option<String>,
+    /// How sure the parser was of the receipt, from 0.0 to 1.0
+    pub parse_confidence: Option<f64>,
+    /// Categorization tier that assigned the category, e.g. 'user_rule' or 'provider_default'
+    pub category_source: Option<String>,
+    pub category_reason: Option<String>,
+    /// The rule that assigned the category, while it still exists
+    pub category_rule_id: Option<String>,
+}
+
+/// Fields for inserting a new transaction
+#[derive(Debug, Clone, Default)]
+pub struct NewTransaction<'a> {
+    pub category_id: Option<&'a str>,
+    pub account_id: Option<&'a str>,
+    pub merchant_id: Option<&'a str>,
+    pub category_source: Option<&'a str>,
+    pub category_reason: Option<&'a str>,
+    pub category_rule_id: Option<&'a str>,
+    pub merchant: &'a str,
+    pub merchant_normalized: &'a str,
+    pub amount: i64,
+    pub transaction_date: &'a str,
+    pub provider: &'a str,
+    pub source_hash: &'a str,
+    pub source_type: &'a str,
+    pub source_ref: Option<&'a str>,
+    pub receipt_text: Option<&'a str>,
+    /// How sure the parser was of the receipt, from 0.0 to 1.0
+    pub parse_confidence: Option<f64>,
+    /// Sender address of the receipt email
+    pub sender: Option<&'a str>,
+    /// Money received rather than spent
+    pub is_income: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,19 +208,138 @@ pub struct BudgetWithProgress {
     pub is_over_budget: bool,
 }
 
+/// A categorization rule. Every condition that is set must match; unset
+/// conditions match anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantCategoryRule {
+    pub id: String,
+    /// Optional label shown instead of the generated description
+    #[serde(default)]
+    pub name: Option<String>,
+    pub merchant_pattern: Option<String>,
+    /// 'exact', 'contains', 'glob' or 'regex'
+    #[serde(default = "default_rule_match_type")]
+    pub match_type: String,
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Cents, inclusive
+    #[serde(default)]
+    pub min_amount: Option<i64>,
+    /// Cents, exclusive
+    #[serde(default)]
+    pub max_amount: Option<i64>,
+    /// Comma-separated ISO weekdays, 1 = Monday .. 7 = Sunday
+    #[serde(default)]
+    pub days_of_week: Option<String>,
+    /// Case-insensitive substring of any item name
+    #[serde(default)]
+    pub item_keyword: Option<String>,
+    /// Case-insensitive substring of the receipt email's sender
+    #[serde(default)]
+    pub sender: Option<String>,
+    pub category_id: String,
+    /// Rules are tried in ascending priority
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Same as `match_type == "exact"`; kept for datasets exported before rule conditions
+    #[serde(default)]
+    pub is_exact_match: bool,
+    pub created_at: String,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// Fields for creating or updating a rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleInput {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub merchant_pattern: Option<String>,
+    #[serde(default = "default_rule_match_type")]
+    pub match_type: String,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub min_amount: Option<i64>,
+    #[serde(default)]
+    pub max_amount: Option<i64>,
+    #[serde(default)]
+    pub days_of_week: Option<String>,
+    #[serde(default)]
+    pub item_keyword: Option<String>,
+    #[serde(default)]
+    pub sender: Option<String>,
+    pub category_id: String,
+    /// None places a new rule after all existing rules
+    #[serde(default)]
+    pub priority: Option<i64>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+pub const RULE_MATCH_TYPES: [&str; 4] = ["exact", "contains", "glob", "regex"];
+
+fn default_rule_match_type() -> String {
+    "contains".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl RuleInput {
+    /// A rule with only a merchant condition
+    pub fn for_merchant(merchant_pattern: &str, match_type: &str, category_id: &str) -> Self {
+        Self {
+            name: None,
+            merchant_pattern: Some(merchant_pattern.to_string()),
+            match_type: match_type.to_string(),
+            provider: None,
+            min_amount: None,
+            max_amount: None,
+            days_of_week: None,
+            item_keyword: None,
+            sender: None,
+            category_id: category_id.to_string(),
+            priority: None,
+            enabled: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CategorySpending {
     pub category_id: String,
+    pub parent_id: Option<String>,
     pub category_name: String,
     pub category_color: String,
     pub category_icon: String,
+    /// Spending in the category and all of its subcategories
     pub total: i64,
     pub transaction_count: i64,
     pub percentage: f64,
+    /// Subcategories with spending, largest first
+    pub children: Vec<CategorySpending>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSpending {
+    /// None for transactions with no account
+    pub account_id: Option<String>,
+    pub account_name: Option<String>,
+    pub account_type: Option<String>,
+    pub last4: Option<String>,
+    pub total: i64,
+    pub transaction_count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MerchantTotal {
+    /// None for transactions not yet linked to a merchant
+    pub merchant_id: Option<String>,
     pub merchant: String,
     pub total: i64,
     pub count: i64,
@@ -119,35 +350,116 @@ pub struct TransactionFilters {
     pub search: Option<String>,
     pub category_id: Option<String>,
     pub provider: Option<String>,
+    pub account_id: Option<String>,
+    /// 'expense' or 'income'
+    pub direction: Option<String>,
+    /// How the category was assigned, e.g. 'provider_default' or 'manual'
+    pub category_source: Option<String>,
     pub start_date: Option<String>,
     pub end_date: Option<String>,
     pub min_amount: Option<i64>,
     pub max_amount: Option<i64>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSortKey {
+    #[default]
+    Date,
+    Amount,
+    Merchant,
+    Category,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionPageRequest {
+    pub sort: TransactionSortKey,
+    pub direction: SortDirection,
+    /// Opaque cursor from a previous page's `next_cursor`
+    pub cursor: Option<String>,
+    pub limit: i64,
+    /// Compute `total_count` and `total_amount`; callers showing a few rows skip them
+    pub include_totals: bool,
+}
+
+impl Default for TransactionPageRequest {
+    fn default() -> Self {
+        Self {
+            sort: TransactionSortKey::default(),
+            direction: SortDirection::default(),
+            cursor: None,
+            limit: DEFAULT_PAGE_SIZE,
+            include_totals: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<TransactionWithCategory>,
+    /// Cursor for the next page, or None if this is the last page
+    pub next_cursor: Option<String>,
+    /// Number of transactions matching the filters across all pages; 0 unless requested
+    pub total_count: i64,
+    /// Net spending (cents) across all pages: expenses minus income; 0 unless requested
+    pub total_amount: i64,
+}
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
 // ============================================================================
 // Category Queries
 // ============================================================================
 
+const CATEGORY_COLUMNS: &str =
+    "id, user_id, name, icon, color, parent_id, is_system, system_key, is_hidden, created_at, updated_at";
+
+fn category_from_row(row: &rusqlite::Row) -> rusqlite::Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        icon: row.get(3)?,
+        color: row.get(4)?,
+        parent_id: row.get(5)?,
+        is_system: row.get::<_, i32>(6)? != 0,
+        system_key: row.get(7)?,
+        is_hidden: row.get::<_, i32>(8)? != 0,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+/// A subquery selecting `param`'s category ID and the IDs of all its descendants
+pub fn category_subtree_sql(param: &str) -> String {
+    format!(
+        "WITH RECURSIVE category_subtree(id) AS (
+             SELECT {}
+             UNION
+             SELECT sc.id FROM categories sc JOIN category_subtree st ON sc.parent_id = st.id
+         )
+         SELECT id FROM category_subtree",
+        param
+    )
+}
+
 pub fn get_all_categories(conn: &Connection, user_id: &str) -> DbResult<Vec<Category>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, user_id, name, icon, color, is_system, created_at, updated_at
-         FROM categories WHERE user_id = ?1 ORDER BY is_system DESC, name ASC",
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM categories WHERE user_id = ?1 ORDER BY is_system DESC, name ASC",
+        CATEGORY_COLUMNS
+    ))?;
 
     let categories = stmt
-        .query_map([user_id], |row| {
-            Ok(Category {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                name: row.get(2)?,
-                icon: row.get(3)?,
-                color: row.get(4)?,
-                is_system: row.get::<_, i32>(5)? != 0,
-                created_at: row.get(6)?,
-                updated_at: row.get(7)?,
-            })
-        })?
+        .query_map([user_id], category_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(categories)
@@ -156,44 +468,68 @@ pub fn get_all_categories(conn: &Connection, user_id: &str) -> DbResult<Vec<Cate
 pub fn get_category_by_id(conn: &Connection, id: &str) -> DbResult<Option<Category>> {
     let result = conn
         .query_row(
-            "SELECT id, user_id, name, icon, color, is_system, created_at, updated_at
-             FROM categories WHERE id = ?1",
+            &format!("SELECT {} FROM categories WHERE id = ?1", CATEGORY_COLUMNS),
             [id],
-            |row| {
-                Ok(Category {
-                    id: row.get(0)?,
-                    user_id: row.get(1)?,
-                    name: row.get(2)?,
-                    icon: row.get(3)?,
-                    color: row.get(4)?,
-                    is_system: row.get::<_, i32>(5)? != 0,
-                    created_at: row.get(6)?,
-                    updated_at: row.get(7)?,
-                })
-            },
+            category_from_row,
         )
         .optional()?;
 
     Ok(result)
 }
 
+/// Check that `parent_id` can be the parent of `category_id` (None for a new
+/// category): it must exist for the same user and not be the category or one of
+/// its descendants
+fn validate_category_parent(
+    conn: &Connection,
+    user_id: &str,
+    category_id: Option<&str>,
+    parent_id: &str,
+) -> DbResult<()> {
+    let parent = get_category_by_id(conn, parent_id)?
+        .filter(|c| c.user_id == user_id)
+        .ok_or_else(|| super::DbError::InvalidInput("Parent category not found".to_string()))?;
+
+    if let Some(category_id) = category_id {
+        let creates_cycle: bool = conn.query_row(
+            &format!("SELECT ?2 IN ({})", category_subtree_sql("?1")),
+            params![category_id, &parent.id],
+            |row| row.get(0),
+        )?;
+        if creates_cycle {
+            return Err(super::DbError::InvalidInput(
+                "A category can't be nested under itself or one of its subcategories".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 pub fn create_category(
     conn: &Connection,
     user_id: &str,
     name: &str,
     icon: &str,
     color: &str,
+    parent_id: Option<&str>,
 ) -> DbResult<Category> {
+    if let Some(parent_id) = parent_id {
+        validate_category_parent(conn, user_id, None, parent_id)?;
+    }
     let id = uuid::Uuid::new_v4().to_string();
 
     conn.execute(
-        "INSERT INTO categories (id, user_id, name, icon, color, is_system) VALUES (?1, ?2, ?3, ?4, ?5, 0)",
-        params![&id, user_id, name, icon, color],
+        "INSERT INTO categories (id, user_id, name, icon, color, parent_id, is_system)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0)",
+        params![&id, user_id, name, icon, color, parent_id],
     )?;
 
     get_category_by_id(conn, &id)?.ok_or_else(|| super::DbError::NotFound)
 }
 
+/// Rename or restyle a category. System categories can be renamed; the
+/// categorizer finds them by `system_key`.
 pub fn update_category(
     conn: &Connection,
     id: &str,
@@ -201,54 +537,440 @@ pub fn update_category(
     icon: &str,
     color: &str,
 ) -> DbResult<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(super::DbError::InvalidInput("Category name can't be empty".to_string()));
+    }
+
     conn.execute(
         "UPDATE categories SET name = ?2, icon = ?3, color = ?4, updated_at = datetime('now')
-         WHERE id = ?1 AND is_system = 0",
+         WHERE id = ?1",
         params![id, name, icon, color],
     )?;
     Ok(())
 }
 
-pub fn delete_category(conn: &Connection, id: &str) -> DbResult<()> {
+/// Hide a category from pickers and automatic categorization, or show it again.
+/// Its transactions, budgets and rules are kept.
+pub fn set_category_hidden(conn: &Connection, id: &str, hidden: bool) -> DbResult<()> {
+    conn.execute(
+        "UPDATE categories SET is_hidden = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, hidden],
+    )?;
+    Ok(())
+}
+
+/// Find a category by name, preferring a seeded category whose `system_key` is
+/// `name` so renamed system categories are still found. Hidden categories are skipped.
+pub fn find_category_by_name(conn: &Connection, user_id: &str, name: &str) -> DbResult<Option<String>> {
+    let id = conn
+        .query_row(
+            "SELECT id FROM categories
+             WHERE user_id = ?1 AND is_hidden = 0 AND (system_key = ?2 OR name = ?2 COLLATE NOCASE)
+             ORDER BY COALESCE(system_key = ?2, 0) DESC
+             LIMIT 1",
+            params![user_id, name],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(id)
+}
+
+/// Move a category under `parent_id`, or to the top level with None. System
+/// categories can be rearranged too.
+pub fn set_category_parent(conn: &Connection, id: &str, parent_id: Option<&str>) -> DbResult<()> {
+    let category = get_category_by_id(conn, id)?.ok_or(super::DbError::NotFound)?;
+    if let Some(parent_id) = parent_id {
+        validate_category_parent(conn, &category.user_id, Some(id), parent_id)?;
+    }
+
     conn.execute(
-        "DELETE FROM categories WHERE id = ?1 AND is_system = 0",
+        "UPDATE categories SET parent_id = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, parent_id],
+    )?;
+    Ok(())
+}
+
+/// Delete an unused category; its subcategories move up to its parent. Categories
+/// with transactions, rules or budgets must be merged into another with
+/// `merge_categories` instead, and system categories can only be hidden.
+pub fn delete_category(conn: &Connection, id: &str) -> DbResult<()> {
+    let category = get_category_by_id(conn, id)?.ok_or(super::DbError::NotFound)?;
+    if category.is_system {
+        return Err(super::DbError::InvalidInput(
+            "System categories can't be deleted; hide them instead".to_string(),
+        ));
+    }
+
+    let in_use: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM transactions WHERE category_id = ?1)
+             OR EXISTS (SELECT 1 FROM merchant_category_rules WHERE category_id = ?1)
+             OR EXISTS (SELECT 1 FROM budgets WHERE category_id = ?1)",
         [id],
+        |row| row.get(0),
     )?;
+    if in_use {
+        return Err(super::DbError::InvalidInput(format!(
+            "\"{}\" has transactions, rules or budgets; choose a category to move them to",
+            category.name
+        )));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE categories SET parent_id = ?2, updated_at = datetime('now') WHERE parent_id = ?1",
+        params![id, &category.parent_id],
+    )?;
+    tx.execute("DELETE FROM categories WHERE id = ?1", [id])?;
+    tx.commit()?;
     Ok(())
 }
 
+/// Summary of a category merge
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategoryMergeResult {
+    pub transactions_moved: usize,
+    pub rules_moved: usize,
+    pub budgets_moved: usize,
+    pub categories_deleted: usize,
+}
+
+/// Move everything in `source_ids` into `target_id` and delete the sources, in one
+/// transaction. Transactions, rules, merchant defaults and recurring income move to
+/// the target; a budget for a period the target already budgets is added to the
+/// target's amount. Subcategories of a source move up to the source's parent.
+pub fn merge_categories(
+    conn: &Connection,
+    user_id: &str,
+    source_ids: &[String],
+    target_id: &str,
+) -> DbResult<CategoryMergeResult> {
+    let target = get_category_by_id(conn, target_id)?
+        .filter(|c| c.user_id == user_id)
+        .ok_or_else(|| super::DbError::InvalidInput("Target category not found".to_string()))?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut result = CategoryMergeResult::default();
+
+    for source_id in source_ids {
+        if source_id == &target.id {
+            continue;
+        }
+        let source = get_category_by_id(&tx, source_id)?
+            .filter(|c| c.user_id == user_id)
+            .ok_or(super::DbError::NotFound)?;
+        if source.is_system {
+            return Err(super::DbError::InvalidInput(format!(
+                "\"{}\" is a system category; hide it instead",
+                source.name
+            )));
+        }
+
+        result.transactions_moved += tx.execute(
+            "UPDATE transactions
+             SET category_id = ?2, category_reason = ?3, updated_at = datetime('now')
+             WHERE category_id = ?1",
+            params![&source.id, &target.id, format!("Moved from \"{}\"", source.name)],
+        )?;
+        result.rules_moved += tx.execute(
+            "UPDATE merchant_category_rules SET category_id = ?2 WHERE category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "UPDATE transaction_items SET category_id = ?2 WHERE category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "UPDATE transaction_splits SET category_id = ?2 WHERE category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+
+        // Fold budgets into the target's budget for the same period, else move them
+        result.budgets_moved += tx.execute(
+            "UPDATE budgets
+             SET amount = amount + (
+                     SELECT s.amount FROM budgets s WHERE s.category_id = ?1 AND s.period = budgets.period
+                 ),
+                 updated_at = datetime('now')
+             WHERE category_id = ?2
+               AND period IN (SELECT period FROM budgets WHERE category_id = ?1)",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "DELETE FROM budgets
+             WHERE category_id = ?1 AND period IN (SELECT period FROM budgets WHERE category_id = ?2)",
+            params![&source.id, &target.id],
+        )?;
+        result.budgets_moved += tx.execute(
+            "UPDATE budgets SET category_id = ?2, updated_at = datetime('now') WHERE category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+
+        tx.execute(
+            "UPDATE merchants SET default_category_id = ?2, updated_at = datetime('now')
+             WHERE default_category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "UPDATE recurring_income SET category_id = ?2, updated_at = datetime('now')
+             WHERE category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+        tx.execute(
+            "UPDATE merchant_pattern_overrides SET category_id = ?2, updated_at = datetime('now')
+             WHERE category_id = ?1",
+            params![&source.id, &target.id],
+        )?;
+
+        tx.execute(
+            "UPDATE categories SET parent_id = ?2, updated_at = datetime('now') WHERE parent_id = ?1",
+            params![&source.id, &source.parent_id],
+        )?;
+        result.categories_deleted += tx.execute("DELETE FROM categories WHERE id = ?1", [&source.id])?;
+    }
+
+    tx.commit()?;
+    Ok(result)
+}
+
 // ============================================================================
-// Transaction Queries
+// Account Queries
 // ============================================================================
 
-pub fn get_transactions(
+pub const ACCOUNT_TYPES: [&str; 6] = ["credit_card", "debit_card", "bank", "wallet", "cash", "other"];
+
+const ACCOUNT_COLUMNS: &str =
+    "id, user_id, name, account_type, institution, last4, created_at, updated_at";
+
+fn account_from_row(row: &rusqlite::Row) -> rusqlite::Result<Account> {
+    Ok(Account {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        account_type: row.get(3)?,
+        institution: row.get(4)?,
+        last4: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+pub fn get_accounts(conn: &Connection, user_id: &str) -> DbResult<Vec<Account>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM accounts WHERE user_id = ?1 ORDER BY name",
+        ACCOUNT_COLUMNS
+    ))?;
+
+    let accounts = stmt
+        .query_map([user_id], account_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(accounts)
+}
+
+pub fn get_account_by_id(conn: &Connection, id: &str) -> DbResult<Option<Account>> {
+    let account = conn
+        .query_row(
+            &format!("SELECT {} FROM accounts WHERE id = ?1", ACCOUNT_COLUMNS),
+            [id],
+            account_from_row,
+        )
+        .optional()?;
+
+    Ok(account)
+}
+
+fn validate_account(account_type: &str, last4: Option<&str>) -> DbResult<()> {
+    if !ACCOUNT_TYPES.contains(&account_type) {
+        return Err(super::DbError::InvalidInput(format!(
+            "Unknown account type: {}",
+            account_type
+        )));
+    }
+    if let Some(last4) = last4 {
+        if last4.len() != 4 || !last4.chars().all(|c| c.is_ascii_digit()) {
+            return Err(super::DbError::InvalidInput(
+                "Last four digits must be exactly 4 digits".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn create_account(
     conn: &Connection,
     user_id: &str,
-    filters: &TransactionFilters,
-) -> DbResult<Vec<TransactionWithCategory>> {
-    let mut sql = String::from(
-        "SELECT t.id, t.user_id, t.category_id, t.merchant, t.merchant_normalized,
-                t.amount, t.transaction_date, t.provider, t.source_hash, t.notes,
-                t.created_at, t.updated_at,
-                c.name as category_name, c.color as category_color, c.icon as category_icon
-         FROM transactions t
-         LEFT JOIN categories c ON t.category_id = c.id
-         WHERE t.user_id = ?",
-    );
+    name: &str,
+    account_type: &str,
+    institution: Option<&str>,
+    last4: Option<&str>,
+) -> DbResult<Account> {
+    validate_account(account_type, last4)?;
+    let id = uuid::Uuid::new_v4().to_string();
 
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(user_id.to_string())];
+    conn.execute(
+        "INSERT INTO accounts (id, user_id, name, account_type, institution, last4)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![&id, user_id, name, account_type, institution, last4],
+    )?;
+
+    get_account_by_id(conn, &id)?.ok_or(super::DbError::NotFound)
+}
+
+pub fn update_account(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    account_type: &str,
+    institution: Option<&str>,
+    last4: Option<&str>,
+) -> DbResult<()> {
+    validate_account(account_type, last4)?;
+    conn.execute(
+        "UPDATE accounts
+         SET name = ?2, account_type = ?3, institution = ?4, last4 = ?5, updated_at = datetime('now')
+         WHERE id = ?1",
+        params![id, name, account_type, institution, last4],
+    )?;
+    Ok(())
+}
+
+/// Delete an account. Its transactions keep existing with no account.
+pub fn delete_account(conn: &Connection, id: &str) -> DbResult<()> {
+    conn.execute("DELETE FROM accounts WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+/// Find the account a receipt's payment method refers to, creating it on first sight.
+/// Cards are matched on their last four digits (and network, when both are known);
+/// wallet balances on institution and type.
+pub fn resolve_account(
+    conn: &Connection,
+    user_id: &str,
+    institution: Option<&str>,
+    account_type: &str,
+    last4: Option<&str>,
+) -> DbResult<String> {
+    let existing = match last4 {
+        Some(last4) => conn
+            .query_row(
+                "SELECT id FROM accounts
+                 WHERE user_id = ?1 AND last4 = ?2
+                   AND (?3 IS NULL OR institution IS NULL OR institution = ?3 COLLATE NOCASE)
+                 ORDER BY institution IS NULL, created_at
+                 LIMIT 1",
+                params![user_id, last4, institution],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?,
+        None => conn
+            .query_row(
+                "SELECT id FROM accounts
+                 WHERE user_id = ?1 AND account_type = ?2 AND last4 IS NULL
+                   AND COALESCE(institution, '') = COALESCE(?3, '') COLLATE NOCASE
+                 ORDER BY created_at
+                 LIMIT 1",
+                params![user_id, account_type, institution],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?,
+    };
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    let label = institution.unwrap_or(match account_type {
+        "debit_card" => "Debit card",
+        "credit_card" => "Card",
+        "wallet" => "Wallet",
+        _ => "Account",
+    });
+    let name = match last4 {
+        Some(last4) => format!("{} ••{}", label, last4),
+        None if account_type == "wallet" => format!("{} balance", label),
+        None => label.to_string(),
+    };
+
+    // A user-created account may already have the generated name
+    if let Some(id) = conn
+        .query_row(
+            "SELECT id FROM accounts WHERE user_id = ?1 AND name = ?2",
+            params![user_id, &name],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+    {
+        return Ok(id);
+    }
+
+    Ok(create_account(conn, user_id, &name, account_type, institution, last4)?.id)
+}
+
+// ============================================================================
+// Transaction Queries
+// ============================================================================
 
+/// Columns selected for a `TransactionWithCategory`, in the order expected by
+/// `transaction_with_category_from_row`. Assumes `transactions t LEFT JOIN categories c`.
+pub const TRANSACTION_WITH_CATEGORY_COLUMNS: &str =
+    "t.id, t.user_id, t.category_id, t.merchant, t.merchant_normalized,
+     t.amount, t.transaction_date, t.provider, t.source_hash, t.notes,
+     t.created_at, t.updated_at,
+     c.name as category_name, c.color as category_color, c.icon as category_icon,
+     t.account_id, (SELECT a.name FROM accounts a WHERE a.id = t.account_id) as account_name,
+     t.direction, t.merchant_id";
+
+pub fn transaction_with_category_from_row(
+    row: &rusqlite::Row,
+) -> rusqlite::Result<TransactionWithCategory> {
+    Ok(TransactionWithCategory {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        category_id: row.get(2)?,
+        merchant: row.get(3)?,
+        merchant_normalized: row.get(4)?,
+        amount: row.get(5)?,
+        transaction_date: row.get(6)?,
+        provider: row.get(7)?,
+        source_hash: row.get(8)?,
+        notes: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        category_name: row.get(12)?,
+        category_color: row.get(13)?,
+        category_icon: row.get(14)?,
+        account_id: row.get(15)?,
+        account_name: row.get(16)?,
+        direction: row.get(17)?,
+        merchant_id: row.get(18)?,
+    })
+}
+
+/// Append `AND ...` clauses for each set filter to a query over `transactions t`
+pub fn append_transaction_filters(
+    sql: &mut String,
+    params: &mut Vec<Box<dyn rusqlite::ToSql>>,
+    filters: &TransactionFilters,
+) {
     if let Some(ref search) = filters.search {
-        if !search.is_empty() {
-            sql.push_str(" AND (t.merchant LIKE ? OR t.merchant_normalized LIKE ?)");
-            let pattern = format!("%{}%", search);
-            params.push(Box::new(pattern.clone()));
-            params.push(Box::new(pattern));
+        if let Some(match_query) = super::search::build_match_query(search) {
+            sql.push_str(
+                " AND t.id IN (SELECT transaction_id FROM transactions_fts WHERE transactions_fts MATCH ?)",
+            );
+            params.push(Box::new(match_query));
         }
     }
 
+    // A category includes its subcategories and transactions split into it
     if let Some(ref category_id) = filters.category_id {
-        sql.push_str(" AND t.category_id = ?");
+        sql.push_str(&format!(
+            " AND (t.category_id IN ({0}) OR t.id IN (
+                 SELECT transaction_id FROM transaction_splits WHERE category_id IN ({0})
+             ))",
+            category_subtree_sql("?")
+        ));
+        params.push(Box::new(category_id.clone()));
         params.push(Box::new(category_id.clone()));
     }
 
@@ -257,6 +979,21 @@ pub fn get_transactions(
         params.push(Box::new(provider.clone()));
     }
 
+    if let Some(ref account_id) = filters.account_id {
+        sql.push_str(" AND t.account_id = ?");
+        params.push(Box::new(account_id.clone()));
+    }
+
+    if let Some(ref direction) = filters.direction {
+        sql.push_str(" AND t.direction = ?");
+        params.push(Box::new(direction.clone()));
+    }
+
+    if let Some(ref category_source) = filters.category_source {
+        sql.push_str(" AND t.category_source = ?");
+        params.push(Box::new(category_source.clone()));
+    }
+
     if let Some(ref start_date) = filters.start_date {
         sql.push_str(" AND t.transaction_date >= ?");
         params.push(Box::new(start_date.clone()));
@@ -276,95 +1013,456 @@ pub fn get_transactions(
         sql.push_str(" AND t.amount <= ?");
         params.push(Box::new(max_amount));
     }
+}
+
+pub fn get_transactions(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+) -> DbResult<Vec<TransactionWithCategory>> {
+    let mut sql = format!(
+        "SELECT {}
+         FROM transactions t
+         LEFT JOIN categories c ON t.category_id = c.id
+         WHERE t.user_id = ?",
+        TRANSACTION_WITH_CATEGORY_COLUMNS
+    );
+
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(user_id.to_string())];
+    append_transaction_filters(&mut sql, &mut params, filters);
 
     sql.push_str(" ORDER BY t.transaction_date DESC, t.created_at DESC");
 
     let mut stmt = conn.prepare(&sql)?;
     let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
 
-    let transactions = stmt
-        .query_map(param_refs.as_slice(), |row| {
-            Ok(TransactionWithCategory {
+    let transactions = stmt
+        .query_map(param_refs.as_slice(), transaction_with_category_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(transactions)
+}
+
+/// Get one page of transactions using keyset pagination.
+///
+/// Rows are ordered by the requested sort key with `id` as a tie-breaker, so a cursor
+/// stays valid while new transactions are being imported.
+pub fn get_transactions_page(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+    page: &TransactionPageRequest,
+) -> DbResult<TransactionPage> {
+    let sort_expr = match page.sort {
+        TransactionSortKey::Date => "t.transaction_date",
+        TransactionSortKey::Amount => "t.amount",
+        TransactionSortKey::Merchant => "t.merchant_normalized",
+        TransactionSortKey::Category => "COALESCE(c.name, '')",
+    };
+    let (order, comparison) = match page.direction {
+        SortDirection::Asc => ("ASC", ">"),
+        SortDirection::Desc => ("DESC", "<"),
+    };
+    let limit = page.limit.clamp(1, MAX_PAGE_SIZE);
+
+    let mut where_sql = String::from(" WHERE t.user_id = ?");
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(user_id.to_string())];
+    append_transaction_filters(&mut where_sql, &mut params, filters);
+
+    // Totals cover the whole filter, not just this page
+    let (total_count, total_amount) = if page.include_totals {
+        let totals_sql = format!(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(CASE WHEN t.direction = 'income' THEN -t.amount ELSE t.amount END), 0)
+             FROM transactions t
+             LEFT JOIN categories c ON t.category_id = c.id{}",
+            where_sql
+        );
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        conn.query_row(&totals_sql, param_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?
+    } else {
+        (0, 0)
+    };
+
+    if let Some(ref cursor) = page.cursor {
+        let (value, id) = decode_page_cursor(cursor)?;
+        where_sql.push_str(&format!(" AND ({}, t.id) {} (?, ?)", sort_expr, comparison));
+        params.push(value);
+        params.push(Box::new(id));
+    }
+
+    let sql = format!(
+        "SELECT {}, {} as sort_value
+         FROM transactions t
+         LEFT JOIN categories c ON t.category_id = c.id{}
+         ORDER BY sort_value {order}, t.id {order}
+         LIMIT ?",
+        TRANSACTION_WITH_CATEGORY_COLUMNS,
+        sort_expr,
+        where_sql,
+        order = order
+    );
+    // Fetch one extra row to learn whether another page exists
+    params.push(Box::new(limit + 1));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    let mut rows = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok((
+                transaction_with_category_from_row(row)?,
+                row.get::<_, rusqlite::types::Value>(19)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last()
+            .map(|(transaction, value)| encode_page_cursor(value, &transaction.id))
+    } else {
+        None
+    };
+
+    Ok(TransactionPage {
+        transactions: rows.into_iter().map(|(transaction, _)| transaction).collect(),
+        next_cursor,
+        total_count,
+        total_amount,
+    })
+}
+
+/// Encode the last row's sort value and ID as an opaque cursor
+fn encode_page_cursor(value: &rusqlite::types::Value, id: &str) -> String {
+    use base64::Engine;
+    use rusqlite::types::Value;
+
+    let value = match value {
+        Value::Integer(i) => serde_json::json!(i),
+        Value::Text(s) => serde_json::json!(s),
+        _ => serde_json::Value::Null,
+    };
+    let json = serde_json::json!([value, id]).to_string();
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+}
+
+fn decode_page_cursor(cursor: &str) -> DbResult<(Box<dyn rusqlite::ToSql>, String)> {
+    use base64::Engine;
+
+    let invalid = || super::DbError::InvalidInput("malformed page cursor".to_string());
+
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| invalid())?;
+    let (value, id): (serde_json::Value, String) =
+        serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+    let value: Box<dyn rusqlite::ToSql> = match value {
+        serde_json::Value::Number(n) => Box::new(n.as_i64().ok_or_else(invalid)?),
+        serde_json::Value::String(s) => Box::new(s),
+        _ => return Err(invalid()),
+    };
+
+    Ok((value, id))
+}
+
+pub fn transaction_exists(conn: &Connection, source_hash: &str) -> DbResult<bool> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM transactions WHERE source_hash = ?1",
+        [source_hash],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+pub fn insert_transaction(
+    conn: &Connection,
+    user_id: &str,
+    transaction: &NewTransaction,
+) -> DbResult<String> {
+    let id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO transactions (id, user_id, category_id, category_source, category_reason,
+                                   merchant, merchant_normalized, amount, transaction_date, provider,
+                                   source_hash, source_type, source_ref, receipt_text, account_id,
+                                   direction, merchant_id, sender, category_rule_id, parse_confidence)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+        params![
+            &id,
+            user_id,
+            transaction.category_id,
+            transaction.category_source,
+            transaction.category_reason,
+            transaction.merchant,
+            transaction.merchant_normalized,
+            transaction.amount,
+            transaction.transaction_date,
+            transaction.provider,
+            transaction.source_hash,
+            transaction.source_type,
+            transaction.source_ref,
+            transaction.receipt_text,
+            transaction.account_id,
+            if transaction.is_income { direction::INCOME } else { direction::EXPENSE },
+            transaction.merchant_id,
+            transaction.sender,
+            transaction.category_rule_id,
+            transaction.parse_confidence,
+        ],
+    )?;
+
+    Ok(id)
+}
+
+pub fn insert_transaction_item(
+    conn: &Connection,
+    transaction_id: &str,
+    name: &str,
+    quantity: i32,
+    unit_price: i64,
+    total_price: i64,
+) -> DbResult<String> {
+    let id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO transaction_items (id, transaction_id, name, quantity, unit_price, total_price)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![&id, transaction_id, name, quantity, unit_price, total_price],
+    )?;
+
+    Ok(id)
+}
+
+pub fn insert_transaction_fee(
+    conn: &Connection,
+    transaction_id: &str,
+    kind: &str,
+    label: &str,
+    amount: i64,
+) -> DbResult<String> {
+    let id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO transaction_fees (id, transaction_id, kind, label, amount)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![&id, transaction_id, kind, label, amount],
+    )?;
+
+    Ok(id)
+}
+
+pub fn get_transaction_items(conn: &Connection, transaction_id: &str) -> DbResult<Vec<TransactionItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, transaction_id, name, quantity, unit_price, total_price,
+                category_id, category_source, category_reason
+         FROM transaction_items WHERE transaction_id = ?1 ORDER BY rowid",
+    )?;
+
+    let items = stmt
+        .query_map([transaction_id], |row| {
+            Ok(TransactionItem {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                name: row.get(2)?,
+                quantity: row.get(3)?,
+                unit_price: row.get(4)?,
+                total_price: row.get(5)?,
+                category_id: row.get(6)?,
+                category_source: row.get(7)?,
+                category_reason: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+/// Set an item's own category; None puts it back under the transaction's category
+pub fn update_transaction_item_category(
+    conn: &Connection,
+    item_id: &str,
+    category_id: Option<&str>,
+    category_source: Option<&str>,
+    category_reason: Option<&str>,
+) -> DbResult<()> {
+    conn.execute(
+        "UPDATE transaction_items SET category_id = ?2, category_source = ?3, category_reason = ?4
+         WHERE id = ?1",
+        params![item_id, category_id, category_source, category_reason],
+    )?;
+    Ok(())
+}
+
+/// Splits with the transaction's category filled in for shares that follow it
+pub fn get_transaction_splits(conn: &Connection, transaction_id: &str) -> DbResult<Vec<TransactionSplit>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.transaction_id, COALESCE(s.category_id, t.category_id), c.name, s.amount
+         FROM transaction_splits s
+         JOIN transactions t ON t.id = s.transaction_id
+         LEFT JOIN categories c ON c.id = COALESCE(s.category_id, t.category_id)
+         WHERE s.transaction_id = ?1
+         ORDER BY s.amount DESC, s.rowid",
+    )?;
+
+    let splits = stmt
+        .query_map([transaction_id], |row| {
+            Ok(TransactionSplit {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                category_id: row.get(2)?,
+                category_name: row.get(3)?,
+                amount: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(splits)
+}
+
+/// Replace a transaction's splits. A None category follows the transaction's
+/// category; an empty list leaves the whole amount in the transaction's category.
+pub fn replace_transaction_splits(
+    conn: &Connection,
+    transaction_id: &str,
+    splits: &[(Option<String>, i64)],
+) -> DbResult<()> {
+    conn.execute("DELETE FROM transaction_splits WHERE transaction_id = ?1", [transaction_id])?;
+    for (category_id, amount) in splits {
+        conn.execute(
+            "INSERT INTO transaction_splits (id, transaction_id, category_id, amount) VALUES (?1, ?2, ?3, ?4)",
+            params![uuid::Uuid::new_v4().to_string(), transaction_id, category_id, amount],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn get_transaction_fees(conn: &Connection, transaction_id: &str) -> DbResult<Vec<TransactionFee>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, transaction_id, kind, label, amount
+         FROM transaction_fees WHERE transaction_id = ?1 ORDER BY rowid",
+    )?;
+
+    let fees = stmt
+        .query_map([transaction_id], |row| {
+            Ok(TransactionFee {
                 id: row.get(0)?,
-                user_id: row.get(1)?,
-                category_id: row.get(2)?,
-                merchant: row.get(3)?,
-                merchant_normalized: row.get(4)?,
-                amount: row.get(5)?,
-                transaction_date: row.get(6)?,
-                provider: row.get(7)?,
-                source_hash: row.get(8)?,
-                notes: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-                category_name: row.get(12)?,
-                category_color: row.get(13)?,
-                category_icon: row.get(14)?,
+                transaction_id: row.get(1)?,
+                kind: row.get(2)?,
+                label: row.get(3)?,
+                amount: row.get(4)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(transactions)
+    Ok(fees)
 }
 
-pub fn transaction_exists(conn: &Connection, source_hash: &str) -> DbResult<bool> {
-    let count: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM transactions WHERE source_hash = ?1",
-        [source_hash],
-        |row| row.get(0),
-    )?;
-    Ok(count > 0)
+pub fn get_transaction_detail(
+    conn: &Connection,
+    user_id: &str,
+    transaction_id: &str,
+) -> DbResult<Option<TransactionDetail>> {
+    let sql = format!(
+        "SELECT {}, t.source_type, t.source_ref, t.receipt_text, t.category_source, t.category_reason,
+                t.category_rule_id, t.parse_confidence
+         FROM transactions t
+         LEFT JOIN categories c ON t.category_id = c.id
+         WHERE t.user_id = ?1 AND t.id = ?2",
+        TRANSACTION_WITH_CATEGORY_COLUMNS
+    );
+
+    let row = conn
+        .query_row(&sql, params![user_id, transaction_id], |row| {
+            Ok((
+                transaction_with_category_from_row(row)?,
+                row.get::<_, Option<String>>(19)?,
+                row.get::<_, Option<String>>(20)?,
+                row.get::<_, Option<String>>(21)?,
+                row.get::<_, Option<String>>(22)?,
+                row.get::<_, Option<String>>(23)?,
+                row.get::<_, Option<String>>(24)?,
+                row.get::<_, Option<f64>>(25)?,
+            ))
+        })
+        .optional()?;
+
+    let Some((
+        transaction,
+        source_type,
+        source_ref,
+        receipt_text,
+        category_source,
+        category_reason,
+        category_rule_id,
+        parse_confidence,
+    )) = row
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(TransactionDetail {
+        items: get_transaction_items(conn, &transaction.id)?,
+        fees: get_transaction_fees(conn, &transaction.id)?,
+        splits: get_transaction_splits(conn, &transaction.id)?,
+        transaction,
+        source_type,
+        source_ref,
+        receipt_text,
+        parse_confidence,
+        category_source,
+        category_reason,
+        category_rule_id,
+    }))
 }
 
-pub fn insert_transaction(
+pub fn update_transaction_category(
     conn: &Connection,
-    user_id: &str,
+    transaction_id: &str,
     category_id: Option<&str>,
-    merchant: &str,
-    merchant_normalized: &str,
-    amount: i64,
-    transaction_date: &str,
-    provider: &str,
-    source_hash: &str,
-) -> DbResult<String> {
-    let id = uuid::Uuid::new_v4().to_string();
-
+    category_source: &str,
+    category_reason: &str,
+    category_rule_id: Option<&str>,
+) -> DbResult<()> {
     conn.execute(
-        "INSERT INTO transactions (id, user_id, category_id, merchant, merchant_normalized, amount, transaction_date, provider, source_hash)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        params![&id, user_id, category_id, merchant, merchant_normalized, amount, transaction_date, provider, source_hash],
+        "UPDATE transactions
+         SET category_id = ?2, category_source = ?3, category_reason = ?4, category_rule_id = ?5,
+             updated_at = datetime('now')
+         WHERE id = ?1",
+        params![transaction_id, category_id, category_source, category_reason, category_rule_id],
     )?;
-
-    Ok(id)
+    Ok(())
 }
 
-pub fn insert_transaction_item(
+pub fn update_transaction_account(
     conn: &Connection,
     transaction_id: &str,
-    name: &str,
-    quantity: i32,
-    unit_price: i64,
-    total_price: i64,
-) -> DbResult<String> {
-    let id = uuid::Uuid::new_v4().to_string();
-
+    account_id: Option<&str>,
+) -> DbResult<()> {
     conn.execute(
-        "INSERT INTO transaction_items (id, transaction_id, name, quantity, unit_price, total_price)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![&id, transaction_id, name, quantity, unit_price, total_price],
+        "UPDATE transactions SET account_id = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![transaction_id, account_id],
     )?;
-
-    Ok(id)
+    Ok(())
 }
 
-pub fn update_transaction_category(
+/// Mark a transaction as income or expense, e.g. a Venmo payment imported before
+/// received payments were recognized
+pub fn update_transaction_direction(
     conn: &Connection,
     transaction_id: &str,
-    category_id: Option<&str>,
+    direction: &str,
 ) -> DbResult<()> {
+    if direction != direction::EXPENSE && direction != direction::INCOME {
+        return Err(super::DbError::InvalidInput(format!(
+            "Unknown direction: {}",
+            direction
+        )));
+    }
     conn.execute(
-        "UPDATE transactions SET category_id = ?2, updated_at = datetime('now') WHERE id = ?1",
-        params![transaction_id, category_id],
+        "UPDATE transactions SET direction = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![transaction_id, direction],
     )?;
     Ok(())
 }
@@ -470,6 +1568,15 @@ fn get_budget_period_dates(period: &str, start_date: &str) -> (String, String) {
     }
 }
 
+/// Each transaction's amount per category: one row per split for split transactions,
+/// otherwise the whole amount under the transaction's category
+const CATEGORY_AMOUNTS_SQL: &str = "SELECT t.id AS transaction_id, t.user_id, t.direction, t.transaction_date,
+        COALESCE(s.category_id, t.category_id) AS category_id,
+        COALESCE(s.amount, t.amount) AS amount
+    FROM transactions t
+    LEFT JOIN transaction_splits s ON s.transaction_id = t.id";
+
+/// Spending in a category and its subcategories
 fn get_category_spent(
     conn: &Connection,
     user_id: &str,
@@ -479,9 +1586,13 @@ fn get_category_spent(
 ) -> DbResult<i64> {
     let spent: i64 = conn
         .query_row(
-            "SELECT COALESCE(SUM(amount), 0) FROM transactions
-             WHERE user_id = ?1 AND category_id = ?2
-             AND transaction_date >= ?3 AND transaction_date <= ?4",
+            &format!(
+                "SELECT COALESCE(SUM(amount), 0) FROM ({})
+                 WHERE user_id = ?1 AND category_id IN ({}) AND direction = 'expense'
+                 AND transaction_date >= ?3 AND transaction_date <= ?4",
+                CATEGORY_AMOUNTS_SQL,
+                category_subtree_sql("?2")
+            ),
             params![user_id, category_id, start_date, end_date],
             |row| row.get(0),
         )
@@ -539,6 +1650,8 @@ pub fn delete_budget(conn: &Connection, budget_id: &str) -> DbResult<()> {
 // Dashboard/Reporting Queries
 // ============================================================================
 
+/// Spending per top-level category, with subcategory spending rolled up into
+/// each parent and broken out in `children`
 pub fn get_category_spending(
     conn: &Connection,
     user_id: &str,
@@ -548,42 +1661,111 @@ pub fn get_category_spending(
     let total_spent: i64 = conn
         .query_row(
             "SELECT COALESCE(SUM(amount), 0) FROM transactions
-             WHERE user_id = ?1 AND transaction_date >= ?2 AND transaction_date <= ?3",
+             WHERE user_id = ?1 AND direction = 'expense'
+               AND transaction_date >= ?2 AND transaction_date <= ?3",
             params![user_id, start_date, end_date],
             |row| row.get(0),
         )
         .unwrap_or(0);
 
+    let mut stmt = conn.prepare(&format!(
+        "SELECT category_id, SUM(amount), COUNT(DISTINCT transaction_id)
+         FROM ({})
+         WHERE user_id = ?1 AND direction = 'expense' AND category_id IS NOT NULL
+           AND transaction_date >= ?2 AND transaction_date <= ?3
+         GROUP BY category_id",
+        CATEGORY_AMOUNTS_SQL
+    ))?;
+    let direct: HashMap<String, (i64, i64)> = stmt
+        .query_map(params![user_id, start_date, end_date], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let categories = get_all_categories(conn, user_id)?;
+    let ids: HashSet<&str> = categories.iter().map(|c| c.id.as_str()).collect();
+    let mut children: HashMap<Option<&str>, Vec<&Category>> = HashMap::new();
+    for category in &categories {
+        // A category whose parent is gone is treated as top-level
+        let parent = category.parent_id.as_deref().filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(category);
+    }
+
+    fn rollup(
+        category: &Category,
+        children: &HashMap<Option<&str>, Vec<&Category>>,
+        direct: &HashMap<String, (i64, i64)>,
+        total_spent: i64,
+    ) -> CategorySpending {
+        let mut subcategories: Vec<CategorySpending> = children
+            .get(&Some(category.id.as_str()))
+            .into_iter()
+            .flatten()
+            .map(|child| rollup(child, children, direct, total_spent))
+            .filter(|child| child.total > 0)
+            .collect();
+        subcategories.sort_by_key(|c| std::cmp::Reverse(c.total));
+
+        let (own_total, own_count) = direct.get(&category.id).copied().unwrap_or((0, 0));
+        let total = own_total + subcategories.iter().map(|c| c.total).sum::<i64>();
+        CategorySpending {
+            category_id: category.id.clone(),
+            parent_id: category.parent_id.clone(),
+            category_name: category.name.clone(),
+            category_color: category.color.clone(),
+            category_icon: category.icon.clone(),
+            total,
+            transaction_count: own_count + subcategories.iter().map(|c| c.transaction_count).sum::<i64>(),
+            percentage: if total_spent > 0 {
+                total as f64 / total_spent as f64
+            } else {
+                0.0
+            },
+            children: subcategories,
+        }
+    }
+
+    let mut spending: Vec<CategorySpending> = children
+        .get(&None)
+        .into_iter()
+        .flatten()
+        .map(|category| rollup(category, &children, &direct, total_spent))
+        .filter(|c| c.total > 0)
+        .collect();
+    spending.sort_by_key(|c| std::cmp::Reverse(c.total));
+
+    Ok(spending)
+}
+
+/// Spending per account in the range, including a row with no `account_id` for
+/// transactions that aren't assigned to an account
+pub fn get_account_spending(
+    conn: &Connection,
+    user_id: &str,
+    start_date: &str,
+    end_date: &str,
+) -> DbResult<Vec<AccountSpending>> {
     let mut stmt = conn.prepare(
-        "SELECT c.id, c.name, c.color, c.icon,
-                COALESCE(SUM(t.amount), 0) as total,
+        "SELECT a.id, a.name, a.account_type, a.last4,
+                SUM(t.amount) as total,
                 COUNT(t.id) as count
-         FROM categories c
-         LEFT JOIN transactions t ON c.id = t.category_id
-             AND t.user_id = ?1
-             AND t.transaction_date >= ?2
-             AND t.transaction_date <= ?3
-         WHERE c.user_id = ?1
-         GROUP BY c.id
-         HAVING total > 0
+         FROM transactions t
+         LEFT JOIN accounts a ON a.id = t.account_id
+         WHERE t.user_id = ?1 AND t.direction = 'expense'
+           AND t.transaction_date >= ?2 AND t.transaction_date <= ?3
+         GROUP BY a.id
          ORDER BY total DESC",
     )?;
 
     let spending = stmt
         .query_map(params![user_id, start_date, end_date], |row| {
-            let total: i64 = row.get(4)?;
-            Ok(CategorySpending {
-                category_id: row.get(0)?,
-                category_name: row.get(1)?,
-                category_color: row.get(2)?,
-                category_icon: row.get(3)?,
-                total,
+            Ok(AccountSpending {
+                account_id: row.get(0)?,
+                account_name: row.get(1)?,
+                account_type: row.get(2)?,
+                last4: row.get(3)?,
+                total: row.get(4)?,
                 transaction_count: row.get(5)?,
-                percentage: if total_spent > 0 {
-                    total as f64 / total_spent as f64
-                } else {
-                    0.0
-                },
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -599,10 +1781,12 @@ pub fn get_top_merchants(
     limit: i32,
 ) -> DbResult<Vec<MerchantTotal>> {
     let mut stmt = conn.prepare(
-        "SELECT merchant, SUM(amount) as total, COUNT(*) as count
-         FROM transactions
-         WHERE user_id = ?1 AND transaction_date >= ?2 AND transaction_date <= ?3
-         GROUP BY merchant_normalized
+        "SELECT t.merchant_id, COALESCE(m.name, MIN(t.merchant)), SUM(t.amount) as total, COUNT(*) as count
+         FROM transactions t
+         LEFT JOIN merchants m ON m.id = t.merchant_id
+         WHERE t.user_id = ?1 AND t.direction = 'expense'
+           AND t.transaction_date >= ?2 AND t.transaction_date <= ?3
+         GROUP BY COALESCE(t.merchant_id, t.merchant_normalized)
          ORDER BY total DESC
          LIMIT ?4",
     )?;
@@ -610,9 +1794,10 @@ pub fn get_top_merchants(
     let merchants = stmt
         .query_map(params![user_id, start_date, end_date, limit], |row| {
             Ok(MerchantTotal {
-                merchant: row.get(0)?,
-                total: row.get(1)?,
-                count: row.get(2)?,
+                merchant_id: row.get(0)?,
+                merchant: row.get(1)?,
+                total: row.get(2)?,
+                count: row.get(3)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -625,12 +1810,32 @@ pub fn get_total_spent(
     user_id: &str,
     start_date: &str,
     end_date: &str,
+) -> DbResult<i64> {
+    get_total_by_direction(conn, user_id, direction::EXPENSE, start_date, end_date)
+}
+
+pub fn get_total_income(
+    conn: &Connection,
+    user_id: &str,
+    start_date: &str,
+    end_date: &str,
+) -> DbResult<i64> {
+    get_total_by_direction(conn, user_id, direction::INCOME, start_date, end_date)
+}
+
+fn get_total_by_direction(
+    conn: &Connection,
+    user_id: &str,
+    direction: &str,
+    start_date: &str,
+    end_date: &str,
 ) -> DbResult<i64> {
     let total: i64 = conn
         .query_row(
             "SELECT COALESCE(SUM(amount), 0) FROM transactions
-             WHERE user_id = ?1 AND transaction_date >= ?2 AND transaction_date <= ?3",
-            params![user_id, start_date, end_date],
+             WHERE user_id = ?1 AND direction = ?2
+               AND transaction_date >= ?3 AND transaction_date <= ?4",
+            params![user_id, direction, start_date, end_date],
             |row| row.get(0),
         )
         .unwrap_or(0);
@@ -660,58 +1865,397 @@ pub fn get_transaction_count(
 // Merchant Category Rules
 // ============================================================================
 
-pub fn get_merchant_category_rule(
+const RULE_COLUMNS: &str =
+    "id, name, merchant_pattern, match_type, provider, min_amount, max_amount, days_of_week,
+     item_keyword, sender, category_id, priority, enabled, created_at, updated_at";
+
+fn rule_from_row(row: &rusqlite::Row) -> rusqlite::Result<MerchantCategoryRule> {
+    let match_type: String = row.get(3)?;
+    Ok(MerchantCategoryRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        merchant_pattern: row.get(2)?,
+        is_exact_match: match_type == "exact",
+        match_type,
+        provider: row.get(4)?,
+        min_amount: row.get(5)?,
+        max_amount: row.get(6)?,
+        days_of_week: row.get(7)?,
+        item_keyword: row.get(8)?,
+        sender: row.get(9)?,
+        category_id: row.get(10)?,
+        priority: row.get(11)?,
+        enabled: row.get::<_, i32>(12)? != 0,
+        created_at: row.get(13)?,
+        updated_at: row.get(14)?,
+    })
+}
+
+/// All rules in the order they're evaluated
+pub fn get_merchant_category_rules(
     conn: &Connection,
     user_id: &str,
-    merchant_normalized: &str,
-) -> DbResult<Option<String>> {
-    // First try exact match
-    let exact: Option<String> = conn
+) -> DbResult<Vec<MerchantCategoryRule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM merchant_category_rules
+         WHERE user_id = ?1
+         ORDER BY priority, created_at, id",
+        RULE_COLUMNS
+    ))?;
+
+    let rules = stmt
+        .query_map([user_id], rule_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rules)
+}
+
+pub fn get_merchant_category_rule_by_id(
+    conn: &Connection,
+    id: &str,
+) -> DbResult<Option<MerchantCategoryRule>> {
+    let rule = conn
         .query_row(
-            "SELECT category_id FROM merchant_category_rules
-             WHERE user_id = ?1 AND merchant_pattern = ?2 AND is_exact_match = 1",
-            params![user_id, merchant_normalized],
-            |row| row.get(0),
+            &format!("SELECT {} FROM merchant_category_rules WHERE id = ?1", RULE_COLUMNS),
+            [id],
+            rule_from_row,
         )
         .optional()?;
 
-    if exact.is_some() {
-        return Ok(exact);
+    Ok(rule)
+}
+
+/// Trim a text condition, treating blank as unset
+fn rule_text(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// Validate and normalize a rule: merchant patterns are normalized like
+/// `merchant_normalized` (regexes are kept as written), keywords are lowercased
+pub fn normalize_rule_input(conn: &Connection, user_id: &str, input: &RuleInput) -> DbResult<RuleInput> {
+    let invalid = |message: String| super::DbError::InvalidInput(message);
+
+    if !RULE_MATCH_TYPES.contains(&input.match_type.as_str()) {
+        return Err(invalid(format!("Unknown match type: {}", input.match_type)));
+    }
+    let merchant_pattern = rule_text(&input.merchant_pattern).map(|pattern| match input.match_type.as_str() {
+        "regex" => pattern,
+        "glob" => pattern.to_lowercase(),
+        _ => crate::parser::types::normalize_merchant(&pattern),
+    });
+    if let (Some(pattern), "regex") = (&merchant_pattern, input.match_type.as_str()) {
+        regex::Regex::new(pattern).map_err(|e| invalid(format!("Invalid regex: {}", e)))?;
+    }
+
+    if let (Some(min), Some(max)) = (input.min_amount, input.max_amount) {
+        if min >= max {
+            return Err(invalid("Minimum amount must be below the maximum".to_string()));
+        }
+    }
+
+    let days_of_week = match rule_text(&input.days_of_week) {
+        Some(days) => {
+            let mut parsed = days
+                .split(',')
+                .map(|d| d.trim().parse::<u32>().ok().filter(|d| (1..=7).contains(d)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid(format!("Invalid days of week: {}", days)))?;
+            parsed.sort_unstable();
+            parsed.dedup();
+            Some(parsed.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(","))
+        }
+        None => None,
+    };
+
+    let normalized = RuleInput {
+        name: rule_text(&input.name),
+        merchant_pattern,
+        match_type: input.match_type.clone(),
+        provider: rule_text(&input.provider),
+        min_amount: input.min_amount,
+        max_amount: input.max_amount,
+        days_of_week,
+        item_keyword: rule_text(&input.item_keyword).map(|k| k.to_lowercase()),
+        sender: rule_text(&input.sender).map(|s| s.to_lowercase()),
+        category_id: input.category_id.clone(),
+        priority: input.priority,
+        enabled: input.enabled,
+    };
+
+    let has_condition = normalized.merchant_pattern.is_some()
+        || normalized.provider.is_some()
+        || normalized.min_amount.is_some()
+        || normalized.max_amount.is_some()
+        || normalized.days_of_week.is_some()
+        || normalized.item_keyword.is_some()
+        || normalized.sender.is_some();
+    if !has_condition {
+        return Err(invalid("A rule needs at least one condition".to_string()));
+    }
+
+    get_category_by_id(conn, &normalized.category_id)?
+        .filter(|c| c.user_id == user_id)
+        .ok_or_else(|| invalid("Category not found".to_string()))?;
+
+    Ok(normalized)
+}
+
+/// Replace a rule's conditions and category. Without an explicit priority the rule
+/// keeps its place.
+pub fn update_category_rule(
+    conn: &Connection,
+    user_id: &str,
+    rule_id: &str,
+    input: &RuleInput,
+) -> DbResult<MerchantCategoryRule> {
+    let input = normalize_rule_input(conn, user_id, input)?;
+
+    let updated = conn.execute(
+        "UPDATE merchant_category_rules
+         SET name = ?3, merchant_pattern = ?4, match_type = ?5, provider = ?6, min_amount = ?7,
+             max_amount = ?8, days_of_week = ?9, item_keyword = ?10, sender = ?11, category_id = ?12,
+             priority = COALESCE(?13, priority), enabled = ?14, updated_at = datetime('now')
+         WHERE id = ?1 AND user_id = ?2",
+        params![
+            rule_id,
+            user_id,
+            input.name,
+            input.merchant_pattern,
+            input.match_type,
+            input.provider,
+            input.min_amount,
+            input.max_amount,
+            input.days_of_week,
+            input.item_keyword,
+            input.sender,
+            input.category_id,
+            input.priority,
+            input.enabled,
+        ],
+    )?;
+    if updated == 0 {
+        return Err(super::DbError::NotFound);
+    }
+
+    get_merchant_category_rule_by_id(conn, rule_id)?.ok_or(super::DbError::NotFound)
+}
+
+/// Delete a rule. Transactions it categorized keep their category.
+pub fn delete_category_rule(conn: &Connection, user_id: &str, rule_id: &str) -> DbResult<()> {
+    let deleted = conn.execute(
+        "DELETE FROM merchant_category_rules WHERE id = ?1 AND user_id = ?2",
+        params![rule_id, user_id],
+    )?;
+    if deleted == 0 {
+        return Err(super::DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Create a rule. Without an explicit priority it's tried after every existing rule.
+pub fn create_category_rule(
+    conn: &Connection,
+    user_id: &str,
+    input: &RuleInput,
+) -> DbResult<MerchantCategoryRule> {
+    let input = normalize_rule_input(conn, user_id, input)?;
+    let id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO merchant_category_rules
+             (id, user_id, name, merchant_pattern, match_type, provider, min_amount, max_amount,
+              days_of_week, item_keyword, sender, category_id, priority, enabled)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,
+                 COALESCE(?13, (SELECT COALESCE(MAX(priority), 0) + 1 FROM merchant_category_rules WHERE user_id = ?2)),
+                 ?14)",
+        params![
+            &id,
+            user_id,
+            input.name,
+            input.merchant_pattern,
+            input.match_type,
+            input.provider,
+            input.min_amount,
+            input.max_amount,
+            input.days_of_week,
+            input.item_keyword,
+            input.sender,
+            input.category_id,
+            input.priority,
+            input.enabled,
+        ],
+    )?;
+
+    get_merchant_category_rule_by_id(conn, &id)?.ok_or(super::DbError::NotFound)
+}
+
+/// Point a simple merchant rule at `category_id`, creating it if needed. Exact
+/// rules go first so they beat broader rules; contains rules go last.
+pub fn set_merchant_category_rule(
+    conn: &Connection,
+    user_id: &str,
+    merchant_pattern: &str,
+    category_id: &str,
+    is_exact_match: bool,
+) -> DbResult<()> {
+    let match_type = if is_exact_match { "exact" } else { "contains" };
+    let pattern = crate::parser::types::normalize_merchant(merchant_pattern);
+
+    let updated = conn.execute(
+        "UPDATE merchant_category_rules SET category_id = ?4, updated_at = datetime('now')
+         WHERE user_id = ?1 AND merchant_pattern = ?2 AND match_type = ?3
+           AND provider IS NULL AND min_amount IS NULL AND max_amount IS NULL
+           AND days_of_week IS NULL AND item_keyword IS NULL AND sender IS NULL",
+        params![user_id, &pattern, match_type, category_id],
+    )?;
+    if updated > 0 {
+        return Ok(());
     }
 
-    // Then try pattern match
-    let pattern: Option<String> = conn
+    let priority: i64 = conn.query_row(
+        "SELECT CASE WHEN ?2 THEN COALESCE(MIN(priority), 1) - 1 ELSE COALESCE(MAX(priority), 0) + 1 END
+         FROM merchant_category_rules WHERE user_id = ?1",
+        params![user_id, is_exact_match],
+        |row| row.get(0),
+    )?;
+    create_category_rule(
+        conn,
+        user_id,
+        &RuleInput {
+            name: None,
+            merchant_pattern: Some(pattern),
+            match_type: match_type.to_string(),
+            provider: None,
+            min_amount: None,
+            max_amount: None,
+            days_of_week: None,
+            item_keyword: None,
+            sender: None,
+            category_id: category_id.to_string(),
+            priority: Some(priority),
+            enabled: true,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Add a rule from another database, or update the category of an existing rule
+/// with the same conditions. New rules go after existing ones, in import order;
+/// `input.priority` is ignored.
+pub fn import_category_rule(
+    conn: &Connection,
+    user_id: &str,
+    input: &RuleInput,
+) -> DbResult<MerchantCategoryRule> {
+    let input = normalize_rule_input(conn, user_id, &RuleInput { priority: None, ..input.clone() })?;
+
+    let existing: Option<String> = conn
         .query_row(
-            "SELECT category_id FROM merchant_category_rules
-             WHERE user_id = ?1 AND is_exact_match = 0 AND ?2 LIKE '%' || merchant_pattern || '%'
-             ORDER BY LENGTH(merchant_pattern) DESC
+            "SELECT id FROM merchant_category_rules
+             WHERE user_id = ?1 AND match_type = ?3
+               AND merchant_pattern IS ?2 AND provider IS ?4 AND min_amount IS ?5 AND max_amount IS ?6
+               AND days_of_week IS ?7 AND item_keyword IS ?8 AND sender IS ?9
              LIMIT 1",
-            params![user_id, merchant_normalized],
+            params![
+                user_id,
+                input.merchant_pattern,
+                input.match_type,
+                input.provider,
+                input.min_amount,
+                input.max_amount,
+                input.days_of_week,
+                input.item_keyword,
+                input.sender,
+            ],
             |row| row.get(0),
         )
         .optional()?;
 
-    Ok(pattern)
+    match existing {
+        Some(id) => {
+            conn.execute(
+                "UPDATE merchant_category_rules
+                 SET category_id = ?2, name = COALESCE(?3, name), enabled = ?4, updated_at = datetime('now')
+                 WHERE id = ?1",
+                params![&id, &input.category_id, input.name, input.enabled],
+            )?;
+            get_merchant_category_rule_by_id(conn, &id)?.ok_or(super::DbError::NotFound)
+        }
+        None => create_category_rule(conn, user_id, &input),
+    }
 }
 
-pub fn set_merchant_category_rule(
+// ============================================================================
+// Merchant Knowledge Base Overrides
+// ============================================================================
+
+/// The user's category for a pattern from (or missing from) the bundled merchant knowledge base
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantPatternOverride {
+    pub id: String,
+    pub user_id: String,
+    /// Normalized merchant words
+    pub pattern: String,
+    /// None disables the bundled entry with the same pattern
+    pub category_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+pub fn get_merchant_pattern_overrides(conn: &Connection, user_id: &str) -> DbResult<Vec<MerchantPatternOverride>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_id, pattern, category_id, created_at, updated_at
+         FROM merchant_pattern_overrides WHERE user_id = ?1 ORDER BY pattern",
+    )?;
+    let overrides = stmt
+        .query_map([user_id], |row| {
+            Ok(MerchantPatternOverride {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                pattern: row.get(2)?,
+                category_id: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(overrides)
+}
+
+/// Set the category for a knowledge base pattern, or disable it with `None`
+pub fn set_merchant_pattern_override(
     conn: &Connection,
     user_id: &str,
-    merchant_pattern: &str,
-    category_id: &str,
-    is_exact_match: bool,
+    pattern: &str,
+    category_id: Option<&str>,
 ) -> DbResult<()> {
-    let id = uuid::Uuid::new_v4().to_string();
+    let pattern = crate::parser::types::normalize_merchant(pattern);
+    if pattern.is_empty() {
+        return Err(super::DbError::InvalidInput("Pattern is required".to_string()));
+    }
 
     conn.execute(
-        "INSERT OR REPLACE INTO merchant_category_rules (id, user_id, merchant_pattern, category_id, is_exact_match)
-         VALUES (
-             COALESCE((SELECT id FROM merchant_category_rules WHERE user_id = ?2 AND merchant_pattern = ?3), ?1),
-             ?2, ?3, ?4, ?5
-         )",
-        params![&id, user_id, merchant_pattern, category_id, is_exact_match],
+        "INSERT INTO merchant_pattern_overrides (id, user_id, pattern, category_id)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(user_id, pattern) DO UPDATE SET
+             category_id = excluded.category_id,
+             updated_at = datetime('now')",
+        params![uuid::Uuid::new_v4().to_string(), user_id, pattern, category_id],
     )?;
+    Ok(())
+}
 
+pub fn delete_merchant_pattern_override(conn: &Connection, user_id: &str, id: &str) -> DbResult<()> {
+    conn.execute(
+        "DELETE FROM merchant_pattern_overrides WHERE user_id = ?1 AND id = ?2",
+        params![user_id, id],
+    )?;
     Ok(())
 }
 
@@ -720,14 +2264,16 @@ pub fn get_category_from_previous_transaction(
     conn: &Connection,
     user_id: &str,
     merchant_normalized: &str,
+    exclude_transaction_id: Option<&str>,
 ) -> DbResult<Option<String>> {
     let category_id: Option<String> = conn
         .query_row(
             "SELECT category_id FROM transactions
              WHERE user_id = ?1 AND merchant_normalized = ?2 AND category_id IS NOT NULL
+               AND id IS NOT ?3
              ORDER BY created_at DESC
              LIMIT 1",
-            params![user_id, merchant_normalized],
+            params![user_id, merchant_normalized, exclude_transaction_id],
             |row| row.get(0),
         )
         .optional()?;
@@ -735,4 +2281,351 @@ pub fn get_category_from_previous_transaction(
     Ok(category_id)
 }
 
+// ============================================================================
+// Settings
+// ============================================================================
+
+pub fn get_setting(conn: &Connection, user_id: &str, key: &str) -> DbResult<Option<String>> {
+    let value = conn
+        .query_row(
+            "SELECT value FROM settings WHERE user_id = ?1 AND key = ?2",
+            params![user_id, key],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(value)
+}
+
+pub fn set_setting(conn: &Connection, user_id: &str, key: &str, value: &str) -> DbResult<()> {
+    conn.execute(
+        "INSERT INTO settings (id, user_id, key, value) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (user_id, key) DO UPDATE SET value = excluded.value, updated_at = datetime('now')",
+        params![uuid::Uuid::new_v4().to_string(), user_id, key, value],
+    )?;
+
+    Ok(())
+}
+
 use chrono::Datelike;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    #[test]
+    fn test_category_hierarchy() {
+        // Categories seeded before v10 get the default hierarchy on upgrade
+        let conn = schema::open_in_memory_at_version(9);
+        for name in ["Food & Dining", "Food Delivery", "Transportation"] {
+            conn.execute(
+                "INSERT INTO categories (id, user_id, name, icon, color, is_system)
+                 VALUES (?1, 'local', ?1, 'tag', '#000', 1)",
+                [name],
+            )
+            .unwrap();
+        }
+        schema::run_migrations(&conn).unwrap();
+        let parent_of = |name: &str| -> Option<String> {
+            conn.query_row("SELECT parent_id FROM categories WHERE name = ?1", [name], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(parent_of("Food Delivery").as_deref(), Some("Food & Dining"));
+        assert_eq!(parent_of("Groceries").as_deref(), Some("Food & Dining"));
+        assert_eq!(parent_of("Transportation"), None);
+
+        // No cycles
+        assert!(set_category_parent(&conn, "Food & Dining", Some("Groceries")).is_err());
+        assert!(set_category_parent(&conn, "Food & Dining", Some("Food & Dining")).is_err());
+
+        // Child spending rolls up into the parent
+        let today = chrono::Local::now().date_naive().format("%Y-%m-%d").to_string();
+        for (category, amount) in [("Food Delivery", 1500), ("Food & Dining", 500)] {
+            let id = insert(
+                &conn,
+                &NewTransaction {
+                    transaction_date: &today,
+                    ..new_transaction(category, amount, category)
+                },
+            );
+            update_transaction_category(&conn, &id, Some(category), "manual", "", None).unwrap();
+        }
+        let spending = get_category_spending(&conn, "local", &today, &today).unwrap();
+        assert_eq!(spending.len(), 1);
+        assert_eq!(spending[0].total, 2000);
+        assert_eq!(spending[0].children[0].category_name, "Food Delivery");
+
+        set_budget(&conn, "local", "Food & Dining", 3000, "monthly").unwrap();
+        assert_eq!(get_all_budgets(&conn, "local").unwrap()[0].spent, 2000);
+    }
+
+    #[test]
+    fn test_merge_categories() {
+        let conn = open_seeded();
+        let coffee = create_category(&conn, "local", "Coffee", "cup", "#000", None).unwrap();
+        let cafes = create_category(&conn, "local", "Cafes", "cup", "#111", None).unwrap();
+        let id = insert(
+            &conn,
+            &NewTransaction {
+                category_id: Some(&coffee.id),
+                merchant: "Blue Bottle",
+                ..new_transaction("blue bottle", 500, "blue-bottle")
+            },
+        );
+        set_merchant_category_rule(&conn, "local", "blue bottle", &coffee.id, true).unwrap();
+        set_budget(&conn, "local", &coffee.id, 2000, "monthly").unwrap();
+        set_budget(&conn, "local", &cafes.id, 3000, "monthly").unwrap();
+
+        // In-use categories aren't silently emptied
+        assert!(delete_category(&conn, &coffee.id).is_err());
+
+        let result = merge_categories(&conn, "local", std::slice::from_ref(&coffee.id), &cafes.id).unwrap();
+        assert_eq!(result.transactions_moved, 1);
+        assert_eq!(result.rules_moved, 1);
+        assert!(get_category_by_id(&conn, &coffee.id).unwrap().is_none());
+        let detail = get_transaction_detail(&conn, "local", &id).unwrap().unwrap();
+        assert_eq!(detail.transaction.category_id.as_deref(), Some(cafes.id.as_str()));
+        let budgets = get_all_budgets(&conn, "local").unwrap();
+        assert_eq!(budgets.len(), 1);
+        assert_eq!(budgets[0].amount, 5000);
+
+        // Renamed system categories are still found by the categorizer
+        let income = category(&conn, "Income");
+        update_category(&conn, &income, "Paychecks", "wallet", "#000").unwrap();
+        assert_eq!(find_category_by_name(&conn, "local", "Income").unwrap(), Some(income.clone()));
+        set_category_hidden(&conn, &income, true).unwrap();
+        assert_eq!(find_category_by_name(&conn, "local", "Income").unwrap(), None);
+    }
+
+    /// Every page of `filters`, following cursors from the first
+    fn all_pages(
+        conn: &Connection,
+        filters: &TransactionFilters,
+        mut request: TransactionPageRequest,
+    ) -> Vec<Vec<String>> {
+        let mut pages = Vec::new();
+        loop {
+            let page = get_transactions_page(conn, "local", filters, &request).unwrap();
+            pages.push(page.transactions.iter().map(|t| t.merchant.clone()).collect());
+            match page.next_cursor {
+                Some(cursor) => request.cursor = Some(cursor),
+                None => return pages,
+            }
+        }
+    }
+
+    #[test]
+    fn test_transactions_page() {
+        let conn = open_seeded();
+        for (merchant, amount, date) in [
+            ("a", 1000, "2024-03-01"),
+            ("b", 3000, "2024-03-02"),
+            ("c", 2000, "2024-03-02"),
+            ("d", 2000, "2024-03-03"),
+            ("e", 500, "2024-03-04"),
+        ] {
+            insert(
+                &conn,
+                &NewTransaction {
+                    transaction_date: date,
+                    ..new_transaction(merchant, amount, merchant)
+                },
+            );
+        }
+        insert(
+            &conn,
+            &NewTransaction {
+                is_income: true,
+                ..new_transaction("refund", 700, "refund")
+            },
+        );
+
+        // Ties on the sort value are broken by ID, so no row is skipped or repeated
+        let filters = TransactionFilters {
+            max_amount: Some(5000),
+            ..Default::default()
+        };
+        let by_amount = TransactionPageRequest {
+            sort: TransactionSortKey::Amount,
+            direction: SortDirection::Asc,
+            limit: 2,
+            ..Default::default()
+        };
+        let pages = all_pages(&conn, &filters, by_amount);
+        assert_eq!(pages.len(), 3);
+        let merchants: Vec<&str> = pages.iter().flatten().map(String::as_str).collect();
+        assert_eq!(&merchants[..3], ["e", "refund", "a"]);
+        assert_eq!(merchants[5], "b");
+        let mut tied = merchants[3..5].to_vec();
+        tied.sort();
+        assert_eq!(tied, ["c", "d"]);
+
+        // Totals cover the whole filter, with income subtracted
+        let dated = TransactionFilters {
+            start_date: Some("2024-03-02".to_string()),
+            ..Default::default()
+        };
+        let page = get_transactions_page(&conn, "local", &dated, &TransactionPageRequest::default()).unwrap();
+        assert_eq!(page.total_count, 4);
+        assert_eq!(page.total_amount, 3000 + 2000 + 2000 + 500);
+        let one_row = TransactionPageRequest {
+            limit: 1,
+            ..Default::default()
+        };
+        let everything = get_transactions_page(&conn, "local", &Default::default(), &one_row).unwrap();
+        assert_eq!(everything.transactions.len(), 1);
+        assert_eq!(everything.total_count, 6);
+        assert_eq!(everything.total_amount, 1000 + 3000 + 2000 + 2000 + 500 - 700);
+
+        let without_totals = TransactionPageRequest {
+            include_totals: false,
+            ..Default::default()
+        };
+        assert_eq!(get_transactions_page(&conn, "local", &dated, &without_totals).unwrap().total_count, 0);
+    }
+
+    #[test]
+    fn test_page_cursor_round_trip() {
+        use rusqlite::types::{ToSqlOutput, Value};
+        use rusqlite::ToSql;
+
+        let (value, id) = decode_page_cursor(&encode_page_cursor(&Value::Integer(1500), "t1")).unwrap();
+        assert_eq!(id, "t1");
+        assert_eq!(value.to_sql().unwrap(), ToSqlOutput::from(1500i64));
+
+        let (value, id) = decode_page_cursor(&encode_page_cursor(&Value::Text("blue bottle".into()), "t2")).unwrap();
+        assert_eq!(id, "t2");
+        assert_eq!(value.to_sql().unwrap(), ToSqlOutput::from("blue bottle"));
+
+        for cursor in ["not base64!", "W10", &encode_page_cursor(&Value::Null, "t3")] {
+            assert!(matches!(decode_page_cursor(cursor), Err(crate::db::DbError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn test_transaction_detail() {
+        let conn = open_seeded();
+        let travel = category(&conn, "Travel");
+        let id = insert(
+            &conn,
+            &NewTransaction {
+                category_id: Some(&travel),
+                category_source: Some("user_rule"),
+                category_reason: Some("Matches your rule \"hotel\""),
+                source_ref: Some("msg-1"),
+                receipt_text: Some("Thanks for staying with us"),
+                parse_confidence: Some(0.9),
+                source_type: "gmail",
+                ..new_transaction("hotel lumen", 21500, "hotel")
+            },
+        );
+        insert_transaction_item(&conn, &id, "Room, 1 night", 1, 20000, 20000).unwrap();
+        insert_transaction_fee(&conn, &id, "tax", "Occupancy tax", 1500).unwrap();
+
+        let detail = get_transaction_detail(&conn, "local", &id).unwrap().unwrap();
+        assert_eq!(detail.transaction.category_name.as_deref(), Some("Travel"));
+        assert_eq!(detail.source_type.as_deref(), Some("gmail"));
+        assert_eq!(detail.source_ref.as_deref(), Some("msg-1"));
+        assert_eq!(detail.receipt_text.as_deref(), Some("Thanks for staying with us"));
+        assert_eq!(detail.parse_confidence, Some(0.9));
+        assert_eq!(detail.category_source.as_deref(), Some("user_rule"));
+        assert_eq!(detail.category_reason.as_deref(), Some("Matches your rule \"hotel\""));
+        assert_eq!(detail.items.len(), 1);
+        assert_eq!(detail.items[0].name, "Room, 1 night");
+        assert_eq!(detail.fees[0].amount, 1500);
+        assert!(detail.splits.is_empty());
+
+        assert!(get_transaction_detail(&conn, "someone-else", &id).unwrap().is_none());
+        assert!(get_transaction_detail(&conn, "local", "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_category_source_and_rule() {
+        let conn = open_seeded();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        let dining = category(&conn, "Food & Dining");
+        let rule = create_category_rule(&conn, "local", &RuleInput::for_merchant("blue bottle", "contains", &dining))
+            .unwrap();
+
+        let coffee = insert(
+            &conn,
+            &NewTransaction {
+                category_source: Some("provider_default"),
+                ..new_transaction("blue bottle coffee", 550, "coffee")
+            },
+        );
+        insert(
+            &conn,
+            &NewTransaction {
+                category_source: Some("manual"),
+                ..new_transaction("hardware store", 2500, "hardware")
+            },
+        );
+
+        let by_source = |source: &str| {
+            let filters = TransactionFilters {
+                category_source: Some(source.to_string()),
+                ..Default::default()
+            };
+            get_transactions(&conn, "local", &filters).unwrap()
+        };
+        assert_eq!(by_source("provider_default").len(), 1);
+        assert!(by_source("user_rule").is_empty());
+
+        let reason = "Matches your rule \"blue bottle\"";
+        update_transaction_category(&conn, &coffee, Some(&dining), "user_rule", reason, Some(&rule.id)).unwrap();
+        let matched = by_source("user_rule");
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].id, coffee);
+        assert!(by_source("provider_default").is_empty());
+        let detail = get_transaction_detail(&conn, "local", &coffee).unwrap().unwrap();
+        assert_eq!(detail.category_rule_id.as_deref(), Some(rule.id.as_str()));
+        assert_eq!(detail.category_reason.as_deref(), Some(reason));
+
+        // Recategorizing by hand clears the rule
+        update_transaction_category(&conn, &coffee, Some(&dining), "manual", "Set by you", None).unwrap();
+        let detail = get_transaction_detail(&conn, "local", &coffee).unwrap().unwrap();
+        assert_eq!(detail.category_rule_id, None);
+        assert_eq!(detail.category_source.as_deref(), Some("manual"));
+
+        // Deleting the rule keeps the category but forgets which rule set it
+        update_transaction_category(&conn, &coffee, Some(&dining), "user_rule", reason, Some(&rule.id)).unwrap();
+        delete_category_rule(&conn, "local", &rule.id).unwrap();
+        let detail = get_transaction_detail(&conn, "local", &coffee).unwrap().unwrap();
+        assert_eq!(detail.category_rule_id, None);
+        assert_eq!(detail.transaction.category_id.as_deref(), Some(dining.as_str()));
+        update_transaction_category(&conn, &coffee, Some(&dining), "manual", "Set by you", None).unwrap();
+        assert_eq!(by_source("manual").len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_account() {
+        let conn = open_seeded();
+
+        // First sight of a card creates it, later receipts reuse it
+        let visa = resolve_account(&conn, "local", Some("Visa"), "credit_card", Some("4821")).unwrap();
+        assert_eq!(resolve_account(&conn, "local", Some("VISA"), "credit_card", Some("4821")).unwrap(), visa);
+        let accounts = get_accounts(&conn, "local").unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].name, "Visa ••4821");
+        assert_eq!(accounts[0].account_type, "credit_card");
+
+        // Without a network, the last four digits alone pick the card
+        assert_eq!(resolve_account(&conn, "local", None, "debit_card", Some("4821")).unwrap(), visa);
+
+        // Same digits on another network are a different card
+        let amex = resolve_account(&conn, "local", Some("Amex"), "credit_card", Some("4821")).unwrap();
+        assert_ne!(amex, visa);
+
+        let debit = resolve_account(&conn, "local", None, "debit_card", Some("1234")).unwrap();
+        let venmo = resolve_account(&conn, "local", Some("Venmo"), "wallet", None).unwrap();
+        assert_eq!(resolve_account(&conn, "local", Some("venmo"), "wallet", None).unwrap(), venmo);
+        let names: Vec<String> = get_accounts(&conn, "local").unwrap().into_iter().map(|a| a.name).collect();
+        assert_eq!(names, vec!["Amex ••4821", "Debit card ••1234", "Venmo balance", "Visa ••4821"]);
+
+        // A card found later with its network matches the account created without one
+        assert_eq!(resolve_account(&conn, "local", Some("Mastercard"), "debit_card", Some("1234")).unwrap(), debit);
+    }
+}
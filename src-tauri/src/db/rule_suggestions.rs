@@ -0,0 +1,189 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::queries::{self, MerchantCategoryRule, RuleInput};
+use super::{DbError, DbResult};
+
+/// A merchant rule proposed after the user moved the merchant's transactions to the
+/// same category several times in a row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSuggestion {
+    pub id: String,
+    pub user_id: String,
+    pub merchant_normalized: String,
+    pub category_id: String,
+    pub category_name: Option<String>,
+    /// Consistent manual assignments seen when the suggestion was last updated
+    pub assignment_count: i64,
+    /// 'pending', 'accepted' or 'rejected'
+    pub status: String,
+    /// The rule created on acceptance
+    pub rule_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const SUGGESTION_COLUMNS: &str = "s.id, s.user_id, s.merchant_normalized, s.category_id, c.name,
+    s.assignment_count, s.status, s.rule_id, s.created_at, s.updated_at";
+
+fn suggestion_from_row(row: &rusqlite::Row) -> rusqlite::Result<RuleSuggestion> {
+    Ok(RuleSuggestion {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        merchant_normalized: row.get(2)?,
+        category_id: row.get(3)?,
+        category_name: row.get(4)?,
+        assignment_count: row.get(5)?,
+        status: row.get(6)?,
+        rule_id: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+/// Pending suggestions, most consistent first
+pub fn get_pending_rule_suggestions(conn: &Connection, user_id: &str) -> DbResult<Vec<RuleSuggestion>> {
+    let sql = format!(
+        "SELECT {} FROM rule_suggestions s
+         LEFT JOIN categories c ON c.id = s.category_id
+         WHERE s.user_id = ?1 AND s.status = 'pending'
+         ORDER BY s.assignment_count DESC, s.updated_at DESC",
+        SUGGESTION_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let suggestions = stmt
+        .query_map([user_id], suggestion_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(suggestions)
+}
+
+pub fn get_rule_suggestion(conn: &Connection, user_id: &str, id: &str) -> DbResult<Option<RuleSuggestion>> {
+    let sql = format!(
+        "SELECT {} FROM rule_suggestions s
+         LEFT JOIN categories c ON c.id = s.category_id
+         WHERE s.user_id = ?1 AND s.id = ?2",
+        SUGGESTION_COLUMNS
+    );
+    let suggestion = conn
+        .query_row(&sql, params![user_id, id], suggestion_from_row)
+        .optional()?;
+
+    Ok(suggestion)
+}
+
+/// Record a suggestion, or refresh the count of an existing one. Suggestions the user
+/// already accepted or rejected keep their status and aren't returned.
+pub fn upsert_rule_suggestion(
+    conn: &Connection,
+    user_id: &str,
+    merchant_normalized: &str,
+    category_id: &str,
+    assignment_count: i64,
+) -> DbResult<Option<RuleSuggestion>> {
+    conn.execute(
+        "INSERT INTO rule_suggestions (id, user_id, merchant_normalized, category_id, assignment_count)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(user_id, merchant_normalized, category_id) DO UPDATE SET
+             assignment_count = excluded.assignment_count,
+             updated_at = datetime('now')
+         WHERE status = 'pending'",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            user_id,
+            merchant_normalized,
+            category_id,
+            assignment_count
+        ],
+    )?;
+
+    let id: String = conn.query_row(
+        "SELECT id FROM rule_suggestions
+         WHERE user_id = ?1 AND merchant_normalized = ?2 AND category_id = ?3",
+        params![user_id, merchant_normalized, category_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(get_rule_suggestion(conn, user_id, &id)?.filter(|s| s.status == "pending"))
+}
+
+/// Create the suggested rule. It goes after every existing rule, so rules the user
+/// wrote keep precedence.
+pub fn accept_rule_suggestion(conn: &Connection, user_id: &str, id: &str) -> DbResult<MerchantCategoryRule> {
+    let suggestion = get_rule_suggestion(conn, user_id, id)?.ok_or(DbError::NotFound)?;
+    if suggestion.status != "pending" {
+        return Err(DbError::InvalidInput(format!("Suggestion was already {}", suggestion.status)));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let rule = queries::create_category_rule(
+        &tx,
+        user_id,
+        &RuleInput::for_merchant(&suggestion.merchant_normalized, "exact", &suggestion.category_id),
+    )?;
+    tx.execute(
+        "UPDATE rule_suggestions SET status = 'accepted', rule_id = ?2, updated_at = datetime('now')
+         WHERE id = ?1",
+        params![id, &rule.id],
+    )?;
+    tx.commit()?;
+
+    Ok(rule)
+}
+
+/// Dismiss a suggestion; the same merchant and category won't be suggested again
+pub fn reject_rule_suggestion(conn: &Connection, user_id: &str, id: &str) -> DbResult<()> {
+    let updated = conn.execute(
+        "UPDATE rule_suggestions SET status = 'rejected', updated_at = datetime('now')
+         WHERE user_id = ?1 AND id = ?2 AND status = 'pending'",
+        params![user_id, id],
+    )?;
+    if updated == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categorizer::learn_from_assignment;
+    use crate::db::test_support::{category, insert, new_transaction, open_seeded};
+
+    fn recategorize(conn: &Connection, hash: &str, merchant: &str, category_id: &str) -> Option<RuleSuggestion> {
+        let id = insert(conn, &new_transaction(merchant, 1500, hash));
+        queries::update_transaction_category(conn, &id, Some(category_id), "manual", "Set manually", None).unwrap();
+        learn_from_assignment(conn, "local", &id).unwrap()
+    }
+
+    #[test]
+    fn test_suggest_accept_and_reject() {
+        let conn = open_seeded();
+        let travel = category(&conn, "Travel");
+        let shopping = category(&conn, "Shopping");
+
+        assert!(recategorize(&conn, "a1", "hotel lumen", &travel).is_none());
+        assert!(recategorize(&conn, "a2", "hotel lumen", &travel).is_none());
+        let suggestion = recategorize(&conn, "a3", "hotel lumen", &travel).unwrap();
+        assert_eq!(suggestion.assignment_count, 3);
+
+        let rule = accept_rule_suggestion(&conn, "local", &suggestion.id).unwrap();
+        assert_eq!(rule.merchant_pattern.as_deref(), Some("hotel lumen"));
+        assert!(get_pending_rule_suggestions(&conn, "local").unwrap().is_empty());
+
+        // A merchant the user wrote a rule for is never suggested
+        queries::create_category_rule(&conn, "local", &RuleInput::for_merchant("corner", "contains", &shopping))
+            .unwrap();
+        for hash in ["b1", "b2", "b3"] {
+            assert!(recategorize(&conn, hash, "corner cafe", &travel).is_none());
+        }
+
+        for hash in ["c1", "c2", "c3"] {
+            recategorize(&conn, hash, "gadget hub", &shopping);
+        }
+        let pending = get_pending_rule_suggestions(&conn, "local").unwrap();
+        assert_eq!(pending.len(), 1);
+        reject_rule_suggestion(&conn, "local", &pending[0].id).unwrap();
+        assert!(recategorize(&conn, "c4", "gadget hub", &shopping).is_none());
+    }
+}
@@ -1,12 +1,105 @@
+use std::path::{Path, PathBuf};
+
 use rusqlite::Connection;
-use super::DbResult;
+use super::{DbError, DbResult};
+
+/// Latest schema version this build understands
+pub const SCHEMA_VERSION: i32 = 18;
 
-#[allow(dead_code)]
-const SCHEMA_VERSION: i32 = 3;
+/// A single schema migration step
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    apply: fn(&Connection) -> DbResult<()>,
+}
 
-/// Run database migrations
+/// Every migration in order. Append new steps here and bump `SCHEMA_VERSION`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "initial schema", apply: migrate_v1 },
+    Migration { version: 2, description: "Gmail integration", apply: migrate_v2 },
+    Migration { version: 3, description: "drop gmail_credentials, use hardcoded OAuth", apply: migrate_v3 },
+    Migration { version: 4, description: "full-text search index", apply: migrate_v4 },
+    Migration { version: 5, description: "transaction fees, source and category reason", apply: migrate_v5 },
+    Migration { version: 6, description: "bank statement transactions", apply: migrate_v6 },
+    Migration { version: 7, description: "accounts and payment methods", apply: migrate_v7 },
+    Migration { version: 8, description: "income and recurring income", apply: migrate_v8 },
+    Migration { version: 9, description: "merchant entities and aliases", apply: migrate_v9 },
+    Migration { version: 10, description: "category hierarchy", apply: migrate_v10 },
+    Migration { version: 11, description: "hidden and renamable system categories", apply: migrate_v11 },
+    Migration { version: 12, description: "rule conditions and priorities", apply: migrate_v12 },
+    Migration { version: 13, description: "categorization rule on transactions", apply: migrate_v13 },
+    Migration { version: 14, description: "rule suggestions", apply: migrate_v14 },
+    Migration { version: 15, description: "naive Bayes classifier", apply: migrate_v15 },
+    Migration { version: 16, description: "merchant knowledge base overrides", apply: migrate_v16 },
+    Migration { version: 17, description: "item categories and transaction splits", apply: migrate_v17 },
+    Migration { version: 18, description: "parser confidence on transactions", apply: migrate_v18 },
+];
+
+/// Run database migrations.
+///
+/// Refuses to touch a database written by a newer version of the app. Before upgrading
+/// an existing on-disk database, a copy is saved next to it (see `pre_migration_backup_path`).
 pub fn run_migrations(conn: &Connection) -> DbResult<()> {
-    // Create migrations table if it doesn't exist
+    ensure_migrations_table(conn)?;
+    let current_version = get_schema_version(conn)?;
+
+    if current_version > SCHEMA_VERSION {
+        return Err(DbError::SchemaTooNew {
+            found: current_version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    if current_version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    if current_version > 0 {
+        if let Some(db_path) = conn.path().filter(|p| !p.is_empty()) {
+            let backup_path = pre_migration_backup_path(Path::new(db_path), current_version);
+            backup_before_migration(conn, &backup_path)?;
+            log::info!("Backed up schema v{} database to {:?}", current_version, backup_path);
+        }
+    }
+
+    migrate_to(conn, SCHEMA_VERSION)
+}
+
+/// Apply pending migrations up to and including `target_version`.
+/// Each step and its `migrations` row are committed in one transaction, so a failing
+/// step leaves the database at the previous version.
+pub fn migrate_to(conn: &Connection, target_version: i32) -> DbResult<()> {
+    ensure_migrations_table(conn)?;
+    let current_version = get_schema_version(conn)?;
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current_version && m.version <= target_version)
+    {
+        log::info!("Running migration v{} ({})", migration.version, migration.description);
+
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx).map_err(|e| {
+            DbError::Migration(format!("v{} ({}): {}", migration.version, migration.description, e))
+        })?;
+        tx.execute("INSERT INTO migrations (version) VALUES (?1)", [migration.version])?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// The highest migration version applied to this database (0 for a new database)
+pub fn get_schema_version(conn: &Connection) -> DbResult<i32> {
+    let version = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM migrations",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(version)
+}
+
+fn ensure_migrations_table(conn: &Connection) -> DbResult<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS migrations (
             version INTEGER PRIMARY KEY,
@@ -14,30 +107,24 @@ pub fn run_migrations(conn: &Connection) -> DbResult<()> {
         )",
         [],
     )?;
+    Ok(())
+}
 
-    let current_version: i32 = conn
-        .query_row(
-            "SELECT COALESCE(MAX(version), 0) FROM migrations",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-
-    if current_version < 1 {
-        log::info!("Running migration v1");
-        migrate_v1(conn)?;
-    }
-
-    if current_version < 2 {
-        log::info!("Running migration v2 (Gmail integration)");
-        migrate_v2(conn)?;
-    }
+/// Where the copy of a database is saved before migrating it from `from_version`
+pub fn pre_migration_backup_path(db_path: &Path, from_version: i32) -> PathBuf {
+    let file_name = db_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "email_budget.db".to_string());
+    db_path.with_file_name(format!("{}.pre-v{}-migration.bak", file_name, from_version))
+}
 
-    if current_version < 3 {
-        log::info!("Running migration v3 (drop gmail_credentials, use hardcoded OAuth)");
-        migrate_v3(conn)?;
+fn backup_before_migration(conn: &Connection, backup_path: &Path) -> DbResult<()> {
+    // VACUUM INTO refuses to overwrite an existing file
+    if backup_path.exists() {
+        std::fs::remove_file(backup_path)?;
     }
-
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])?;
     Ok(())
 }
 
@@ -173,9 +260,6 @@ fn migrate_v1(conn: &Connection) -> DbResult<()> {
             ON budgets(user_id, category_id);
         CREATE INDEX IF NOT EXISTS idx_merchant_rules_user
             ON merchant_category_rules(user_id);
-
-        -- Record migration
-        INSERT INTO migrations (version) VALUES (1);
         "#,
     )?;
 
@@ -238,9 +322,6 @@ fn migrate_v2(conn: &Connection) -> DbResult<()> {
             ('filter_ubereats', 'uber.us@uber.com', 'Uber Eats', 1),
             ('filter_uber', 'noreply@uber.com', 'Uber', 1),
             ('filter_venmo', 'venmo@venmo.com', 'Venmo', 1);
-
-        -- Record migration
-        INSERT INTO migrations (version) VALUES (2);
         "#,
     )?;
 
@@ -252,15 +333,568 @@ fn migrate_v3(conn: &Connection) -> DbResult<()> {
         r#"
         -- Drop gmail_credentials table (credentials are now hardcoded in the app)
         DROP TABLE IF EXISTS gmail_credentials;
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v4(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- Plain text of the receipt a transaction was parsed from
+        ALTER TABLE transactions ADD COLUMN receipt_text TEXT;
+
+        -- Full-text index over merchant, notes, item names and receipt text.
+        -- Rows are keyed by transaction_id and maintained by the triggers below.
+        CREATE VIRTUAL TABLE IF NOT EXISTS transactions_fts USING fts5(
+            transaction_id UNINDEXED,
+            merchant,
+            notes,
+            items,
+            receipt_text,
+            tokenize = 'unicode61 remove_diacritics 2',
+            prefix = '2 3'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS transactions_fts_insert
+        AFTER INSERT ON transactions
+        BEGIN
+            INSERT INTO transactions_fts (transaction_id, merchant, notes, items, receipt_text)
+            VALUES (
+                new.id, new.merchant, new.notes,
+                (SELECT group_concat(name, ' ') FROM transaction_items WHERE transaction_id = new.id),
+                new.receipt_text
+            );
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS transactions_fts_update
+        AFTER UPDATE OF merchant, notes, receipt_text ON transactions
+        BEGIN
+            DELETE FROM transactions_fts WHERE transaction_id = old.id;
+            INSERT INTO transactions_fts (transaction_id, merchant, notes, items, receipt_text)
+            VALUES (
+                new.id, new.merchant, new.notes,
+                (SELECT group_concat(name, ' ') FROM transaction_items WHERE transaction_id = new.id),
+                new.receipt_text
+            );
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS transactions_fts_delete
+        AFTER DELETE ON transactions
+        BEGIN
+            DELETE FROM transactions_fts WHERE transaction_id = old.id;
+        END;
+
+        -- Item changes refresh the items column of the owning transaction
+        CREATE TRIGGER IF NOT EXISTS transaction_items_fts_insert
+        AFTER INSERT ON transaction_items
+        BEGIN
+            UPDATE transactions_fts
+            SET items = (SELECT group_concat(name, ' ') FROM transaction_items WHERE transaction_id = new.transaction_id)
+            WHERE transaction_id = new.transaction_id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS transaction_items_fts_update
+        AFTER UPDATE OF name ON transaction_items
+        BEGIN
+            UPDATE transactions_fts
+            SET items = (SELECT group_concat(name, ' ') FROM transaction_items WHERE transaction_id = new.transaction_id)
+            WHERE transaction_id = new.transaction_id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS transaction_items_fts_delete
+        AFTER DELETE ON transaction_items
+        BEGIN
+            UPDATE transactions_fts
+            SET items = (SELECT group_concat(name, ' ') FROM transaction_items WHERE transaction_id = old.transaction_id)
+            WHERE transaction_id = old.transaction_id;
+        END;
+
+        -- Index existing transactions
+        INSERT INTO transactions_fts (transaction_id, merchant, notes, items, receipt_text)
+        SELECT t.id, t.merchant, t.notes,
+               (SELECT group_concat(i.name, ' ') FROM transaction_items i WHERE i.transaction_id = t.id),
+               t.receipt_text
+        FROM transactions t;
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v5(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- Where a transaction came from: 'gmail', 'import' or 'ocr' (NULL for older rows)
+        ALTER TABLE transactions ADD COLUMN source_type TEXT;
+        -- Gmail message ID or imported file name
+        ALTER TABLE transactions ADD COLUMN source_ref TEXT;
+
+        -- Which categorization tier picked the category, and why
+        ALTER TABLE transactions ADD COLUMN category_source TEXT;
+        ALTER TABLE transactions ADD COLUMN category_reason TEXT;
+
+        -- Fees, taxes, tips and discounts from the receipt
+        CREATE TABLE IF NOT EXISTS transaction_fees (
+            id TEXT PRIMARY KEY,
+            transaction_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            label TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_transaction_fees_transaction
+            ON transaction_fees(transaction_id);
+        CREATE INDEX IF NOT EXISTS idx_transaction_items_transaction
+            ON transaction_items(transaction_id);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v6(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- Rows imported from bank/credit-card CSV statements, reconciled against receipts
+        CREATE TABLE IF NOT EXISTS statement_transactions (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'local',
+            -- Groups the rows of one imported file
+            import_id TEXT NOT NULL,
+            account_label TEXT,
+            posted_date TEXT NOT NULL,
+            description TEXT NOT NULL,
+            description_normalized TEXT NOT NULL,
+            -- Cents, positive for spending like transactions.amount
+            amount INTEGER NOT NULL,
+            source_hash TEXT NOT NULL UNIQUE,
+            matched_transaction_id TEXT,
+            -- 'unmatched', 'auto', 'confirmed' or 'no_match'
+            match_status TEXT NOT NULL DEFAULT 'unmatched'
+                CHECK (match_status IN ('unmatched', 'auto', 'confirmed', 'no_match')),
+            match_score REAL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (matched_transaction_id) REFERENCES transactions(id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_statement_transactions_date
+            ON statement_transactions(user_id, posted_date);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_statement_transactions_matched
+            ON statement_transactions(matched_transaction_id)
+            WHERE matched_transaction_id IS NOT NULL;
+
+        -- A deleted receipt leaves its statement row unmatched rather than 'auto' with no match
+        CREATE TRIGGER IF NOT EXISTS statement_transactions_unlink
+        AFTER UPDATE OF matched_transaction_id ON statement_transactions
+        WHEN NEW.matched_transaction_id IS NULL AND NEW.match_status IN ('auto', 'confirmed')
+        BEGIN
+            UPDATE statement_transactions
+            SET match_status = 'unmatched', match_score = NULL
+            WHERE id = NEW.id;
+        END;
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v7(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- Cards, bank accounts and wallet balances that transactions are paid from
+        CREATE TABLE IF NOT EXISTS accounts (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'local',
+            name TEXT NOT NULL,
+            -- 'credit_card', 'debit_card', 'bank', 'wallet', 'cash' or 'other'
+            account_type TEXT NOT NULL DEFAULT 'other'
+                CHECK (account_type IN ('credit_card', 'debit_card', 'bank', 'wallet', 'cash', 'other')),
+            -- Card network or institution as printed on receipts, e.g. 'Visa', 'Venmo'
+            institution TEXT,
+            last4 TEXT CHECK (last4 IS NULL OR length(last4) = 4),
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            UNIQUE(user_id, name)
+        );
 
-        -- Record migration
-        INSERT INTO migrations (version) VALUES (3);
+        ALTER TABLE transactions ADD COLUMN account_id TEXT
+            REFERENCES accounts(id) ON DELETE SET NULL;
+
+        CREATE INDEX IF NOT EXISTS idx_transactions_account
+            ON transactions(account_id);
         "#,
     )?;
 
     Ok(())
 }
 
+fn migrate_v8(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- 'expense' or 'income'; amount stays positive either way
+        ALTER TABLE transactions ADD COLUMN direction TEXT NOT NULL DEFAULT 'expense'
+            CHECK (direction IN ('expense', 'income'));
+
+        CREATE INDEX IF NOT EXISTS idx_transactions_user_direction_date
+            ON transactions(user_id, direction, transaction_date);
+
+        -- Paychecks and other income that repeats on a schedule
+        CREATE TABLE IF NOT EXISTS recurring_income (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'local',
+            name TEXT NOT NULL,
+            amount INTEGER NOT NULL CHECK (amount > 0),
+            -- 'weekly', 'biweekly', 'semimonthly' or 'monthly'
+            frequency TEXT NOT NULL
+                CHECK (frequency IN ('weekly', 'biweekly', 'semimonthly', 'monthly')),
+            -- First occurrence; monthly income keeps its day of month
+            start_date TEXT NOT NULL,
+            -- Date of the next occurrence not yet recorded as a transaction
+            next_date TEXT NOT NULL,
+            category_id TEXT,
+            account_id TEXT,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE SET NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE SET NULL
+        );
+
+        INSERT INTO categories (id, user_id, name, icon, color, is_system)
+        SELECT lower(hex(randomblob(16))), 'local', 'Income', 'wallet', '#10b981', 1
+        WHERE EXISTS (SELECT 1 FROM categories WHERE user_id = 'local')
+          AND NOT EXISTS (
+              SELECT 1 FROM categories WHERE user_id = 'local' AND name = 'Income' COLLATE NOCASE
+          );
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v9(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- One row per real-world merchant; receipts name the same merchant many ways
+        CREATE TABLE IF NOT EXISTS merchants (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'local',
+            name TEXT NOT NULL,
+            -- normalize_merchant(name), matched against transactions.merchant_normalized
+            name_normalized TEXT NOT NULL,
+            default_category_id TEXT,
+            -- Icon name or logo URL shown next to the merchant
+            icon TEXT,
+            logo_url TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (default_category_id) REFERENCES categories(id) ON DELETE SET NULL,
+            UNIQUE(user_id, name_normalized)
+        );
+
+        -- Other spellings of a merchant, matched against transactions.merchant_normalized
+        CREATE TABLE IF NOT EXISTS merchant_aliases (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'local',
+            merchant_id TEXT NOT NULL,
+            pattern TEXT NOT NULL,
+            -- 'exact', 'prefix' or 'contains'
+            match_type TEXT NOT NULL DEFAULT 'exact'
+                CHECK (match_type IN ('exact', 'prefix', 'contains')),
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (merchant_id) REFERENCES merchants(id) ON DELETE CASCADE,
+            UNIQUE(user_id, pattern, match_type)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_merchant_aliases_merchant
+            ON merchant_aliases(merchant_id);
+
+        -- Existing transactions are linked on the next startup by merchants::link_unassigned
+        ALTER TABLE transactions ADD COLUMN merchant_id TEXT
+            REFERENCES merchants(id) ON DELETE SET NULL;
+
+        CREATE INDEX IF NOT EXISTS idx_transactions_merchant
+            ON transactions(merchant_id);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v10(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- Subcategory of parent_id; spending and budgets roll up the tree
+        ALTER TABLE categories ADD COLUMN parent_id TEXT
+            REFERENCES categories(id) ON DELETE SET NULL;
+
+        CREATE INDEX IF NOT EXISTS idx_categories_parent ON categories(parent_id);
+
+        INSERT INTO categories (id, user_id, name, icon, color, is_system)
+        SELECT lower(hex(randomblob(16))), 'local', 'Groceries', 'shopping-cart', '#f43f5e', 1
+        WHERE EXISTS (SELECT 1 FROM categories WHERE user_id = 'local')
+          AND NOT EXISTS (
+              SELECT 1 FROM categories WHERE user_id = 'local' AND name = 'Groceries' COLLATE NOCASE
+          );
+
+        -- Default hierarchy for the seeded categories, matching seed_default_categories
+        UPDATE categories
+        SET parent_id = (
+            SELECT p.id FROM categories p
+            WHERE p.user_id = categories.user_id AND p.is_system = 1
+              AND p.name = CASE categories.name
+                  WHEN 'Groceries' THEN 'Food & Dining'
+                  WHEN 'Food Delivery' THEN 'Food & Dining'
+                  WHEN 'Rideshare' THEN 'Transportation'
+                  WHEN 'Subscriptions' THEN 'Entertainment'
+              END
+        )
+        WHERE is_system = 1 AND parent_id IS NULL
+          AND name IN ('Groceries', 'Food Delivery', 'Rideshare', 'Subscriptions');
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v11(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- Hidden categories stay on their transactions but aren't offered or auto-assigned
+        ALTER TABLE categories ADD COLUMN is_hidden INTEGER NOT NULL DEFAULT 0;
+
+        -- Seeded name of a system category, so it can still be found after a rename
+        ALTER TABLE categories ADD COLUMN system_key TEXT;
+
+        UPDATE categories SET system_key = name WHERE is_system = 1;
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v12(conn: &Connection) -> DbResult<()> {
+    // Rebuilt to drop UNIQUE(user_id, merchant_pattern): several rules may share a
+    // merchant with different amount ranges, and a rule needn't name a merchant at all
+    conn.execute_batch(
+        r#"
+        CREATE TABLE merchant_category_rules_v12 (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'local',
+            -- Optional label shown instead of the generated description
+            name TEXT,
+            -- Conditions; NULL matches anything and every set condition must match
+            merchant_pattern TEXT,
+            -- How merchant_pattern is compared with merchant_normalized
+            match_type TEXT NOT NULL DEFAULT 'contains'
+                CHECK (match_type IN ('exact', 'contains', 'glob', 'regex')),
+            provider TEXT,
+            -- Cents; min inclusive, max exclusive
+            min_amount INTEGER,
+            max_amount INTEGER,
+            -- Comma-separated ISO weekdays, 1 = Monday .. 7 = Sunday
+            days_of_week TEXT,
+            -- Case-insensitive substring of any item name
+            item_keyword TEXT,
+            -- Case-insensitive substring of the receipt email's sender
+            sender TEXT,
+            category_id TEXT NOT NULL,
+            -- Rules are tried in ascending priority; the first match wins
+            priority INTEGER NOT NULL DEFAULT 0,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE
+        );
+
+        -- Keep the old precedence: exact rules, then longer patterns first
+        INSERT INTO merchant_category_rules_v12
+            (id, user_id, merchant_pattern, match_type, category_id, priority, created_at, updated_at)
+        SELECT id, user_id, merchant_pattern,
+               CASE WHEN is_exact_match = 1 THEN 'exact' ELSE 'contains' END,
+               category_id,
+               ROW_NUMBER() OVER (
+                   PARTITION BY user_id
+                   ORDER BY is_exact_match DESC, length(merchant_pattern) DESC, merchant_pattern
+               ),
+               created_at, created_at
+        FROM merchant_category_rules;
+
+        DROP TABLE merchant_category_rules;
+        ALTER TABLE merchant_category_rules_v12 RENAME TO merchant_category_rules;
+
+        CREATE INDEX IF NOT EXISTS idx_merchant_category_rules_priority
+            ON merchant_category_rules(user_id, priority);
+
+        -- Sender address of the receipt email, for sender rule conditions
+        ALTER TABLE transactions ADD COLUMN sender TEXT;
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Migration v13: record which rule categorized a transaction, and mark
+/// transactions that were never categorized
+fn migrate_v13(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE transactions ADD COLUMN category_rule_id TEXT
+            REFERENCES merchant_category_rules(id) ON DELETE SET NULL;
+
+        UPDATE transactions SET category_source = 'uncategorized'
+        WHERE category_source IS NULL AND category_id IS NULL;
+
+        CREATE INDEX IF NOT EXISTS idx_transactions_category_source
+            ON transactions(user_id, category_source);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Migration v14: merchant rules suggested from repeated manual recategorizations
+fn migrate_v14(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS rule_suggestions (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            merchant_normalized TEXT NOT NULL,
+            category_id TEXT NOT NULL,
+            assignment_count INTEGER NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'rejected')),
+            rule_id TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE,
+            FOREIGN KEY (rule_id) REFERENCES merchant_category_rules(id) ON DELETE SET NULL,
+            UNIQUE(user_id, merchant_normalized, category_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_transactions_merchant_source
+            ON transactions(user_id, merchant_normalized, category_source);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Migration v15: token counts for the naive Bayes categorizer, rebuilt from
+/// categorized transactions
+fn migrate_v15(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS classifier_categories (
+            user_id TEXT NOT NULL,
+            category_id TEXT NOT NULL,
+            document_count INTEGER NOT NULL,
+            token_count INTEGER NOT NULL,
+            PRIMARY KEY (user_id, category_id),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS classifier_tokens (
+            user_id TEXT NOT NULL,
+            category_id TEXT NOT NULL,
+            token TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            PRIMARY KEY (user_id, category_id, token),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_classifier_tokens_token
+            ON classifier_tokens(user_id, token);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Migration v16: the user's changes to the bundled merchant knowledge base
+fn migrate_v16(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS merchant_pattern_overrides (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            pattern TEXT NOT NULL,
+            -- NULL disables the bundled entry with the same pattern
+            category_id TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE,
+            UNIQUE(user_id, pattern)
+        );
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v17(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- NULL means the item follows the transaction's category
+        ALTER TABLE transaction_items ADD COLUMN category_id TEXT
+            REFERENCES categories(id) ON DELETE SET NULL;
+        ALTER TABLE transaction_items ADD COLUMN category_source TEXT;
+        ALTER TABLE transaction_items ADD COLUMN category_reason TEXT;
+
+        CREATE TABLE IF NOT EXISTS transaction_splits (
+            id TEXT PRIMARY KEY,
+            transaction_id TEXT NOT NULL,
+            -- NULL means the share follows the transaction's category
+            category_id TEXT,
+            amount INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+            FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_transaction_splits_transaction
+            ON transaction_splits(transaction_id);
+        CREATE INDEX IF NOT EXISTS idx_transaction_splits_category
+            ON transaction_splits(category_id);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+fn migrate_v18(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(
+        r#"
+        -- How sure the parser was of the receipt, 0.0 to 1.0; NULL where it wasn't recorded
+        ALTER TABLE transactions ADD COLUMN parse_confidence REAL;
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Open an in-memory database migrated to `version`, for testing upgrades from
+/// historical schemas
+#[cfg(test)]
+pub fn open_in_memory_at_version(version: i32) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+    migrate_to(&conn, version).unwrap();
+    conn
+}
+
 /// Seed default categories
 pub fn seed_default_categories(conn: &Connection) -> DbResult<()> {
     let count: i32 = conn.query_row(
@@ -273,32 +907,74 @@ pub fn seed_default_categories(conn: &Connection) -> DbResult<()> {
         return Ok(());
     }
 
+    // (name, icon, color, parent); parents come before their children
     let categories = [
-        ("Food & Dining", "utensils", "#ef4444"),
-        ("Food Delivery", "bike", "#f97316"),
-        ("Transportation", "car", "#eab308"),
-        ("Rideshare", "map-pin", "#84cc16"),
-        ("Shopping", "shopping-bag", "#22c55e"),
-        ("Entertainment", "film", "#14b8a6"),
-        ("Subscriptions", "repeat", "#06b6d4"),
-        ("Utilities", "zap", "#0ea5e9"),
-        ("Healthcare", "heart-pulse", "#3b82f6"),
-        ("Personal Care", "sparkles", "#6366f1"),
-        ("Travel", "plane", "#8b5cf6"),
-        ("Gifts & Donations", "gift", "#a855f7"),
-        ("Education", "graduation-cap", "#d946ef"),
-        ("Peer Payment", "users", "#ec4899"),
-        ("Uncategorized", "help-circle", "#6b7280"),
+        ("Food & Dining", "utensils", "#ef4444", None),
+        ("Groceries", "shopping-cart", "#f43f5e", Some("Food & Dining")),
+        ("Food Delivery", "bike", "#f97316", Some("Food & Dining")),
+        ("Transportation", "car", "#eab308", None),
+        ("Rideshare", "map-pin", "#84cc16", Some("Transportation")),
+        ("Shopping", "shopping-bag", "#22c55e", None),
+        ("Entertainment", "film", "#14b8a6", None),
+        ("Subscriptions", "repeat", "#06b6d4", Some("Entertainment")),
+        ("Utilities", "zap", "#0ea5e9", None),
+        ("Healthcare", "heart-pulse", "#3b82f6", None),
+        ("Personal Care", "sparkles", "#6366f1", None),
+        ("Travel", "plane", "#8b5cf6", None),
+        ("Gifts & Donations", "gift", "#a855f7", None),
+        ("Education", "graduation-cap", "#d946ef", None),
+        ("Peer Payment", "users", "#ec4899", None),
+        ("Income", "wallet", "#10b981", None),
+        ("Uncategorized", "help-circle", "#6b7280", None),
     ];
 
-    for (name, icon, color) in categories {
+    for (name, icon, color, parent) in categories {
         let id = uuid::Uuid::new_v4().to_string();
         conn.execute(
-            "INSERT INTO categories (id, user_id, name, icon, color, is_system) VALUES (?1, 'local', ?2, ?3, ?4, 1)",
-            [&id, name, icon, color],
+            "INSERT INTO categories (id, user_id, name, icon, color, parent_id, is_system, system_key)
+             VALUES (?1, 'local', ?2, ?3, ?4,
+                     (SELECT id FROM categories WHERE user_id = 'local' AND name = ?5), 1, ?2)",
+            rusqlite::params![&id, name, icon, color, parent],
         )?;
     }
 
     log::info!("Seeded {} default categories", categories.len());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_contiguous() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i32 + 1);
+        }
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_upgrade_from_every_historical_version() {
+        for version in 0..=SCHEMA_VERSION {
+            let conn = open_in_memory_at_version(version);
+            assert_eq!(get_schema_version(&conn).unwrap(), version);
+
+            run_migrations(&conn).unwrap();
+            assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+            seed_default_categories(&conn).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let conn = open_in_memory_at_version(SCHEMA_VERSION);
+        conn.execute("INSERT INTO migrations (version) VALUES (?1)", [SCHEMA_VERSION + 1])
+            .unwrap();
+
+        assert!(matches!(
+            run_migrations(&conn),
+            Err(DbError::SchemaTooNew { found, .. }) if found == SCHEMA_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::queries::{
+    append_transaction_filters, transaction_with_category_from_row, TransactionFilters,
+    TransactionWithCategory, TRANSACTION_WITH_CATEGORY_COLUMNS,
+};
+use super::DbResult;
+
+/// Markers wrapped around matched terms in search snippets
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_END: &str = "</mark>";
+
+/// Private-use characters FTS5 puts around matches, swapped for the HTML markers
+/// once the rest of the snippet is escaped
+const MATCH_START: char = '\u{E000}';
+const MATCH_END: char = '\u{E001}';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionSearchHit {
+    pub transaction: TransactionWithCategory,
+    /// Best-matching fragment as HTML-escaped text, with matched terms wrapped in
+    /// `<mark>` tags
+    pub snippet: String,
+    /// BM25 relevance score (lower is more relevant)
+    pub rank: f64,
+}
+
+/// A purchased item together with the transaction it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemMatch {
+    pub item_id: String,
+    pub transaction_id: String,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: i64,
+    pub total_price: i64,
+    pub merchant: String,
+    pub provider: String,
+    pub transaction_date: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemSearchResult {
+    pub items: Vec<ItemMatch>,
+    /// Sum of `total_price` (cents) across all matching items
+    pub total_spent: i64,
+    /// Sum of quantities across all matching items
+    pub total_quantity: i64,
+    /// Number of distinct transactions containing a matching item
+    pub transaction_count: i64,
+}
+
+/// Build an FTS5 MATCH expression from user search input.
+///
+/// Every term is quoted so punctuation in merchant names can't produce FTS syntax
+/// errors. Supported syntax:
+/// - `hdmi cable` matches transactions containing both words
+/// - `"hdmi cable"` matches the exact phrase
+/// - `hdm*` / `"usb c"*` match by prefix
+///
+/// The last unquoted word also matches by prefix, so results keep up while the user
+/// is still typing it. Returns `None` if the input contains nothing searchable.
+pub fn build_match_query(input: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut last_is_bare = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut text = String::new();
+        let mut prefix = false;
+        let bare = c != '"';
+
+        if !bare {
+            // Phrase: everything up to the closing quote (or end of input)
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                text.push(c);
+            }
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                prefix = true;
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                if c != '"' {
+                    text.push(c);
+                }
+                chars.next();
+            }
+            if text.ends_with('*') {
+                prefix = true;
+                text = text.trim_end_matches('*').to_string();
+            }
+        }
+
+        if text.chars().any(|c| c.is_alphanumeric()) {
+            terms.push(format!(
+                "\"{}\"{}",
+                text.trim().replace('"', "\"\""),
+                if prefix { "*" } else { "" }
+            ));
+            last_is_bare = bare && !prefix;
+        }
+    }
+
+    if last_is_bare {
+        if let Some(last) = terms.last_mut() {
+            last.push('*');
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Full-text search over merchant, notes, item names and receipt text.
+///
+/// Results are ordered by relevance. Any `filters.search` value is ignored in favour
+/// of `query`; the remaining filters narrow the result set as in `get_transactions`.
+pub fn search_transactions(
+    conn: &Connection,
+    user_id: &str,
+    query: &str,
+    filters: &TransactionFilters,
+    limit: i64,
+) -> DbResult<Vec<TransactionSearchHit>> {
+    let match_query = match build_match_query(query) {
+        Some(q) => q,
+        None => return Ok(Vec::new()),
+    };
+
+    // Column weights: transaction_id, merchant, notes, items, receipt_text
+    let mut sql = format!(
+        "SELECT {},
+                snippet(transactions_fts, -1, '{}', '{}', '…', 12) as snippet,
+                bm25(transactions_fts, 0.0, 10.0, 4.0, 4.0, 1.0) as rank
+         FROM transactions_fts
+         JOIN transactions t ON t.id = transactions_fts.transaction_id
+         LEFT JOIN categories c ON t.category_id = c.id
+         WHERE transactions_fts MATCH ? AND t.user_id = ?",
+        TRANSACTION_WITH_CATEGORY_COLUMNS, MATCH_START, MATCH_END
+    );
+
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> =
+        vec![Box::new(match_query), Box::new(user_id.to_string())];
+
+    let filters = TransactionFilters {
+        search: None,
+        ..filters.clone()
+    };
+    append_transaction_filters(&mut sql, &mut params, &filters);
+
+    sql.push_str(" ORDER BY rank LIMIT ?");
+    params.push(Box::new(limit));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    let hits = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(TransactionSearchHit {
+                transaction: transaction_with_category_from_row(row)?,
+                snippet: highlight_snippet(&row.get::<_, String>(19)?),
+                rank: row.get(20)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(hits)
+}
+
+/// Escape a raw FTS5 snippet for HTML and turn its match markers into `<mark>` tags
+fn highlight_snippet(snippet: &str) -> String {
+    snippet
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+        .replace(MATCH_START, HIGHLIGHT_START)
+        .replace(MATCH_END, HIGHLIGHT_END)
+}
+
+/// Find purchased items whose name contains every word of `query`, e.g. "coffee pods"
+/// with a provider filter of "amazon". Transaction filters narrow which orders are searched.
+pub fn search_items(
+    conn: &Connection,
+    user_id: &str,
+    query: &str,
+    filters: &TransactionFilters,
+) -> DbResult<ItemSearchResult> {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    if words.is_empty() {
+        return Ok(ItemSearchResult {
+            items: Vec::new(),
+            total_spent: 0,
+            total_quantity: 0,
+            transaction_count: 0,
+        });
+    }
+
+    let mut sql = String::from(
+        "SELECT i.id, i.transaction_id, i.name, i.quantity, i.unit_price, i.total_price,
+                t.merchant, t.provider, t.transaction_date
+         FROM transaction_items i
+         JOIN transactions t ON t.id = i.transaction_id
+         LEFT JOIN categories c ON t.category_id = c.id
+         WHERE t.user_id = ?",
+    );
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(user_id.to_string())];
+
+    for word in &words {
+        sql.push_str(" AND i.name LIKE ? ESCAPE '\\'");
+        let escaped = word
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        params.push(Box::new(format!("%{}%", escaped)));
+    }
+
+    let filters = TransactionFilters {
+        search: None,
+        ..filters.clone()
+    };
+    append_transaction_filters(&mut sql, &mut params, &filters);
+    sql.push_str(" ORDER BY t.transaction_date DESC, i.rowid");
+
+    let mut stmt = conn.prepare(&sql)?;
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    let items = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(ItemMatch {
+                item_id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                name: row.get(2)?,
+                quantity: row.get(3)?,
+                unit_price: row.get(4)?,
+                total_price: row.get(5)?,
+                merchant: row.get(6)?,
+                provider: row.get(7)?,
+                transaction_date: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let total_spent = items.iter().map(|i| i.total_price).sum();
+    let total_quantity = items.iter().map(|i| i.quantity as i64).sum();
+    let mut transaction_ids: Vec<&str> = items.iter().map(|i| i.transaction_id.as_str()).collect();
+    transaction_ids.sort_unstable();
+    transaction_ids.dedup();
+
+    Ok(ItemSearchResult {
+        transaction_count: transaction_ids.len() as i64,
+        items,
+        total_spent,
+        total_quantity,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{self, NewTransaction};
+    use crate::db::test_support::{insert, new_transaction, open_seeded};
+
+    #[test]
+    fn test_build_match_query() {
+        assert_eq!(build_match_query("hdmi cable").unwrap(), r#""hdmi" "cable"*"#);
+        assert_eq!(build_match_query(r#""hdmi cable""#).unwrap(), r#""hdmi cable""#);
+        assert_eq!(build_match_query("hdm*").unwrap(), r#""hdm"*"#);
+        assert_eq!(build_match_query(r#"cable "usb c"*"#).unwrap(), r#""cable" "usb c"*"#);
+        assert_eq!(build_match_query(r#"hdmi "usb c""#).unwrap(), r#""hdmi" "usb c""#);
+        assert_eq!(build_match_query("AND -").unwrap(), r#""AND"*"#);
+        assert!(build_match_query("  \"\" * ").is_none());
+    }
+
+    #[test]
+    fn test_search_transactions_and_triggers() {
+        let conn = open_seeded();
+        let id = insert(
+            &conn,
+            &NewTransaction {
+                provider: "amazon",
+                receipt_text: Some("Order #112-555 shipped to Seattle <b>fast</b>"),
+                ..new_transaction("Amazon", 1299, "hash1")
+            },
+        );
+        queries::insert_transaction_item(&conn, &id, "UGREEN HDMI Cable 6ft", 1, 1299, 1299)
+            .unwrap();
+
+        let filters = TransactionFilters::default();
+        let hits = search_transactions(&conn, "local", "hdmi", &filters, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].snippet.contains("<mark>HDMI</mark>"));
+
+        let hits = search_transactions(&conn, "local", "shipped seatt", &filters, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].snippet.contains("<mark>Seattle</mark> &lt;b&gt;fast&lt;/b&gt;"));
+
+        // Receipt text set later is indexed too
+        conn.execute(
+            "UPDATE transactions SET receipt_text = ?2 WHERE id = ?1",
+            rusqlite::params![&id, "Delivered to Portland"],
+        )
+        .unwrap();
+        assert_eq!(search_transactions(&conn, "local", "portland", &filters, 10).unwrap().len(), 1);
+        assert!(search_transactions(&conn, "local", "seattle", &filters, 10).unwrap().is_empty());
+
+        queries::delete_transaction(&conn, &id).unwrap();
+        let hits = search_transactions(&conn, "local", "hdmi", &filters, 10).unwrap();
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn test_search_items() {
+        let conn = open_seeded();
+        let orders = [
+            ("amazon", "a1", "2024-01-10", vec![("Coffee Pods 24ct", 2, 1599), ("HDMI Cable", 1, 999)]),
+            ("amazon", "a2", "2024-02-10", vec![("Dark Roast Coffee Pods", 1, 1899)]),
+            ("target", "t1", "2024-02-12", vec![("coffee pods", 1, 1299)]),
+            ("amazon", "a3", "2024-02-20", vec![("Coffee Filters 100% Paper", 1, 499)]),
+        ];
+        for (provider, hash, date, items) in &orders {
+            let id = insert(
+                &conn,
+                &NewTransaction {
+                    provider,
+                    transaction_date: date,
+                    ..new_transaction(provider, 5000, hash)
+                },
+            );
+            for (name, quantity, unit_price) in items {
+                queries::insert_transaction_item(&conn, &id, name, *quantity, *unit_price, *quantity as i64 * unit_price)
+                    .unwrap();
+            }
+        }
+
+        let amazon = TransactionFilters {
+            provider: Some("amazon".to_string()),
+            ..Default::default()
+        };
+        let result = search_items(&conn, "local", "coffee pods", &amazon).unwrap();
+        let names: Vec<&str> = result.items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["Dark Roast Coffee Pods", "Coffee Pods 24ct"]);
+        assert_eq!(result.total_spent, 1899 + 2 * 1599);
+        assert_eq!(result.total_quantity, 3);
+        assert_eq!(result.transaction_count, 2);
+
+        let everywhere = search_items(&conn, "local", "coffee pods", &TransactionFilters::default()).unwrap();
+        assert_eq!(everywhere.transaction_count, 3);
+
+        // LIKE wildcards in the query are matched literally
+        let result = search_items(&conn, "local", "100%", &TransactionFilters::default()).unwrap();
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(search_items(&conn, "local", "_", &TransactionFilters::default()).unwrap().items.len(), 0);
+        assert!(search_items(&conn, "local", "  ", &amazon).unwrap().items.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::queries::{
+    transaction_with_category_from_row, TransactionWithCategory, TRANSACTION_WITH_CATEGORY_COLUMNS,
+};
+use super::DbResult;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementTransaction {
+    pub id: String,
+    pub import_id: String,
+    pub account_label: Option<String>,
+    pub posted_date: String,
+    pub description: String,
+    pub description_normalized: String,
+    /// Cents, positive for spending
+    pub amount: i64,
+    pub matched_transaction_id: Option<String>,
+    /// 'unmatched', 'auto', 'confirmed' or 'no_match'
+    pub match_status: String,
+    pub match_score: Option<f64>,
+    pub created_at: String,
+}
+
+/// How a statement row relates to the receipts
+pub mod match_status {
+    pub const UNMATCHED: &str = "unmatched";
+    /// Matched by `auto_match`, awaiting confirmation
+    pub const AUTO: &str = "auto";
+    pub const CONFIRMED: &str = "confirmed";
+    /// The user said no receipt exists; auto-matching skips it
+    pub const NO_MATCH: &str = "no_match";
+}
+
+pub struct NewStatementTransaction<'a> {
+    pub import_id: &'a str,
+    pub account_label: Option<&'a str>,
+    pub posted_date: &'a str,
+    pub description: &'a str,
+    pub description_normalized: &'a str,
+    pub amount: i64,
+    /// Position among identical rows in the same file, so two same-day purchases of
+    /// the same amount aren't treated as duplicates of each other
+    pub occurrence: usize,
+}
+
+const STATEMENT_COLUMNS: &str = "s.id, s.import_id, s.account_label, s.posted_date, s.description,
+     s.description_normalized, s.amount, s.matched_transaction_id, s.match_status,
+     s.match_score, s.created_at";
+
+fn statement_from_row(row: &rusqlite::Row) -> rusqlite::Result<StatementTransaction> {
+    Ok(StatementTransaction {
+        id: row.get(0)?,
+        import_id: row.get(1)?,
+        account_label: row.get(2)?,
+        posted_date: row.get(3)?,
+        description: row.get(4)?,
+        description_normalized: row.get(5)?,
+        amount: row.get(6)?,
+        matched_transaction_id: row.get(7)?,
+        match_status: row.get(8)?,
+        match_score: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+/// Insert a statement row. Returns None if the same row was already imported,
+/// whatever account label either import was given.
+pub fn insert_statement_transaction(
+    conn: &Connection,
+    user_id: &str,
+    row: &NewStatementTransaction,
+) -> DbResult<Option<String>> {
+    let mut hasher = Sha256::new();
+    hasher.update(
+        format!(
+            "{}|{}|{}|{}",
+            row.posted_date,
+            row.description,
+            row.amount,
+            row.occurrence
+        )
+        .as_bytes(),
+    );
+    let source_hash = hex::encode(hasher.finalize());
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO statement_transactions
+         (id, user_id, import_id, account_label, posted_date, description, description_normalized,
+          amount, source_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            &id,
+            user_id,
+            row.import_id,
+            row.account_label,
+            row.posted_date,
+            row.description,
+            row.description_normalized,
+            row.amount,
+            &source_hash
+        ],
+    )?;
+
+    Ok(if inserted > 0 { Some(id) } else { None })
+}
+
+pub fn get_statement_transaction(
+    conn: &Connection,
+    id: &str,
+) -> DbResult<Option<StatementTransaction>> {
+    let statement = conn
+        .query_row(
+            &format!("SELECT {} FROM statement_transactions s WHERE s.id = ?1", STATEMENT_COLUMNS),
+            [id],
+            statement_from_row,
+        )
+        .optional()?;
+
+    Ok(statement)
+}
+
+/// Statement rows posted between `start_date` and `end_date` (inclusive, either optional)
+pub fn get_statement_transactions(
+    conn: &Connection,
+    user_id: &str,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> DbResult<Vec<StatementTransaction>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM statement_transactions s
+         WHERE s.user_id = ?1
+           AND (?2 IS NULL OR s.posted_date >= ?2)
+           AND (?3 IS NULL OR s.posted_date <= ?3)
+         ORDER BY s.posted_date DESC, s.rowid",
+        STATEMENT_COLUMNS
+    ))?;
+
+    let statements = stmt
+        .query_map(params![user_id, start_date, end_date], statement_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(statements)
+}
+
+pub fn get_unmatched_statement_transactions(
+    conn: &Connection,
+    user_id: &str,
+) -> DbResult<Vec<StatementTransaction>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM statement_transactions s
+         WHERE s.user_id = ?1 AND s.match_status = ?2
+         ORDER BY s.posted_date, s.rowid",
+        STATEMENT_COLUMNS
+    ))?;
+
+    let statements = stmt
+        .query_map(params![user_id, match_status::UNMATCHED], statement_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(statements)
+}
+
+/// Receipt purchases dated within the range that no statement row is matched to
+pub fn get_unreconciled_transactions(
+    conn: &Connection,
+    user_id: &str,
+    start_date: &str,
+    end_date: &str,
+) -> DbResult<Vec<TransactionWithCategory>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM transactions t
+         LEFT JOIN categories c ON t.category_id = c.id
+         WHERE t.user_id = ?1
+           AND t.transaction_date >= ?2 AND t.transaction_date <= ?3
+           AND COALESCE(t.source_type, '') != 'statement'
+           AND t.direction = 'expense'
+           AND NOT EXISTS (
+               SELECT 1 FROM statement_transactions s WHERE s.matched_transaction_id = t.id
+           )
+         ORDER BY t.transaction_date, t.id",
+        TRANSACTION_WITH_CATEGORY_COLUMNS
+    ))?;
+
+    let transactions = stmt
+        .query_map(params![user_id, start_date, end_date], transaction_with_category_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(transactions)
+}
+
+/// Link a statement row to a receipt (or unlink it with `transaction_id: None`).
+/// Any other statement row matched to the same receipt is unlinked first.
+pub fn set_statement_match(
+    conn: &Connection,
+    statement_id: &str,
+    transaction_id: Option<&str>,
+    status: &str,
+    score: Option<f64>,
+) -> DbResult<()> {
+    if let Some(transaction_id) = transaction_id {
+        conn.execute(
+            "UPDATE statement_transactions
+             SET matched_transaction_id = NULL, match_status = ?3, match_score = NULL
+             WHERE matched_transaction_id = ?1 AND id != ?2",
+            params![transaction_id, statement_id, match_status::UNMATCHED],
+        )?;
+    }
+
+    conn.execute(
+        "UPDATE statement_transactions
+         SET matched_transaction_id = ?2, match_status = ?3, match_score = ?4
+         WHERE id = ?1",
+        params![statement_id, transaction_id, status, score],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Fixtures shared by tests that need a database
+
+use rusqlite::Connection;
+
+use super::queries::{self, NewTransaction};
+use super::schema;
+
+/// An in-memory database with the current schema and the default categories
+pub fn open_seeded() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    schema::run_migrations(&conn).unwrap();
+    schema::seed_default_categories(&conn).unwrap();
+    conn
+}
+
+/// ID of the local user's category called `name`
+pub fn category(conn: &Connection, name: &str) -> String {
+    queries::find_category_by_name(conn, "local", name).unwrap().unwrap()
+}
+
+/// A transaction imported on 2024-03-01 from the generic provider. `merchant` is
+/// also its normalized name and `source_hash` must be unique per test.
+pub fn new_transaction<'a>(merchant: &'a str, amount: i64, source_hash: &'a str) -> NewTransaction<'a> {
+    NewTransaction {
+        merchant,
+        merchant_normalized: merchant,
+        amount,
+        transaction_date: "2024-03-01",
+        provider: "generic",
+        source_hash,
+        source_type: "import",
+        ..Default::default()
+    }
+}
+
+/// Insert `transaction` for the local user, returning its ID
+pub fn insert(conn: &Connection, transaction: &NewTransaction) -> String {
+    queries::insert_transaction(conn, "local", transaction).unwrap()
+}
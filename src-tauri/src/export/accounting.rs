@@ -0,0 +1,390 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::{ExportError, ExportResult};
+use crate::db::queries::{self, direction, TransactionFilters, TransactionWithCategory};
+use crate::parser::types::format_cents;
+
+const ACCOUNT_MAPPING_SETTING: &str = "account_mapping";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountingFormat {
+    Ofx,
+    Qif,
+    Ledger,
+    Beancount,
+}
+
+/// How transactions map onto double-entry accounts, stored in the `settings` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountMapping {
+    /// The account purchases are paid from, i.e. the other side of every expense
+    pub funding_account: String,
+    /// Expense account for transactions without a category
+    pub uncategorized_account: String,
+    pub currency: String,
+    /// Category ID -> expense account
+    pub categories: BTreeMap<String, String>,
+}
+
+impl Default for AccountMapping {
+    fn default() -> Self {
+        Self {
+            funding_account: "Liabilities:CreditCard".to_string(),
+            uncategorized_account: "Expenses:Uncategorized".to_string(),
+            currency: "USD".to_string(),
+            categories: BTreeMap::new(),
+        }
+    }
+}
+
+impl AccountMapping {
+    fn account_for(&self, transaction: &TransactionWithCategory) -> &str {
+        transaction
+            .category_id
+            .as_ref()
+            .and_then(|id| self.categories.get(id))
+            .map(|a| a.as_str())
+            .unwrap_or(&self.uncategorized_account)
+    }
+
+    /// Check every account name is usable by ledger and beancount
+    pub fn validate(&self) -> ExportResult<()> {
+        let accounts = [&self.funding_account, &self.uncategorized_account]
+            .into_iter()
+            .chain(self.categories.values());
+        for account in accounts {
+            if !is_valid_account(account) {
+                return Err(ExportError::Invalid(format!(
+                    "\"{}\" is not a valid account name (use e.g. Expenses:Food-Dining)",
+                    account
+                )));
+            }
+        }
+        if self.currency.is_empty() || !self.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(ExportError::Invalid(format!(
+                "\"{}\" is not a valid currency code",
+                self.currency
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `Root:Component:...` where each component starts with an uppercase letter or
+/// digit and contains only letters, digits and dashes
+fn is_valid_account(account: &str) -> bool {
+    let mut components = account.split(':');
+    let root_ok = components.next().is_some_and(|root| {
+        ["Assets", "Liabilities", "Equity", "Income", "Expenses"].contains(&root)
+    });
+    root_ok
+        && account.contains(':')
+        && components.all(|c| {
+            c.chars().next().is_some_and(|f| f.is_uppercase() || f.is_ascii_digit())
+                && c.chars().all(|ch| ch.is_alphanumeric() || ch == '-')
+        })
+}
+
+/// Default expense account for a category, e.g. "Food & Dining" -> "Expenses:Food-Dining".
+/// Only ASCII letters and digits are kept so the name always passes `validate`; a name
+/// with none, e.g. "食品", gets an account named after the category ID instead.
+pub fn default_account_name(category_name: &str, category_id: &str) -> String {
+    let component = account_component(category_name);
+    if component.is_empty() {
+        let id: String = category_id.chars().filter(char::is_ascii_alphanumeric).take(8).collect();
+        format!("Expenses:Category-{}", id.to_ascii_uppercase())
+    } else {
+        format!("Expenses:{}", component)
+    }
+}
+
+/// The ASCII letters and digits of each word, capitalized and joined by dashes
+fn account_component(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.chars().filter(char::is_ascii_alphanumeric).collect::<String>())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub fn get_account_mapping(conn: &Connection, user_id: &str) -> ExportResult<AccountMapping> {
+    match queries::get_setting(conn, user_id, ACCOUNT_MAPPING_SETTING)? {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(AccountMapping::default()),
+    }
+}
+
+pub fn set_account_mapping(
+    conn: &Connection,
+    user_id: &str,
+    mapping: &AccountMapping,
+) -> ExportResult<()> {
+    mapping.validate()?;
+    queries::set_setting(conn, user_id, ACCOUNT_MAPPING_SETTING, &serde_json::to_string(mapping)?)?;
+    Ok(())
+}
+
+/// The saved mapping with a default account added for every unmapped category.
+/// Returns whether anything was added.
+pub fn complete_account_mapping(
+    conn: &Connection,
+    user_id: &str,
+) -> ExportResult<(AccountMapping, bool)> {
+    let mut mapping = get_account_mapping(conn, user_id)?;
+    let mut added = false;
+
+    for category in queries::get_all_categories(conn, user_id)? {
+        let account = default_account_name(&category.name, &category.id);
+        if let Entry::Vacant(entry) = mapping.categories.entry(category.id) {
+            entry.insert(account);
+            added = true;
+        }
+    }
+
+    Ok((mapping, added))
+}
+
+/// Export transactions matching `filters` in an accounting format.
+///
+/// Unmapped categories get a default account which is saved to `settings`, so renaming
+/// a category later doesn't move its history to a new account. Expenses are posted to
+/// the category's account against `funding_account`; the provider is the payee and the
+/// merchant is the memo.
+pub fn export_accounting(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+    format: AccountingFormat,
+    path: &Path,
+) -> ExportResult<usize> {
+    let (mapping, added) = complete_account_mapping(conn, user_id)?;
+    if added {
+        set_account_mapping(conn, user_id, &mapping)?;
+    }
+
+    // Oldest first with a stable tie-break, so re-exports produce identical journals
+    let mut transactions = queries::get_transactions(conn, user_id, filters)?;
+    transactions.sort_by(|a, b| {
+        (&a.transaction_date, &a.created_at, &a.id).cmp(&(&b.transaction_date, &b.created_at, &b.id))
+    });
+
+    let output = match format {
+        AccountingFormat::Ofx => render_ofx(&transactions, &mapping),
+        AccountingFormat::Qif => render_qif(&transactions, &mapping),
+        AccountingFormat::Ledger => render_ledger(&transactions, &mapping),
+        AccountingFormat::Beancount => render_beancount(&transactions, &mapping),
+    };
+    std::fs::write(path, output)?;
+
+    Ok(transactions.len())
+}
+
+pub fn render_ledger(transactions: &[TransactionWithCategory], mapping: &AccountMapping) -> String {
+    let mut out = String::new();
+    for t in transactions {
+        let amount = format_cents(net_spent(t));
+        let _ = writeln!(out, "{} * {}", t.transaction_date, single_line(&t.provider));
+        let _ = writeln!(out, "    ; {}", single_line(&t.merchant));
+        let _ = writeln!(out, "    {}  {} {}", mapping.account_for(t), amount, mapping.currency);
+        let _ = writeln!(out, "    {}", mapping.funding_account);
+        out.push('\n');
+    }
+    out
+}
+
+pub fn render_beancount(
+    transactions: &[TransactionWithCategory],
+    mapping: &AccountMapping,
+) -> String {
+    let mut out = String::new();
+
+    // Beancount rejects postings to accounts that were never opened
+    if let Some(first) = transactions.first() {
+        let accounts: BTreeSet<&str> = transactions
+            .iter()
+            .map(|t| mapping.account_for(t))
+            .chain([mapping.funding_account.as_str()])
+            .collect();
+        for account in accounts {
+            let _ = writeln!(out, "{} open {}", first.transaction_date, account);
+        }
+        out.push('\n');
+    }
+
+    for t in transactions {
+        let amount = format_cents(net_spent(t));
+        let _ = writeln!(
+            out,
+            "{} * \"{}\" \"{}\"",
+            t.transaction_date,
+            beancount_string(&t.provider),
+            beancount_string(&t.merchant)
+        );
+        let _ = writeln!(out, "  id: \"{}\"", t.id);
+        let _ = writeln!(out, "  {}  {} {}", mapping.account_for(t), amount, mapping.currency);
+        let _ = writeln!(
+            out,
+            "  {}  {} {}",
+            mapping.funding_account,
+            format_cents(-net_spent(t)),
+            mapping.currency
+        );
+        out.push('\n');
+    }
+    out
+}
+
+pub fn render_qif(transactions: &[TransactionWithCategory], mapping: &AccountMapping) -> String {
+    let mut out = String::from("!Type:CCard\n");
+    for t in transactions {
+        let date = chrono::NaiveDate::parse_from_str(&t.transaction_date, "%Y-%m-%d")
+            .map(|d| d.format("%m/%d/%Y").to_string())
+            .unwrap_or_else(|_| t.transaction_date.clone());
+        let _ = writeln!(out, "D{}", date);
+        let _ = writeln!(out, "T{}", format_cents(-net_spent(t)));
+        let _ = writeln!(out, "P{}", single_line(&t.provider));
+        let _ = writeln!(out, "M{}", single_line(&t.merchant));
+        let _ = writeln!(out, "L{}", mapping.account_for(t));
+        out.push_str("^\n");
+    }
+    out
+}
+
+pub fn render_ofx(transactions: &[TransactionWithCategory], mapping: &AccountMapping) -> String {
+    let ofx_date = |date: &str| date.replace('-', "");
+    let start = transactions.first().map(|t| ofx_date(&t.transaction_date)).unwrap_or_default();
+    let end = transactions.last().map(|t| ofx_date(&t.transaction_date)).unwrap_or_default();
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n\
+         <OFX>\n<CREDITCARDMSGSRSV1>\n<CCSTMTTRNRS>\n<TRNUID>0</TRNUID>\n\
+         <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n<CCSTMTRS>\n",
+    );
+    let _ = writeln!(out, "<CURDEF>{}</CURDEF>", mapping.currency);
+    let _ = writeln!(
+        out,
+        "<CCACCTFROM><ACCTID>{}</ACCTID></CCACCTFROM>",
+        xml_escape(&mapping.funding_account)
+    );
+    let _ = writeln!(out, "<BANKTRANLIST>\n<DTSTART>{}</DTSTART>\n<DTEND>{}</DTEND>", start, end);
+
+    for t in transactions {
+        out.push_str("<STMTTRN>\n");
+        let _ = writeln!(out, "<TRNTYPE>{}</TRNTYPE>", if net_spent(t) >= 0 { "DEBIT" } else { "CREDIT" });
+        let _ = writeln!(out, "<DTPOSTED>{}</DTPOSTED>", ofx_date(&t.transaction_date));
+        let _ = writeln!(out, "<TRNAMT>{}</TRNAMT>", format_cents(-net_spent(t)));
+        let _ = writeln!(out, "<FITID>{}</FITID>", t.id);
+        // OFX limits NAME to 32 characters
+        let name: String = single_line(&t.provider).chars().take(32).collect();
+        let _ = writeln!(out, "<NAME>{}</NAME>", xml_escape(&name));
+        let _ = writeln!(
+            out,
+            "<MEMO>{} [{}]</MEMO>",
+            xml_escape(&single_line(&t.merchant)),
+            xml_escape(mapping.account_for(t))
+        );
+        out.push_str("</STMTTRN>\n");
+    }
+
+    out.push_str("</BANKTRANLIST>\n</CCSTMTRS>\n</CCSTMTTRNRS>\n</CREDITCARDMSGSRSV1>\n</OFX>\n");
+    out
+}
+
+/// The amount leaving the funding account: negative for income
+fn net_spent(transaction: &TransactionWithCategory) -> i64 {
+    if transaction.direction == direction::INCOME {
+        -transaction.amount
+    } else {
+        transaction.amount
+    }
+}
+
+fn single_line(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn beancount_string(s: &str) -> String {
+    single_line(s).replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(id: &str, category_id: Option<&str>, amount: i64) -> TransactionWithCategory {
+        TransactionWithCategory {
+            id: id.to_string(),
+            user_id: "local".to_string(),
+            category_id: category_id.map(|c| c.to_string()),
+            merchant: "Blue \"Bottle\" Coffee".to_string(),
+            merchant_normalized: "blue bottle coffee".to_string(),
+            amount,
+            transaction_date: "2024-03-05".to_string(),
+            provider: "doordash".to_string(),
+            source_hash: id.to_string(),
+            notes: None,
+            created_at: "2024-03-05 10:00:00".to_string(),
+            updated_at: "2024-03-05 10:00:00".to_string(),
+            category_name: None,
+            category_color: None,
+            category_icon: None,
+            account_id: None,
+            account_name: None,
+            direction: "expense".to_string(),
+            merchant_id: None,
+        }
+    }
+
+    #[test]
+    fn test_default_account_name() {
+        assert_eq!(default_account_name("Food & Dining", "c1"), "Expenses:Food-Dining");
+        assert_eq!(default_account_name("subscriptions", "c1"), "Expenses:Subscriptions");
+        assert_eq!(default_account_name("Cafés", "c1"), "Expenses:Cafs");
+        assert_eq!(default_account_name("???", "4f0e-a1"), "Expenses:Category-4F0EA1");
+        for name in ["Health & Fitness", "école", "食品", "Ünterhaltung", "2nd home", "ß"] {
+            let account = default_account_name(name, "4f0e-a1");
+            assert!(is_valid_account(&account), "{} -> {}", name, account);
+        }
+        assert!(!is_valid_account("Food"));
+        assert!(!is_valid_account("Expenses:food dining"));
+    }
+
+    #[test]
+    fn test_render_beancount_balances() {
+        let mut mapping = AccountMapping::default();
+        mapping
+            .categories
+            .insert("food".to_string(), "Expenses:Food-Dining".to_string());
+        let transactions = vec![transaction("t1", Some("food"), 1250), transaction("t2", None, -300)];
+
+        let journal = render_beancount(&transactions, &mapping);
+        assert!(journal.contains("2024-03-05 open Expenses:Food-Dining"));
+        assert!(journal.contains("2024-03-05 * \"doordash\" \"Blue \\\"Bottle\\\" Coffee\""));
+        assert!(journal.contains("  Expenses:Food-Dining  12.50 USD"));
+        assert!(journal.contains("  Liabilities:CreditCard  -12.50 USD"));
+        assert!(journal.contains("  Expenses:Uncategorized  -3.00 USD"));
+        assert!(journal.contains("  Liabilities:CreditCard  3.00 USD"));
+    }
+}
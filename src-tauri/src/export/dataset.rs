@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::records::write_json;
+use super::{ExportError, ExportResult};
+use crate::db::queries::{self, Budget, Category, MerchantCategoryRule, RuleInput};
+use crate::gmail::tokens;
+use crate::gmail::types::SenderFilter;
+
+/// Bumped when the dataset layout changes incompatibly
+pub const DATASET_FORMAT_VERSION: u32 = 1;
+
+/// Everything the user has configured, as opposed to imported transactions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dataset {
+    pub format_version: u32,
+    pub exported_at: String,
+    pub categories: Vec<Category>,
+    pub merchant_category_rules: Vec<MerchantCategoryRule>,
+    pub budgets: Vec<Budget>,
+    pub sender_filters: Vec<SenderFilter>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatasetImportResult {
+    pub categories_created: usize,
+    pub categories_updated: usize,
+    pub merchant_category_rules: usize,
+    pub budgets: usize,
+    pub sender_filters: usize,
+}
+
+pub fn build_dataset(conn: &Connection, user_id: &str) -> ExportResult<Dataset> {
+    let budgets = queries::get_all_budgets(conn, user_id)?
+        .into_iter()
+        .map(|b| Budget {
+            id: b.id,
+            user_id: b.user_id,
+            category_id: b.category_id,
+            amount: b.amount,
+            period: b.period,
+            start_date: b.start_date,
+            created_at: b.created_at,
+            updated_at: b.updated_at,
+        })
+        .collect();
+
+    Ok(Dataset {
+        format_version: DATASET_FORMAT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        categories: queries::get_all_categories(conn, user_id)?,
+        merchant_category_rules: queries::get_merchant_category_rules(conn, user_id)?,
+        budgets,
+        sender_filters: tokens::get_sender_filters(conn)?,
+    })
+}
+
+pub fn export_dataset(conn: &Connection, user_id: &str, path: &Path) -> ExportResult<Dataset> {
+    let dataset = build_dataset(conn, user_id)?;
+    write_json(path, &dataset)?;
+    Ok(dataset)
+}
+
+pub fn import_dataset_file(
+    conn: &Connection,
+    user_id: &str,
+    path: &Path,
+) -> ExportResult<DatasetImportResult> {
+    let contents = std::fs::read_to_string(path)?;
+    let dataset: Dataset = serde_json::from_str(&contents)?;
+    import_dataset(conn, user_id, &dataset)
+}
+
+/// Merge an exported dataset into the database in a single transaction.
+///
+/// Categories are matched by ID, then by name, so importing into the database it
+/// came from updates in place and importing elsewhere doesn't duplicate categories
+/// that already exist. Rules, budgets and sender filters are upserted on their
+/// natural keys (pattern, category + period, email).
+pub fn import_dataset(
+    conn: &Connection,
+    user_id: &str,
+    dataset: &Dataset,
+) -> ExportResult<DatasetImportResult> {
+    if dataset.format_version > DATASET_FORMAT_VERSION {
+        return Err(ExportError::Invalid(format!(
+            "dataset format v{} is newer than this app supports (v{})",
+            dataset.format_version, DATASET_FORMAT_VERSION
+        )));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut result = DatasetImportResult::default();
+
+    // Exported category ID -> local category ID
+    let mut category_ids: HashMap<&str, String> = HashMap::new();
+
+    for category in &dataset.categories {
+        let by_id = queries::get_category_by_id(&tx, &category.id)?
+            .filter(|c| c.user_id == user_id)
+            .map(|c| c.id);
+        let existing = match by_id {
+            Some(id) => Some(id),
+            None => tx
+                .query_row(
+                    "SELECT id FROM categories WHERE user_id = ?1 AND name = ?2 COLLATE NOCASE",
+                    params![user_id, &category.name],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+
+        let local_id = match existing {
+            Some(id) => {
+                queries::update_category(&tx, &id, &category.name, &category.icon, &category.color)?;
+                result.categories_updated += 1;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO categories (id, user_id, name, icon, color, is_system, system_key)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        &category.id,
+                        user_id,
+                        &category.name,
+                        &category.icon,
+                        &category.color,
+                        category.is_system,
+                        &category.system_key
+                    ],
+                )?;
+                result.categories_created += 1;
+                category.id.clone()
+            }
+        };
+        queries::set_category_hidden(&tx, &local_id, category.is_hidden)?;
+        category_ids.insert(&category.id, local_id);
+    }
+
+    // Rebuild the hierarchy once every category exists. Parents are cleared first so
+    // an imported tree that nests differently from the local one can't form a cycle.
+    for category in &dataset.categories {
+        tx.execute(
+            "UPDATE categories SET parent_id = NULL WHERE id = ?1",
+            [&category_ids[category.id.as_str()]],
+        )?;
+    }
+    for category in &dataset.categories {
+        if let Some(parent_id) = category.parent_id.as_deref().and_then(|p| category_ids.get(p)) {
+            queries::set_category_parent(&tx, &category_ids[category.id.as_str()], Some(parent_id))?;
+        }
+    }
+
+    let resolve = |category_id: &str| -> ExportResult<String> {
+        category_ids.get(category_id).cloned().ok_or_else(|| {
+            ExportError::Invalid(format!("unknown category ID {}", category_id))
+        })
+    };
+
+    for rule in &dataset.merchant_category_rules {
+        // Rules exported before match types only have `is_exact_match`
+        let match_type = if rule.is_exact_match { "exact".to_string() } else { rule.match_type.clone() };
+        let input = RuleInput {
+            name: rule.name.clone(),
+            merchant_pattern: rule.merchant_pattern.clone(),
+            match_type,
+            provider: rule.provider.clone(),
+            min_amount: rule.min_amount,
+            max_amount: rule.max_amount,
+            days_of_week: rule.days_of_week.clone(),
+            item_keyword: rule.item_keyword.clone(),
+            sender: rule.sender.clone(),
+            category_id: resolve(&rule.category_id)?,
+            priority: None,
+            enabled: rule.enabled,
+        };
+        queries::import_category_rule(&tx, user_id, &input)?;
+        result.merchant_category_rules += 1;
+    }
+
+    for budget in &dataset.budgets {
+        tx.execute(
+            "INSERT INTO budgets (id, user_id, category_id, amount, period, start_date)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (user_id, category_id, period) DO UPDATE SET
+                 amount = excluded.amount,
+                 start_date = excluded.start_date,
+                 updated_at = datetime('now')",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                user_id,
+                resolve(&budget.category_id)?,
+                budget.amount,
+                &budget.period,
+                &budget.start_date
+            ],
+        )?;
+        result.budgets += 1;
+    }
+
+    for filter in &dataset.sender_filters {
+        tx.execute(
+            "INSERT INTO gmail_sender_filters (id, email, label, enabled) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (email) DO UPDATE SET label = excluded.label, enabled = excluded.enabled",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                &filter.email,
+                &filter.label,
+                filter.enabled
+            ],
+        )?;
+        result.sender_filters += 1;
+    }
+
+    tx.commit()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    #[test]
+    fn test_dataset_round_trip() {
+        let source = Connection::open_in_memory().unwrap();
+        schema::run_migrations(&source).unwrap();
+        schema::seed_default_categories(&source).unwrap();
+        let food = queries::get_all_categories(&source, "local")
+            .unwrap()
+            .into_iter()
+            .find(|c| c.name == "Food & Dining")
+            .unwrap();
+        let coffee =
+            queries::create_category(&source, "local", "Coffee", "cup", "#6b4", Some(&food.id)).unwrap();
+        queries::set_merchant_category_rule(&source, "local", "blue bottle", &coffee.id, false)
+            .unwrap();
+        queries::set_budget(&source, "local", &coffee.id, 5000, "monthly").unwrap();
+        tokens::add_sender_filter(&source, "receipts@example.com", "Example").unwrap();
+
+        let dataset = build_dataset(&source, "local").unwrap();
+        let json = serde_json::to_string(&dataset).unwrap();
+
+        let target = Connection::open_in_memory().unwrap();
+        schema::run_migrations(&target).unwrap();
+        schema::seed_default_categories(&target).unwrap();
+        let result = import_dataset(&target, "local", &serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(result.categories_created, 1);
+        assert_eq!(result.budgets, 1);
+
+        let imported = build_dataset(&target, "local").unwrap();
+        assert_eq!(imported.categories.len(), dataset.categories.len());
+        assert_eq!(imported.merchant_category_rules[0].category_id, coffee.id);
+        let imported_coffee = imported.categories.iter().find(|c| c.id == coffee.id).unwrap();
+        let imported_food = imported.categories.iter().find(|c| c.name == "Food & Dining").unwrap();
+        assert_eq!(imported_coffee.parent_id.as_ref(), Some(&imported_food.id));
+        assert_eq!(imported.budgets[0].amount, 5000);
+        assert!(imported
+            .sender_filters
+            .iter()
+            .any(|f| f.email == "receipts@example.com"));
+
+        // Importing again updates in place
+        let again = import_dataset(&target, "local", &dataset).unwrap();
+        assert_eq!(again.categories_created, 0);
+        assert_eq!(build_dataset(&target, "local").unwrap().budgets.len(), 1);
+    }
+}
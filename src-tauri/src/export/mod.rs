@@ -0,0 +1,44 @@
+pub mod records;
+pub mod dataset;
+pub mod accounting;
+pub mod rules;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::db::DbError;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid file: {0}")]
+    Invalid(String),
+}
+
+impl From<rusqlite::Error> for ExportError {
+    fn from(e: rusqlite::Error) -> Self {
+        ExportError::Db(e.into())
+    }
+}
+
+pub type ExportResult<T> = Result<T, ExportError>;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub row_count: usize,
+}
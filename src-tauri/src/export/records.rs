@@ -0,0 +1,255 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use super::{ExportFormat, ExportResult};
+use crate::db::queries::{self, TransactionFilters};
+use crate::parser::types::format_cents;
+
+/// A transaction as written to export files. Amounts are decimal strings.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionRecord {
+    pub id: String,
+    pub date: String,
+    pub merchant: String,
+    pub amount: String,
+    /// 'expense' or 'income'
+    pub direction: String,
+    pub category: Option<String>,
+    pub account: Option<String>,
+    pub provider: String,
+    pub notes: Option<String>,
+    pub items: Vec<ItemRecord>,
+    pub fees: Vec<FeeRecord>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemRecord {
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: String,
+    pub total_price: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeRecord {
+    pub kind: String,
+    pub label: String,
+    pub amount: String,
+}
+
+/// CSV has no nesting, so items and fees are flattened into `; `-separated columns
+#[derive(Debug, Serialize)]
+struct TransactionCsvRow<'a> {
+    id: &'a str,
+    date: &'a str,
+    merchant: &'a str,
+    amount: &'a str,
+    direction: &'a str,
+    category: Option<&'a str>,
+    account: Option<&'a str>,
+    provider: &'a str,
+    notes: Option<&'a str>,
+    items: String,
+    fees: String,
+}
+
+/// One row per purchased item, for item-level spending analysis
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemRowRecord {
+    pub transaction_id: String,
+    pub date: String,
+    pub merchant: String,
+    pub category: Option<String>,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: String,
+    pub total_price: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetRecord {
+    pub category: String,
+    pub period: String,
+    pub amount: String,
+    pub spent: String,
+    pub remaining: String,
+    pub percentage: f64,
+    pub is_over_budget: bool,
+    pub start_date: String,
+}
+
+/// Load the transactions matching `filters` with their items and fees
+pub fn transaction_records(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+) -> ExportResult<Vec<TransactionRecord>> {
+    let transactions = queries::get_transactions(conn, user_id, filters)?;
+    let mut records = Vec::with_capacity(transactions.len());
+
+    for t in transactions {
+        let items = queries::get_transaction_items(conn, &t.id)?
+            .into_iter()
+            .map(|i| ItemRecord {
+                name: i.name,
+                quantity: i.quantity,
+                unit_price: format_cents(i.unit_price),
+                total_price: format_cents(i.total_price),
+            })
+            .collect();
+        let fees = queries::get_transaction_fees(conn, &t.id)?
+            .into_iter()
+            .map(|f| FeeRecord {
+                kind: f.kind,
+                label: f.label,
+                amount: format_cents(f.amount),
+            })
+            .collect();
+
+        records.push(TransactionRecord {
+            id: t.id,
+            date: t.transaction_date,
+            merchant: t.merchant,
+            amount: format_cents(t.amount),
+            direction: t.direction,
+            category: t.category_name,
+            account: t.account_name,
+            provider: t.provider,
+            notes: t.notes,
+            items,
+            fees,
+        });
+    }
+
+    Ok(records)
+}
+
+/// Write the transactions matching `filters` to `path`. Returns the number exported.
+pub fn export_transactions(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+    format: ExportFormat,
+    path: &Path,
+) -> ExportResult<usize> {
+    let records = transaction_records(conn, user_id, filters)?;
+
+    match format {
+        ExportFormat::Json => write_json(path, &records)?,
+        ExportFormat::Csv => {
+            let rows: Vec<TransactionCsvRow> = records
+                .iter()
+                .map(|r| TransactionCsvRow {
+                    id: &r.id,
+                    date: &r.date,
+                    merchant: &r.merchant,
+                    amount: &r.amount,
+                    direction: &r.direction,
+                    category: r.category.as_deref(),
+                    account: r.account.as_deref(),
+                    provider: &r.provider,
+                    notes: r.notes.as_deref(),
+                    items: r
+                        .items
+                        .iter()
+                        .map(|i| format!("{} x{} ({})", i.name, i.quantity, i.total_price))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                    fees: r
+                        .fees
+                        .iter()
+                        .map(|f| format!("{} ({})", f.label, f.amount))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                })
+                .collect();
+            write_csv(path, &rows)?;
+        }
+    }
+
+    Ok(records.len())
+}
+
+/// Write every item of the transactions matching `filters` to `path`, one row per item
+pub fn export_items(
+    conn: &Connection,
+    user_id: &str,
+    filters: &TransactionFilters,
+    format: ExportFormat,
+    path: &Path,
+) -> ExportResult<usize> {
+    let rows: Vec<ItemRowRecord> = transaction_records(conn, user_id, filters)?
+        .into_iter()
+        .flat_map(|t| {
+            let TransactionRecord {
+                id,
+                date,
+                merchant,
+                category,
+                items,
+                ..
+            } = t;
+            items.into_iter().map(move |i| ItemRowRecord {
+                transaction_id: id.clone(),
+                date: date.clone(),
+                merchant: merchant.clone(),
+                category: category.clone(),
+                name: i.name,
+                quantity: i.quantity,
+                unit_price: i.unit_price,
+                total_price: i.total_price,
+            })
+        })
+        .collect();
+
+    match format {
+        ExportFormat::Json => write_json(path, &rows)?,
+        ExportFormat::Csv => write_csv(path, &rows)?,
+    }
+    Ok(rows.len())
+}
+
+/// Write all budgets with their current-period progress to `path`
+pub fn export_budgets(
+    conn: &Connection,
+    user_id: &str,
+    format: ExportFormat,
+    path: &Path,
+) -> ExportResult<usize> {
+    let rows: Vec<BudgetRecord> = queries::get_all_budgets(conn, user_id)?
+        .into_iter()
+        .map(|b| BudgetRecord {
+            category: b.category_name,
+            period: b.period,
+            amount: format_cents(b.amount),
+            spent: format_cents(b.spent),
+            remaining: format_cents(b.remaining),
+            percentage: (b.percentage * 10.0).round() / 10.0,
+            is_over_budget: b.is_over_budget,
+            start_date: b.start_date,
+        })
+        .collect();
+
+    match format {
+        ExportFormat::Json => write_json(path, &rows)?,
+        ExportFormat::Csv => write_csv(path, &rows)?,
+    }
+    Ok(rows.len())
+}
+
+pub(super) fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> ExportResult<()> {
+    let file = std::fs::File::create(path)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), value)?;
+    Ok(())
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> ExportResult<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
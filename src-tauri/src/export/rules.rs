@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::records::write_json;
+use super::{ExportError, ExportResult};
+use crate::categorizer::rule_engine::describe_rule;
+use crate::db::queries::{self, RuleInput};
+
+/// Bumped when the rule set layout changes incompatibly
+pub const RULE_SET_FORMAT_VERSION: u32 = 1;
+
+/// Categorization rules in a form another user can import. Categories are referred
+/// to by name because IDs differ between databases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub format_version: u32,
+    pub exported_at: String,
+    /// In priority order
+    pub rules: Vec<SharedRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merchant_pattern: Option<String>,
+    pub match_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days_of_week: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_keyword: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Category name
+    pub category: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleSetImportResult {
+    /// Rules created, or existing rules with the same conditions updated
+    pub imported: usize,
+    /// Why each rule that couldn't be imported was skipped
+    pub skipped: Vec<String>,
+}
+
+pub fn build_rule_set(conn: &Connection, user_id: &str) -> ExportResult<RuleSet> {
+    let categories = queries::get_all_categories(conn, user_id)?;
+
+    let rules = queries::get_merchant_category_rules(conn, user_id)?
+        .into_iter()
+        .filter_map(|rule| {
+            let category = categories.iter().find(|c| c.id == rule.category_id)?.name.clone();
+            Some(SharedRule {
+                name: rule.name,
+                merchant_pattern: rule.merchant_pattern,
+                match_type: rule.match_type,
+                provider: rule.provider,
+                min_amount: rule.min_amount,
+                max_amount: rule.max_amount,
+                days_of_week: rule.days_of_week,
+                item_keyword: rule.item_keyword,
+                sender: rule.sender,
+                category,
+                enabled: rule.enabled,
+            })
+        })
+        .collect();
+
+    Ok(RuleSet {
+        format_version: RULE_SET_FORMAT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        rules,
+    })
+}
+
+pub fn export_rules(conn: &Connection, user_id: &str, path: &Path) -> ExportResult<RuleSet> {
+    let rule_set = build_rule_set(conn, user_id)?;
+    write_json(path, &rule_set)?;
+    Ok(rule_set)
+}
+
+pub fn import_rules_file(conn: &Connection, user_id: &str, path: &Path) -> ExportResult<RuleSetImportResult> {
+    let contents = std::fs::read_to_string(path)?;
+    let rule_set: RuleSet = serde_json::from_str(&contents)?;
+    import_rule_set(conn, user_id, &rule_set)
+}
+
+/// Add a shared rule set after the existing rules, in a single transaction. Rules
+/// whose conditions match an existing rule update its category instead, and rules
+/// for categories that don't exist here are skipped.
+pub fn import_rule_set(conn: &Connection, user_id: &str, rule_set: &RuleSet) -> ExportResult<RuleSetImportResult> {
+    if rule_set.format_version > RULE_SET_FORMAT_VERSION {
+        return Err(ExportError::Invalid(format!(
+            "rule set format v{} is newer than this app supports (v{})",
+            rule_set.format_version, RULE_SET_FORMAT_VERSION
+        )));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut result = RuleSetImportResult::default();
+
+    for rule in &rule_set.rules {
+        let Some(category_id) = queries::find_category_by_name(&tx, user_id, &rule.category)? else {
+            result.skipped.push(format!("No category named \"{}\"", rule.category));
+            continue;
+        };
+        let input = RuleInput {
+            name: rule.name.clone(),
+            merchant_pattern: rule.merchant_pattern.clone(),
+            match_type: rule.match_type.clone(),
+            provider: rule.provider.clone(),
+            min_amount: rule.min_amount,
+            max_amount: rule.max_amount,
+            days_of_week: rule.days_of_week.clone(),
+            item_keyword: rule.item_keyword.clone(),
+            sender: rule.sender.clone(),
+            category_id,
+            priority: None,
+            enabled: rule.enabled,
+        };
+
+        match queries::import_category_rule(&tx, user_id, &input) {
+            Ok(imported) => {
+                log::debug!("Imported rule {}", describe_rule(&imported));
+                result.imported += 1;
+            }
+            Err(crate::db::DbError::InvalidInput(message)) => result.skipped.push(message),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    tx.commit()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{category, open_seeded};
+
+    #[test]
+    fn test_rule_set_round_trip() {
+        let source = open_seeded();
+        let travel = category(&source, "Travel");
+        let mut hotels = RuleInput::for_merchant("hotel*", "glob", &travel);
+        hotels.min_amount = Some(10000);
+        queries::create_category_rule(&source, "local", &hotels).unwrap();
+        let custom = queries::create_category(&source, "local", "Side Project", "box", "#000000", None).unwrap();
+        queries::create_category_rule(&source, "local", &RuleInput::for_merchant("github", "contains", &custom.id))
+            .unwrap();
+
+        let rule_set = build_rule_set(&source, "local").unwrap();
+        assert_eq!(rule_set.rules.len(), 2);
+        let json = serde_json::to_string(&rule_set).unwrap();
+
+        let target = open_seeded();
+        let rule_set: RuleSet = serde_json::from_str(&json).unwrap();
+        for _ in 0..2 {
+            let result = import_rule_set(&target, "local", &rule_set).unwrap();
+            assert_eq!(result.imported, 1);
+            assert_eq!(result.skipped, vec!["No category named \"Side Project\"".to_string()]);
+        }
+
+        let rules = queries::get_merchant_category_rules(&target, "local").unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].merchant_pattern.as_deref(), Some("hotel*"));
+        assert_eq!(rules[0].min_amount, Some(10000));
+    }
+}
@@ -1,7 +1,7 @@
 use tauri::{AppHandle, Emitter};
 
-use crate::categorizer::categorize_transaction;
-use crate::db::{self, queries};
+use crate::commands::import::save_parsed_transaction;
+use crate::db;
 use crate::parser;
 
 use super::client::GmailClient;
@@ -17,7 +17,7 @@ pub async fn run_sync_cycle(app_handle: &AppHandle) -> Result<SyncCycleResult, S
 
     // Read state from DB (no await while conn is live)
     let (sender_emails, sync_state) = {
-        let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+        let conn = db::reader(app_handle).map_err(|e| e.to_string())?;
         let emails = tokens::get_enabled_sender_emails(&conn).map_err(|e| e.to_string())?;
         let state = tokens::get_sync_state(&conn).map_err(|e| e.to_string())?;
         (emails, state)
@@ -34,7 +34,7 @@ pub async fn run_sync_cycle(app_handle: &AppHandle) -> Result<SyncCycleResult, S
                 Ok(r) => r,
                 Err(e) if e == "HISTORY_EXPIRED" => {
                     log::warn!("History ID expired, falling back to initial sync");
-                    let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+                    let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
                     tokens::reset_sync_state(&conn).map_err(|e| e.to_string())?;
                     drop(conn);
                     initial_sync(app_handle, &gmail, &sender_emails).await?
@@ -101,7 +101,7 @@ async fn initial_sync(
     // Get current history ID to use for future incremental syncs
     let profile = gmail.get_profile().await?;
     if let Some(history_id) = profile.history_id {
-        let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+        let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
         tokens::upsert_sync_state(&conn, Some(&history_id), true)
             .map_err(|e| e.to_string())?;
     }
@@ -163,7 +163,7 @@ async fn incremental_sync(
 
     // Update sync state with new history ID
     if let Some(hid) = new_history_id {
-        let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+        let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
         tokens::upsert_sync_state(&conn, Some(&hid), true)
             .map_err(|e| e.to_string())?;
     }
@@ -185,6 +185,16 @@ enum ProcessResult {
     Skipped,
 }
 
+/// The address in a From header, e.g. "Amazon <auto-confirm@amazon.com>" -> "auto-confirm@amazon.com"
+fn sender_address(from_header: &str) -> Option<String> {
+    let address = match (from_header.rfind('<'), from_header.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &from_header[start + 1..end],
+        _ => from_header,
+    };
+    let address = address.trim().to_lowercase();
+    (!address.is_empty()).then_some(address)
+}
+
 /// Process a single Gmail message: fetch, check sender, parse HTML, save transaction
 async fn process_message(
     app_handle: &AppHandle,
@@ -194,7 +204,7 @@ async fn process_message(
 ) -> Result<ProcessResult, String> {
     // Check if already processed (short-lived connection)
     {
-        let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+        let conn = db::reader(app_handle).map_err(|e| e.to_string())?;
         if tokens::is_message_processed(&conn, message_id).map_err(|e| e.to_string())? {
             return Ok(ProcessResult::Skipped);
         }
@@ -212,7 +222,7 @@ async fn process_message(
         .any(|email| from_lower.contains(&email.to_lowercase()));
 
     if !sender_match {
-        let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+        let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
         tokens::mark_message_processed(&conn, message_id).map_err(|e| e.to_string())?;
         return Ok(ProcessResult::Skipped);
     }
@@ -221,7 +231,7 @@ async fn process_message(
     let html = match GmailClient::extract_html_body(&message) {
         Some(h) => h,
         None => {
-            let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+            let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
             tokens::mark_message_processed(&conn, message_id).map_err(|e| e.to_string())?;
             return Ok(ProcessResult::Skipped);
         }
@@ -231,63 +241,20 @@ async fn process_message(
     let parse_result = parser::parse_html(&html);
 
     // All DB operations below are sync — open one connection for the block
-    let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+    let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
 
     match parse_result {
-        parser::types::ParseResult::Success(transaction) => {
-            let source_hash = transaction.source_hash();
-
-            // Check for duplicate via source_hash (covers both Gmail + manual import)
-            if queries::transaction_exists(&conn, &source_hash).unwrap_or(false) {
-                tokens::mark_message_processed(&conn, message_id).map_err(|e| e.to_string())?;
-                return Ok(ProcessResult::Duplicate);
-            }
+        parser::types::ParseResult::Success(mut transaction) => {
+            transaction.sender = sender_address(&from_header);
+            transaction.source_ref = Some(message_id.to_string());
 
-            let user_id = "local";
-            let merchant_normalized = transaction.merchant_normalized();
-
-            // Auto-categorize
-            let category_id = categorize_transaction(
-                &conn,
-                user_id,
-                &merchant_normalized,
-                &transaction.provider,
-            )
-            .ok()
-            .flatten();
-
-            // Insert transaction
-            match queries::insert_transaction(
-                &conn,
-                user_id,
-                category_id.as_deref(),
-                &transaction.merchant,
-                &merchant_normalized,
-                transaction.amount,
-                &transaction.transaction_date,
-                &transaction.provider,
-                &source_hash,
-            ) {
-                Ok(transaction_id) => {
-                    for item in &transaction.items {
-                        let _ = queries::insert_transaction_item(
-                            &conn,
-                            &transaction_id,
-                            &item.name,
-                            item.quantity,
-                            item.unit_price,
-                            item.total_price,
-                        );
-                    }
-                    tokens::mark_message_processed(&conn, message_id)
-                        .map_err(|e| e.to_string())?;
-                    Ok(ProcessResult::Imported)
-                }
-                Err(e) => {
-                    tokens::mark_message_processed(&conn, message_id)
-                        .map_err(|e2| e2.to_string())?;
-                    Err(format!("Failed to insert transaction: {}", e))
-                }
+            // Duplicates (from Gmail or a manual import) are detected by source hash
+            let saved = save_parsed_transaction(&conn, "local", &transaction, "gmail", None);
+            tokens::mark_message_processed(&conn, message_id).map_err(|e| e.to_string())?;
+            match saved {
+                Ok(Some(_)) => Ok(ProcessResult::Imported),
+                Ok(None) => Ok(ProcessResult::Duplicate),
+                Err(e) => Err(format!("Failed to insert transaction: {}", e)),
             }
         }
         parser::types::ParseResult::Failed(err) => {
@@ -307,7 +274,7 @@ async fn process_message(
 async fn get_valid_access_token(app_handle: &AppHandle) -> Result<String, String> {
     // Read tokens from DB (sync block, connection dropped before await)
     let (access_token, refresh_token, expires_at) = {
-        let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+        let conn = db::reader(app_handle).map_err(|e| e.to_string())?;
 
         let (access_token, refresh_token, expires_at, _email) = tokens::get_tokens(&conn)
             .map_err(|e| e.to_string())?
@@ -331,7 +298,7 @@ async fn get_valid_access_token(app_handle: &AppHandle) -> Result<String, String
     // Now do the async refresh using hardcoded credentials (no Connection held)
     match oauth::refresh_access_token(&refresh_token).await {
         Ok((new_access_token, new_expires_at)) => {
-            let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+            let conn = db::writer(app_handle).map_err(|e| e.to_string())?;
             tokens::update_access_token(&conn, &new_access_token, &new_expires_at)
                 .map_err(|e| e.to_string())?;
             Ok(new_access_token)
@@ -1,9 +1,12 @@
+mod backup;
 mod commands;
 mod db;
+mod export;
 mod parser;
 mod categorizer;
 mod gmail;
 mod ocr;
+mod statements;
 
 use tauri::Manager;
 use gmail::poller::{GmailPollerState, spawn_poller};
@@ -22,22 +25,26 @@ pub fn run() {
                 )?;
             }
 
-            // Initialize database on startup
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = db::initialize(&app_handle).await {
-                    log::error!("Failed to initialize database: {}", e);
-                }
-            });
+            // Open the shared database handle and run migrations before anything uses it.
+            // An encrypted database stays locked until the frontend calls `unlock_database`.
+            match db::initialize(app.handle()) {
+                Ok(()) => {}
+                Err(db::DbError::Locked) => log::info!("Database is encrypted, waiting for unlock"),
+                Err(e) => log::error!("Failed to initialize database: {}", e),
+            }
+
+            // Load the merchant knowledge base before anything categorizes
+            categorizer::defaults::load_knowledge_base(app.handle());
+            let kb = categorizer::defaults::knowledge_base_info();
+            log::info!("Merchant knowledge base v{} with {} merchants", kb.version, kb.merchants);
 
             // Set up Gmail poller
             let poller_state = GmailPollerState::new();
             spawn_poller(app.handle().clone(), &poller_state);
 
             // Auto-start polling if tokens exist
-            let app_handle = app.handle().clone();
             let auto_start = {
-                if let Ok(conn) = db::get_connection(&app_handle) {
+                if let Ok(conn) = db::reader(app.handle()) {
                     gmail::tokens::has_tokens(&conn).unwrap_or(false)
                 } else {
                     false
@@ -46,6 +53,9 @@ pub fn run() {
 
             app.manage(poller_state);
 
+            // Run scheduled backups in the background
+            backup::scheduler::spawn_scheduler(app.handle().clone());
+
             if auto_start {
                 let poller = app.state::<GmailPollerState>();
                 poller.start();
@@ -58,20 +68,93 @@ pub fn run() {
             commands::import::import_receipts,
             commands::import::confirm_import,
             commands::transactions::get_transactions,
+            commands::transactions::get_transactions_page,
+            commands::transactions::search_transactions,
+            commands::transactions::get_transaction_detail,
+            commands::transactions::recategorize_transaction_items,
+            commands::transactions::search_items,
             commands::transactions::update_transaction_category,
+            commands::transactions::preview_recategorization,
+            commands::transactions::apply_recategorization,
+            commands::transactions::update_transaction_account,
+            commands::transactions::update_transaction_direction,
             commands::transactions::delete_transaction,
+            commands::accounts::get_accounts,
+            commands::accounts::create_account,
+            commands::accounts::update_account,
+            commands::accounts::delete_account,
+            commands::accounts::get_account_spending,
             commands::categories::get_categories,
             commands::categories::create_category,
             commands::categories::update_category,
+            commands::categories::set_category_parent,
+            commands::categories::set_category_hidden,
             commands::categories::delete_category,
+            commands::categories::delete_category_with_reassign,
+            commands::categories::merge_categories,
             commands::categories::get_category_spending,
+            commands::categories::train_classifier,
             commands::budgets::get_budgets,
             commands::budgets::set_budget,
             commands::budgets::delete_budget,
             commands::dashboard::get_dashboard_stats,
+            commands::income::add_income,
+            commands::income::get_recurring_incomes,
+            commands::income::create_recurring_income,
+            commands::income::update_recurring_income,
+            commands::income::delete_recurring_income,
+            commands::income::get_cash_flow,
+            commands::merchants::get_merchants,
+            commands::merchants::get_merchant_aliases,
+            commands::merchants::update_merchant,
+            commands::merchants::add_merchant_alias,
+            commands::merchants::remove_merchant_alias,
+            commands::merchants::merge_merchants,
+            commands::merchants::split_merchant,
+            commands::export::export_transactions,
+            commands::export::export_items,
+            commands::export::export_budgets,
+            commands::export::export_dataset,
+            commands::export::import_dataset,
+            commands::export::export_rules,
+            commands::export::import_rules,
+            commands::export::export_accounting,
+            commands::export::get_account_mapping,
+            commands::export::set_account_mapping,
+            commands::statements::list_statement_presets,
+            commands::statements::import_statement_csv,
+            commands::statements::auto_match_statements,
+            commands::statements::get_reconciliation,
+            commands::statements::confirm_statement_match,
+            commands::statements::set_statement_match,
+            commands::statements::reset_statement_match,
+            commands::statements::create_transaction_from_statement,
             commands::settings::initialize_database,
+            commands::settings::get_database_status,
+            commands::settings::unlock_database,
+            commands::settings::lock_database,
+            commands::settings::enable_database_encryption,
+            commands::settings::disable_database_encryption,
             commands::settings::get_platform,
             commands::settings::set_merchant_category_rule,
+            commands::settings::create_category_rule,
+            commands::settings::get_category_rules,
+            commands::settings::update_category_rule,
+            commands::settings::delete_category_rule,
+            commands::settings::test_category_rule,
+            commands::settings::get_rule_suggestions,
+            commands::settings::accept_rule_suggestion,
+            commands::settings::reject_rule_suggestion,
+            commands::settings::get_merchant_knowledge_base_info,
+            commands::settings::get_merchant_pattern_overrides,
+            commands::settings::set_merchant_pattern_override,
+            commands::settings::delete_merchant_pattern_override,
+            commands::backup::create_backup,
+            commands::backup::inspect_backup,
+            commands::backup::restore_backup,
+            commands::backup::list_backups,
+            commands::backup::get_backup_schedule,
+            commands::backup::set_backup_schedule,
             commands::gmail::gmail_connect,
             commands::gmail::gmail_disconnect,
             commands::gmail::gmail_get_status,
@@ -84,6 +167,7 @@ pub fn run() {
             commands::gmail::gmail_toggle_sender_filter,
             commands::gmail::gmail_exchange_code,
             commands::ocr::import_receipt_from_ocr,
+            commands::ocr::confirm_ocr_import,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
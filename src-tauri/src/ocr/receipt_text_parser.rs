@@ -1,6 +1,7 @@
 use regex::Regex;
 
 use crate::parser::types::{ParsedItem, ParsedTransaction};
+use crate::parser::vendors::{extract_fees, extract_payment_method};
 
 use super::types::OcrResult;
 
@@ -26,7 +27,12 @@ pub fn parse_receipt_text(ocr: &OcrResult) -> Result<ParsedTransaction, String>
         amount,
         transaction_date: date,
         provider: "receipt_photo".to_string(),
+        is_income: false,
         items,
+        fees: extract_fees(text),
+        payment_method: extract_payment_method(text),
+        source_ref: None,
+        sender: None,
         raw_text: Some(text.clone()),
         confidence,
     };
@@ -1,7 +1,8 @@
 use super::types::ParseResult;
 use super::vendors::{
-    amazon::AmazonParser, doordash::DoorDashParser, generic::GenericParser, uber::UberParser,
-    uber_eats::UberEatsParser, venmo::VenmoParser, VendorParser,
+    amazon::AmazonParser, doordash::DoorDashParser, extract_fees, extract_payment_method, extract_text,
+    generic::GenericParser, uber::UberParser, uber_eats::UberEatsParser, venmo::VenmoParser,
+    VendorParser,
 };
 
 /// Parse HTML content and extract transaction data
@@ -22,7 +23,23 @@ pub fn parse_html(html: &str) -> ParseResult {
         if parser.can_parse(&html_lower) {
             log::info!("Detected vendor: {}", parser.vendor_id());
             match parser.parse(html) {
-                ParseResult::Success(transaction) => {
+                ParseResult::Success(mut transaction) => {
+                    // Keep the receipt's plain text so it can be full-text searched
+                    if transaction.raw_text.is_none() {
+                        let text = extract_text(html);
+                        transaction.raw_text =
+                            Some(text.split_whitespace().collect::<Vec<_>>().join(" "));
+                    }
+                    if transaction.fees.is_empty() {
+                        if let Some(ref text) = transaction.raw_text {
+                            transaction.fees = extract_fees(text);
+                        }
+                    }
+                    if transaction.payment_method.is_none() {
+                        if let Some(ref text) = transaction.raw_text {
+                            transaction.payment_method = extract_payment_method(text);
+                        }
+                    }
                     log::info!(
                         "Successfully parsed {} transaction: {} for ${:.2}",
                         parser.vendor_id(),
@@ -11,8 +11,23 @@ pub struct ParsedTransaction {
     pub transaction_date: String,
     /// Provider ID (e.g., "amazon", "doordash")
     pub provider: String,
+    /// Money received (e.g. a Venmo payment from someone) rather than spent
+    #[serde(default)]
+    pub is_income: bool,
     /// Individual items in the transaction
     pub items: Vec<ParsedItem>,
+    /// Fees, taxes, tips and discounts listed on the receipt
+    #[serde(default)]
+    pub fees: Vec<ParsedFee>,
+    /// Card or balance the purchase was paid with, if the receipt says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_method: Option<ParsedPaymentMethod>,
+    /// Where the receipt came from (e.g. the imported file name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_ref: Option<String>,
+    /// Sender address of the receipt email
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
     /// Raw text extracted (for debugging)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw_text: Option<String>,
@@ -33,6 +48,28 @@ pub struct ParsedItem {
     pub total_price: i64,
 }
 
+/// A fee, tax, tip or discount line on a receipt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedFee {
+    /// Fee kind: "tax", "tip", "delivery_fee", "service_fee", "discount" or "other"
+    pub kind: String,
+    /// Label as printed on the receipt
+    pub label: String,
+    /// Amount in cents (negative for discounts)
+    pub amount: i64,
+}
+
+/// A payment method printed on a receipt, e.g. "Visa ending in 1234"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParsedPaymentMethod {
+    /// Card network or wallet, e.g. "Visa", "Venmo"
+    pub institution: Option<String>,
+    /// Account type as stored in `accounts.account_type`
+    pub account_type: String,
+    /// Last four digits of the card, if shown
+    pub last4: Option<String>,
+}
+
 impl ParsedTransaction {
     /// Create a new parsed transaction
     pub fn new(merchant: String, amount: i64, transaction_date: String, provider: String) -> Self {
@@ -41,7 +78,12 @@ impl ParsedTransaction {
             amount,
             transaction_date,
             provider,
+            is_income: false,
             items: Vec::new(),
+            fees: Vec::new(),
+            payment_method: None,
+            source_ref: None,
+            sender: None,
             raw_text: None,
             confidence: 1.0,
         }
@@ -69,15 +111,52 @@ impl ParsedTransaction {
 
     /// Get normalized merchant name for categorization
     pub fn merchant_normalized(&self) -> String {
-        self.merchant
-            .to_lowercase()
-            .trim()
-            .chars()
-            .filter(|c| c.is_alphanumeric() || c.is_whitespace())
-            .collect::<String>()
-            .split_whitespace()
-            .collect::<Vec<_>>()
-            .join(" ")
+        normalize_merchant(&self.merchant)
+    }
+}
+
+/// Lowercase, strip punctuation and collapse whitespace
+pub fn normalize_merchant(merchant: &str) -> String {
+    merchant
+        .to_lowercase()
+        .trim()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Display name of the merchant behind a receipt's merchant text: trip and order
+/// details after " - ", notes in parentheses, store numbers and corporate suffixes
+/// are dropped ("Uber - Home to Airport" -> "Uber", "Target #1234" -> "Target").
+/// Peer payments keep the person ("Venmo - Alex (dinner)" -> "Venmo - Alex").
+pub fn canonical_merchant_name(merchant: &str) -> String {
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+
+    static PARENTHETICAL: Lazy<Regex> = Lazy::new(|| Regex::new(r"\([^)]*\)").unwrap());
+    static STORE_NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*(#|no\.?\s*)\d+\s*$").unwrap());
+    static CORPORATE_SUFFIX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)[,\s]+(inc|llc|ltd|corp|co)\.?$").unwrap());
+
+    let without_notes = PARENTHETICAL.replace_all(merchant, " ");
+    let name = match without_notes.split_once(" - ") {
+        Some((head, tail)) if normalize_merchant(head) == "venmo" => {
+            format!("{} - {}", head.trim(), tail.trim())
+        }
+        Some((head, _)) if !head.trim().is_empty() => head.to_string(),
+        _ => without_notes.to_string(),
+    };
+    let name = STORE_NUMBER.replace(name.trim(), "");
+    let name = CORPORATE_SUFFIX.replace(name.trim(), "");
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if name.is_empty() {
+        merchant.trim().to_string()
+    } else {
+        name
     }
 }
 
@@ -94,6 +173,7 @@ impl ParsedItem {
 
 /// Result of parsing an HTML receipt
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum ParseResult {
     /// Successfully parsed a transaction
     Success(ParsedTransaction),
@@ -102,3 +182,23 @@ pub enum ParseResult {
     /// Not a recognized receipt format
     NotRecognized,
 }
+
+/// Format an amount in cents as a decimal string, e.g. `-1299` -> `"-12.99"`
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let abs = cents.unsigned_abs();
+    format!("{}{}.{:02}", sign, abs / 100, abs % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_cents() {
+        assert_eq!(format_cents(1299), "12.99");
+        assert_eq!(format_cents(5), "0.05");
+        assert_eq!(format_cents(-250), "-2.50");
+        assert_eq!(format_cents(0), "0.00");
+    }
+}
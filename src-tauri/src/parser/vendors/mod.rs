@@ -5,7 +5,7 @@ pub mod uber;
 pub mod uber_eats;
 pub mod venmo;
 
-use super::types::ParseResult;
+use super::types::{ParseResult, ParsedFee, ParsedPaymentMethod};
 
 /// Trait for vendor-specific parsers
 pub trait VendorParser {
@@ -72,3 +72,157 @@ pub fn extract_text(html: &str) -> String {
         html.to_string()
     }
 }
+
+/// Extract fee, tax, tip and discount lines from receipt text.
+/// Only the first occurrence of each label is kept, since receipts often repeat
+/// the summary in both the HTML and plain-text parts.
+pub fn extract_fees(text: &str) -> Vec<ParsedFee> {
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+
+    static FEE_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)\b(delivery fee|service fee|small order fee|regulatory response fee|booking fee|bag fee|estimated tax(?:es)?|sales tax|taxes|tax|dasher tip|driver tip|courier tip|tip|promotion|promo|discount|savings)\b\s*(?:&\s*fees)?\s*[:\-]?\s*(-?\s*\$?\s*-?[\d,]+\.\d{2})",
+        )
+        .unwrap()
+    });
+
+    let mut fees: Vec<ParsedFee> = Vec::new();
+
+    for caps in FEE_RE.captures_iter(text) {
+        let label = caps[1].to_string();
+        let lower = label.to_lowercase();
+        if fees.iter().any(|f| f.label.to_lowercase() == lower) {
+            continue;
+        }
+
+        let kind = if lower.contains("tax") {
+            "tax"
+        } else if lower.contains("tip") {
+            "tip"
+        } else if lower.contains("delivery") {
+            "delivery_fee"
+        } else if lower.contains("service") || lower.contains("booking") {
+            "service_fee"
+        } else if lower.contains("promo") || lower.contains("discount") || lower.contains("savings") {
+            "discount"
+        } else {
+            "other"
+        };
+
+        if let Some(amount) = parse_amount(&caps[2]) {
+            let amount = if kind == "discount" { -amount.abs() } else { amount.abs() };
+            fees.push(ParsedFee {
+                kind: kind.to_string(),
+                label,
+                amount,
+            });
+        }
+    }
+
+    fees
+}
+
+/// Extract the payment method from receipt text, e.g. "Visa ending in 1234",
+/// "Mastercard ****5678" or "Paid from your Venmo balance"
+pub fn extract_payment_method(text: &str) -> Option<ParsedPaymentMethod> {
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+
+    static CARD_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)\b(visa|mastercard|master card|amex|american express|discover|debit card|credit card|card)\b[^\d\n]{0,20}?(?:ending in|ending with|ends in|ending|[x*•]{2,})\s*:?\s*(\d{4})\b",
+        )
+        .unwrap()
+    });
+    static BALANCE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\b(venmo|paypal|cash app) balance\b").unwrap());
+
+    if let Some(caps) = CARD_RE.captures(text) {
+        let brand = caps[1].to_lowercase();
+        let (institution, account_type) = match brand.as_str() {
+            "visa" => (Some("Visa"), "credit_card"),
+            "mastercard" | "master card" => (Some("Mastercard"), "credit_card"),
+            "amex" | "american express" => (Some("American Express"), "credit_card"),
+            "discover" => (Some("Discover"), "credit_card"),
+            "debit card" => (None, "debit_card"),
+            _ => (None, "credit_card"),
+        };
+        // "Visa Debit ending in 1234"
+        let account_type = if caps[0].to_lowercase().contains("debit") {
+            "debit_card"
+        } else {
+            account_type
+        };
+        return Some(ParsedPaymentMethod {
+            institution: institution.map(|i| i.to_string()),
+            account_type: account_type.to_string(),
+            last4: Some(caps[2].to_string()),
+        });
+    }
+
+    BALANCE_RE.captures(text).map(|caps| {
+        let institution = match caps[1].to_lowercase().as_str() {
+            "venmo" => "Venmo",
+            "paypal" => "PayPal",
+            _ => "Cash App",
+        };
+        ParsedPaymentMethod {
+            institution: Some(institution.to_string()),
+            account_type: "wallet".to_string(),
+            last4: None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_fees() {
+        let text = "Subtotal $20.00\n\
+                    Delivery Fee: $2.99\n\
+                    Service Fee $1.50\n\
+                    Estimated Taxes & Fees $1.80\n\
+                    Dasher Tip $4.00\n\
+                    Promotion -$5.00\n\
+                    Savings 1.25\n\
+                    Delivery Fee: $2.99";
+        let fees = extract_fees(text);
+        let summary: Vec<(&str, &str, i64)> =
+            fees.iter().map(|f| (f.kind.as_str(), f.label.as_str(), f.amount)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("delivery_fee", "Delivery Fee", 299),
+                ("service_fee", "Service Fee", 150),
+                ("tax", "Estimated Taxes", 180),
+                ("tip", "Dasher Tip", 400),
+                ("discount", "Promotion", -500),
+                ("discount", "Savings", -125),
+            ]
+        );
+
+        // A label without an amount next to it isn't a fee line
+        assert!(extract_fees("Tip your driver in the app. Tax included.").is_empty());
+    }
+
+    #[test]
+    fn test_extract_payment_method() {
+        let card = extract_payment_method("Total $10.80\nVISA ending in 4821\nThank you").unwrap();
+        assert_eq!(card.institution.as_deref(), Some("Visa"));
+        assert_eq!(card.account_type, "credit_card");
+        assert_eq!(card.last4.as_deref(), Some("4821"));
+
+        let debit = extract_payment_method("DEBIT CARD XXXX1234 APPROVED").unwrap();
+        assert_eq!(debit.account_type, "debit_card");
+        assert_eq!(debit.last4.as_deref(), Some("1234"));
+
+        let venmo = extract_payment_method("Paid from your Venmo balance").unwrap();
+        assert_eq!(venmo.account_type, "wallet");
+        assert_eq!(venmo.last4, None);
+
+        assert_eq!(extract_payment_method("Total $10.80"), None);
+    }
+}
@@ -32,15 +32,7 @@ impl VendorParser for VenmoParser {
         let note = extract_note(&text);
 
         match (total, date) {
-            (Some(mut amount), Some(transaction_date)) => {
-                // If payment received, make it negative (income)
-                // For expense tracking, we typically only care about outgoing payments
-                if !is_payment_out {
-                    // Mark received payments as negative for now
-                    // The UI can decide how to display this
-                    amount = -amount;
-                }
-
+            (Some(amount), Some(transaction_date)) => {
                 let merchant = if let Some(person) = counterparty {
                     if let Some(ref note_text) = note {
                         format!("Venmo - {} ({})", person, note_text)
@@ -54,10 +46,8 @@ impl VendorParser for VenmoParser {
                 let mut transaction =
                     ParsedTransaction::new(merchant, amount.abs(), transaction_date, "venmo".to_string());
 
-                // Store if it's an outgoing payment
-                if !is_payment_out {
-                    transaction.confidence = 0.8; // Lower confidence for received payments
-                }
+                // Payments received are income, not spending
+                transaction.is_income = !is_payment_out;
 
                 ParseResult::Success(transaction)
             }
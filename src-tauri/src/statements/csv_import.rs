@@ -0,0 +1,204 @@
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::presets::{detect_preset, ColumnMapping};
+use crate::db::{DbError, DbResult};
+
+/// One parsed statement row
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatementRow {
+    /// YYYY-MM-DD
+    pub posted_date: String,
+    pub description: String,
+    /// Cents, positive for spending
+    pub amount: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedStatement {
+    pub rows: Vec<StatementRow>,
+    /// Per-row problems, e.g. "Row 7: invalid date '13/45/2024'"
+    pub errors: Vec<String>,
+    /// ID of the preset used when none was given
+    pub detected_preset: Option<String>,
+}
+
+/// Parse a statement CSV. With no `mapping` the columns are detected from the header row.
+pub fn parse_statement(content: &str, mapping: Option<&ColumnMapping>) -> DbResult<ParsedStatement> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| DbError::InvalidInput(format!("Unreadable CSV header: {}", e)))?
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+
+    let mut parsed = ParsedStatement::default();
+    let mapping = match mapping {
+        Some(mapping) => mapping.clone(),
+        None => {
+            let preset = detect_preset(&headers).ok_or_else(|| {
+                DbError::InvalidInput(
+                    "Unrecognized statement format; choose a column mapping".to_string(),
+                )
+            })?;
+            parsed.detected_preset = Some(preset.id);
+            preset.mapping
+        }
+    };
+
+    let column = |name: &str| -> DbResult<usize> {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or_else(|| DbError::InvalidInput(format!("Column \"{}\" not found", name)))
+    };
+    let date_col = column(&mapping.date_column)?;
+    let description_col = column(&mapping.description_column)?;
+    let amount_col = mapping.amount_column.as_deref().map(column).transpose()?;
+    let debit_col = mapping.debit_column.as_deref().map(column).transpose()?;
+    let credit_col = mapping.credit_column.as_deref().map(column).transpose()?;
+    if amount_col.is_none() && debit_col.is_none() && credit_col.is_none() {
+        return Err(DbError::InvalidInput(
+            "Mapping needs an amount column or debit/credit columns".to_string(),
+        ));
+    }
+
+    for (index, record) in reader.records().enumerate() {
+        // +2: 1-based, and the header is row 1
+        let row_number = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                parsed.errors.push(format!("Row {}: {}", row_number, e));
+                continue;
+            }
+        };
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+
+        let field = |col: usize| record.get(col).unwrap_or("");
+
+        let date = match parse_date(field(date_col), &mapping.date_format) {
+            Some(date) => date,
+            None => {
+                parsed.errors.push(format!(
+                    "Row {}: invalid date '{}'",
+                    row_number,
+                    field(date_col)
+                ));
+                continue;
+            }
+        };
+
+        let amount = if let Some(col) = amount_col {
+            parse_amount(field(col)).map(|a| if mapping.spending_is_negative { -a } else { a })
+        } else {
+            // Debit/credit columns hold unsigned values; only one is filled per row
+            let debit = debit_col.map(field).filter(|v| !v.is_empty());
+            let credit = credit_col.map(field).filter(|v| !v.is_empty());
+            match (debit, credit) {
+                (Some(debit), _) => parse_amount(debit).map(|a| a.abs()),
+                (None, Some(credit)) => parse_amount(credit).map(|a| -a.abs()),
+                (None, None) => None,
+            }
+        };
+        let Some(amount) = amount else {
+            parsed.errors.push(format!("Row {}: missing or invalid amount", row_number));
+            continue;
+        };
+
+        parsed.rows.push(StatementRow {
+            posted_date: date.format("%Y-%m-%d").to_string(),
+            description: field(description_col).split_whitespace().collect::<Vec<_>>().join(" "),
+            amount,
+        });
+    }
+
+    Ok(parsed)
+}
+
+fn parse_date(value: &str, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, format)
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%d", "%m/%d/%Y", "%m/%d/%y", "%d/%m/%Y"]
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(value, f).ok())
+        })
+}
+
+/// Parse "$1,234.56", "-12.00", "(12.00)" or "12.5" into cents
+pub fn parse_amount(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let negative = value.starts_with('-') || (value.starts_with('(') && value.ends_with(')'));
+    let digits: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    if digits.is_empty() {
+        return None;
+    }
+
+    let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let fraction: i64 = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<i64>().ok()? * 10,
+        _ => fraction[..2].parse().ok()?,
+    };
+
+    let cents = whole * 100 + fraction;
+    Some(if negative { -cents } else { cents })
+}
+
+/// Card processors prefix merchant names ("SQ *BLUE BOTTLE", "TST* CHIPOTLE") and
+/// append store numbers and locations; strip them so descriptions compare to receipts
+static PROCESSOR_PREFIX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(sq|tst|pp|paypal|sp|dd|ic|py|pos|ach|debit card purchase|purchase)\s*\*\s*")
+        .unwrap()
+});
+static STORE_NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"#?\d{3,}").unwrap());
+
+pub fn normalize_description(description: &str) -> String {
+    let without_prefix = PROCESSOR_PREFIX.replace(description, "");
+    let without_numbers = STORE_NUMBER.replace_all(&without_prefix, " ");
+    crate::parser::types::normalize_merchant(&without_numbers.replace('*', " "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("$1,234.56"), Some(123456));
+        assert_eq!(parse_amount("-12.00"), Some(-1200));
+        assert_eq!(parse_amount("(7.5)"), Some(-750));
+        assert_eq!(parse_amount("3"), Some(300));
+        assert_eq!(parse_amount(""), None);
+    }
+
+    #[test]
+    fn test_parse_chase_statement() {
+        let csv = "Transaction Date,Post Date,Description,Category,Type,Amount,Memo\n\
+                   01/14/2024,01/15/2024,SQ *BLUE BOTTLE COFFEE,Food & Drink,Sale,-5.75,\n\
+                   01/16/2024,01/16/2024,Payment Thank You-Mobile,,Payment,250.00,\n\
+                   bad,01/16/2024,Broken,,Sale,-1.00,\n";
+
+        let parsed = parse_statement(csv, None).unwrap();
+        assert_eq!(parsed.detected_preset.as_deref(), Some("chase_card"));
+        assert_eq!(parsed.rows.len(), 2);
+        assert_eq!(parsed.rows[0].posted_date, "2024-01-14");
+        assert_eq!(parsed.rows[0].amount, 575);
+        assert_eq!(parsed.rows[1].amount, -25000);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(normalize_description(&parsed.rows[0].description), "blue bottle coffee");
+    }
+}
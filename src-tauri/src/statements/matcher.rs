@@ -0,0 +1,371 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::categorizer::{categorize_transaction, CategorizationInput, CategorySource};
+use crate::db::queries::{self, NewTransaction, TransactionWithCategory};
+use crate::db::statements::{self, match_status, StatementTransaction};
+use crate::db::{merchants, DbError, DbResult};
+
+/// Minimum score for `auto_match` to link a statement row to a receipt
+pub const MATCH_THRESHOLD: f64 = 0.6;
+
+/// A charge usually posts on the receipt date or a few days after it
+const DAYS_BEFORE_RECEIPT: i64 = 1;
+const DAYS_AFTER_RECEIPT: i64 = 5;
+
+/// Tips added after authorization (rideshare, delivery) change the posted amount
+const AMOUNT_TOLERANCE: f64 = 0.25;
+
+/// Below this, the statement description and the receipt's merchant are taken to be
+/// different businesses, however well the amount and date agree
+const MIN_MERCHANT_SIMILARITY: f64 = 0.3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementMatch {
+    pub statement: StatementTransaction,
+    pub transaction: TransactionWithCategory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reconciliation {
+    /// Statement rows linked to a receipt (auto-matched or confirmed)
+    pub matched: Vec<StatementMatch>,
+    /// Statement rows with no receipt, e.g. in-person purchases
+    pub unmatched_statement: Vec<StatementTransaction>,
+    /// Receipts in the statement period that no statement row accounts for
+    pub unmatched_receipts: Vec<TransactionWithCategory>,
+}
+
+/// Score how likely `receipt` is the purchase behind `statement`, from 0 to 1.
+/// Returns None if the amount, date or merchant rule it out.
+pub fn score_match(statement: &StatementTransaction, receipt: &TransactionWithCategory) -> Option<f64> {
+    if statement.amount == 0 || statement.amount.signum() != receipt.amount.signum() {
+        return None;
+    }
+
+    let amount_score = if statement.amount == receipt.amount {
+        1.0
+    } else {
+        let relative = (statement.amount - receipt.amount).abs() as f64 / receipt.amount.abs() as f64;
+        if relative > AMOUNT_TOLERANCE {
+            return None;
+        }
+        // Close amounts score below exact ones
+        0.7 - relative
+    };
+
+    let posted = NaiveDate::parse_from_str(&statement.posted_date, "%Y-%m-%d").ok()?;
+    let purchased = NaiveDate::parse_from_str(&receipt.transaction_date, "%Y-%m-%d").ok()?;
+    let lag = (posted - purchased).num_days();
+    if !(-DAYS_BEFORE_RECEIPT..=DAYS_AFTER_RECEIPT).contains(&lag) {
+        return None;
+    }
+    let date_score = 1.0 - lag.abs() as f64 / (DAYS_AFTER_RECEIPT + 1) as f64;
+
+    let merchant_score = merchant_similarity(&statement.description_normalized, &receipt.merchant_normalized)
+        .max(merchant_similarity(&statement.description_normalized, &receipt.provider.to_lowercase()));
+    if merchant_score < MIN_MERCHANT_SIMILARITY {
+        return None;
+    }
+
+    Some(0.5 * amount_score + 0.2 * date_score + 0.3 * merchant_score)
+}
+
+/// Similarity of two normalized merchant strings, from 0 to 1. A whole word of one
+/// appearing in the other ("uber" in "uber trip help uber com") counts as a match.
+pub fn merchant_similarity(a: &str, b: &str) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let a_words: HashSet<&str> = a.split_whitespace().filter(|w| w.len() >= 3).collect();
+    let b_words: HashSet<&str> = b.split_whitespace().filter(|w| w.len() >= 3).collect();
+    if a_words.iter().any(|w| b_words.contains(w)) {
+        return 1.0;
+    }
+
+    // Dice coefficient over character bigrams
+    let bigrams = |s: &str| -> Vec<(char, char)> {
+        let chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    let a_bigrams = bigrams(a);
+    let mut b_bigrams = bigrams(b);
+    if a_bigrams.is_empty() || b_bigrams.is_empty() {
+        return 0.0;
+    }
+
+    let total = a_bigrams.len() + b_bigrams.len();
+    let mut shared = 0;
+    for bigram in &a_bigrams {
+        if let Some(pos) = b_bigrams.iter().position(|b| b == bigram) {
+            b_bigrams.swap_remove(pos);
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / total as f64
+}
+
+/// Match every unmatched statement row to the best-scoring receipt not already
+/// matched, highest scores first. Returns the number of new matches.
+pub fn auto_match(conn: &Connection, user_id: &str) -> DbResult<usize> {
+    let pending = statements::get_unmatched_statement_transactions(conn, user_id)?;
+    let (Some(first), Some(last)) = (pending.first(), pending.last()) else {
+        return Ok(0);
+    };
+
+    let (start, end) = receipt_window(&first.posted_date, &last.posted_date);
+    let receipts = statements::get_unreconciled_transactions(conn, user_id, &start, &end)?;
+
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (si, statement) in pending.iter().enumerate() {
+        for (ri, receipt) in receipts.iter().enumerate() {
+            if let Some(score) = score_match(statement, receipt).filter(|s| *s >= MATCH_THRESHOLD) {
+                candidates.push((score, si, ri));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let tx = conn.unchecked_transaction()?;
+    let mut used_statements = HashSet::new();
+    let mut used_receipts = HashSet::new();
+    for (score, si, ri) in candidates {
+        if used_statements.contains(&si) || used_receipts.contains(&ri) {
+            continue;
+        }
+        used_statements.insert(si);
+        used_receipts.insert(ri);
+        statements::set_statement_match(
+            &tx,
+            &pending[si].id,
+            Some(&receipts[ri].id),
+            match_status::AUTO,
+            Some((score * 1000.0).round() / 1000.0),
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(used_statements.len())
+}
+
+/// Matched, unmatched-statement and unmatched-receipt lists for statement rows posted
+/// in the range. Without dates the range covers every imported statement row.
+pub fn get_reconciliation(
+    conn: &Connection,
+    user_id: &str,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> DbResult<Reconciliation> {
+    let rows = statements::get_statement_transactions(conn, user_id, start_date, end_date)?;
+
+    let mut matched = Vec::new();
+    let mut unmatched_statement = Vec::new();
+    for statement in rows.iter().cloned() {
+        let transaction = match &statement.matched_transaction_id {
+            Some(id) => queries::get_transaction_detail(conn, user_id, id)?.map(|d| d.transaction),
+            None => None,
+        };
+        match transaction {
+            Some(transaction) => matched.push(StatementMatch { statement, transaction }),
+            None => unmatched_statement.push(statement),
+        }
+    }
+
+    // Receipts are compared over the statement period, widened by the posting lag
+    let earliest = start_date.map(|d| d.to_string()).or_else(|| rows.iter().map(|s| s.posted_date.clone()).min());
+    let latest = end_date.map(|d| d.to_string()).or_else(|| rows.iter().map(|s| s.posted_date.clone()).max());
+    let unmatched_receipts = match (earliest, latest) {
+        (Some(earliest), Some(latest)) => {
+            let (start, end) = receipt_window(&earliest, &latest);
+            statements::get_unreconciled_transactions(conn, user_id, &start, &end)?
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(Reconciliation {
+        matched,
+        unmatched_statement,
+        unmatched_receipts,
+    })
+}
+
+/// Record a statement row with no receipt (cash-like card purchases, subscriptions
+/// billed without email) as a transaction and link the two. Without `category_id`
+/// the row is categorized like an imported receipt.
+pub fn create_transaction_from_statement(
+    conn: &Connection,
+    user_id: &str,
+    statement_id: &str,
+    category_id: Option<&str>,
+) -> DbResult<String> {
+    let statement = statements::get_statement_transaction(conn, statement_id)?
+        .ok_or(DbError::NotFound)?;
+    if statement.matched_transaction_id.is_some() {
+        return Err(DbError::InvalidInput(
+            "Statement transaction is already matched to a receipt".to_string(),
+        ));
+    }
+
+    let (category_id, category_source, category_reason, category_rule_id) = match category_id {
+        Some(id) => (
+            Some(id.to_string()),
+            CategorySource::Manual,
+            "Chosen during reconciliation".to_string(),
+            None,
+        ),
+        None => {
+            let input = CategorizationInput {
+                merchant_normalized: &statement.description_normalized,
+                provider: "statement",
+                amount: statement.amount,
+                transaction_date: &statement.posted_date,
+                ..Default::default()
+            };
+            let result = categorize_transaction(conn, user_id, &input)?;
+            (result.category_id, result.source, result.reason, result.rule_id)
+        }
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    let merchant_id =
+        merchants::resolve_merchant(&tx, user_id, &statement.description, &statement.description_normalized)?;
+    let transaction_id = queries::insert_transaction(
+        &tx,
+        user_id,
+        &NewTransaction {
+            category_id: category_id.as_deref(),
+            account_id: None,
+            merchant_id: Some(&merchant_id),
+            category_source: Some(category_source.as_str()),
+            category_reason: Some(&category_reason),
+            category_rule_id: category_rule_id.as_deref(),
+            merchant: &statement.description,
+            merchant_normalized: &statement.description_normalized,
+            amount: statement.amount,
+            transaction_date: &statement.posted_date,
+            provider: "statement",
+            source_hash: &format!("statement:{}", statement.id),
+            source_type: "statement",
+            source_ref: Some(&statement.id),
+            receipt_text: None,
+            parse_confidence: None,
+            sender: None,
+            is_income: false,
+        },
+    )?;
+    statements::set_statement_match(&tx, &statement.id, Some(&transaction_id), match_status::CONFIRMED, None)?;
+    tx.commit()?;
+
+    Ok(transaction_id)
+}
+
+/// Receipt dates that could have posted between `first_posted` and `last_posted`
+fn receipt_window(first_posted: &str, last_posted: &str) -> (String, String) {
+    let shift = |date: &str, days: i64| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|d| (d + chrono::Duration::days(days)).format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|_| date.to_string())
+    };
+    (shift(first_posted, -DAYS_AFTER_RECEIPT), shift(last_posted, DAYS_BEFORE_RECEIPT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+    use crate::db::statements::NewStatementTransaction;
+
+    fn add_statement_row(conn: &Connection, date: &str, description: &str, amount: i64) -> String {
+        statements::insert_statement_transaction(
+            conn,
+            "local",
+            &NewStatementTransaction {
+                import_id: "import1",
+                account_label: None,
+                posted_date: date,
+                description,
+                description_normalized: &crate::statements::csv_import::normalize_description(description),
+                amount,
+                occurrence: 0,
+            },
+        )
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_auto_match_and_reconciliation() {
+        let conn = Connection::open_in_memory().unwrap();
+        schema::run_migrations(&conn).unwrap();
+
+        let receipt = |merchant: &str, amount: i64, date: &str, provider: &str| {
+            queries::insert_transaction(
+                &conn,
+                "local",
+                &NewTransaction {
+                    merchant,
+                    merchant_normalized: &merchant.to_lowercase(),
+                    amount,
+                    transaction_date: date,
+                    provider,
+                    source_hash: &format!("{}{}{}", merchant, amount, date),
+                    source_type: "gmail",
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let uber = receipt("Uber", 2350, "2024-02-01", "uber");
+        let _doordash = receipt("Chipotle", 1875, "2024-02-03", "doordash");
+        let amazon = receipt("Amazon", 4999, "2024-02-04", "amazon");
+        // Same amount and day as the cafe charge, but a different business
+        let _pharmacy = receipt("Walgreens", 650, "2024-02-03", "generic");
+
+        // Tip added after the receipt, posted the next day
+        let uber_row = add_statement_row(&conn, "2024-02-02", "UBER *TRIP HELP.UBER.COM", 2850);
+        let amazon_row = add_statement_row(&conn, "2024-02-06", "AMAZON MKTPL*2K3", 4999);
+        let cafe_row = add_statement_row(&conn, "2024-02-03", "SQ *CORNER CAFE", 650);
+
+        assert_eq!(auto_match(&conn, "local").unwrap(), 2);
+
+        let reconciliation = get_reconciliation(&conn, "local", None, None).unwrap();
+        let pairs: Vec<(&str, &str)> = reconciliation
+            .matched
+            .iter()
+            .map(|m| (m.statement.id.as_str(), m.transaction.id.as_str()))
+            .collect();
+        assert!(pairs.contains(&(uber_row.as_str(), uber.as_str())));
+        assert!(pairs.contains(&(amazon_row.as_str(), amazon.as_str())));
+        assert_eq!(reconciliation.unmatched_statement.len(), 1);
+        assert_eq!(reconciliation.unmatched_statement[0].id, cafe_row);
+        let unmatched: Vec<&str> = reconciliation.unmatched_receipts.iter().map(|r| r.merchant.as_str()).collect();
+        assert_eq!(unmatched.len(), 2);
+        assert!(unmatched.contains(&"Chipotle") && unmatched.contains(&"Walgreens"));
+
+        // Re-importing a row under another account label doesn't duplicate it
+        let again = statements::insert_statement_transaction(
+            &conn,
+            "local",
+            &NewStatementTransaction {
+                import_id: "import2",
+                account_label: Some("Visa"),
+                posted_date: "2024-02-03",
+                description: "SQ *CORNER CAFE",
+                description_normalized: &crate::statements::csv_import::normalize_description("SQ *CORNER CAFE"),
+                amount: 650,
+                occurrence: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(again, None);
+
+        // Deleting a matched receipt leaves the statement row unmatched
+        queries::delete_transaction(&conn, &amazon).unwrap();
+        let row = statements::get_statement_transaction(&conn, &amazon_row).unwrap().unwrap();
+        assert_eq!(row.match_status, match_status::UNMATCHED);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod presets;
+pub mod csv_import;
+pub mod matcher;
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Which CSV columns hold the date, description and amount of each statement row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub date_column: String,
+    pub description_column: String,
+    /// Single signed amount column. Banks that split money in and out use
+    /// `debit_column`/`credit_column` instead.
+    pub amount_column: Option<String>,
+    pub debit_column: Option<String>,
+    pub credit_column: Option<String>,
+    /// chrono format string, e.g. "%m/%d/%Y"
+    pub date_format: String,
+    /// Whether purchases appear as negative numbers in `amount_column`
+    pub spending_is_negative: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementPreset {
+    pub id: String,
+    pub name: String,
+    pub mapping: ColumnMapping,
+}
+
+fn preset(
+    id: &str,
+    name: &str,
+    date: &str,
+    description: &str,
+    amount: &str,
+    date_format: &str,
+    spending_is_negative: bool,
+) -> StatementPreset {
+    StatementPreset {
+        id: id.to_string(),
+        name: name.to_string(),
+        mapping: ColumnMapping {
+            date_column: date.to_string(),
+            description_column: description.to_string(),
+            amount_column: Some(amount.to_string()),
+            debit_column: None,
+            credit_column: None,
+            date_format: date_format.to_string(),
+            spending_is_negative,
+        },
+    }
+}
+
+/// Built-in column mappings for common bank exports, in detection order (most
+/// specific header sets first)
+pub fn statement_presets() -> Vec<StatementPreset> {
+    vec![
+        preset("apple_card", "Apple Card", "Transaction Date", "Merchant", "Amount (USD)", "%m/%d/%Y", false),
+        StatementPreset {
+            id: "capital_one".to_string(),
+            name: "Capital One".to_string(),
+            mapping: ColumnMapping {
+                date_column: "Transaction Date".to_string(),
+                description_column: "Description".to_string(),
+                amount_column: None,
+                debit_column: Some("Debit".to_string()),
+                credit_column: Some("Credit".to_string()),
+                date_format: "%Y-%m-%d".to_string(),
+                spending_is_negative: false,
+            },
+        },
+        preset("chase_card", "Chase credit card", "Transaction Date", "Description", "Amount", "%m/%d/%Y", true),
+        preset("chase_checking", "Chase checking", "Posting Date", "Description", "Amount", "%m/%d/%Y", true),
+        preset("discover", "Discover", "Trans. Date", "Description", "Amount", "%m/%d/%Y", false),
+        preset("bank_of_america", "Bank of America", "Posted Date", "Payee", "Amount", "%m/%d/%Y", true),
+        preset("amex", "American Express", "Date", "Description", "Amount", "%m/%d/%Y", false),
+    ]
+}
+
+pub fn get_preset(id: &str) -> Option<StatementPreset> {
+    statement_presets().into_iter().find(|p| p.id == id)
+}
+
+/// Pick the first preset whose columns all appear in `headers`
+pub fn detect_preset(headers: &[String]) -> Option<StatementPreset> {
+    let has = |column: &Option<String>| match column {
+        Some(column) => headers.iter().any(|h| h.trim().eq_ignore_ascii_case(column)),
+        None => true,
+    };
+
+    statement_presets().into_iter().find(|p| {
+        let m = &p.mapping;
+        has(&Some(m.date_column.clone()))
+            && has(&Some(m.description_column.clone()))
+            && has(&m.amount_column)
+            && has(&m.debit_column)
+            && has(&m.credit_column)
+    })
+}
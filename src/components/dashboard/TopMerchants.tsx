@@ -1,12 +1,7 @@
 import { Card, CardContent, CardHeader, CardTitle } from '../ui/card';
 import { Skeleton } from '../ui/skeleton';
 import { formatCurrency } from '../../lib/format';
-
-interface MerchantTotal {
-  merchant: string;
-  total: number;
-  count: number;
-}
+import type { MerchantTotal } from '../../types';
 
 interface TopMerchantsProps {
   merchants: MerchantTotal[];
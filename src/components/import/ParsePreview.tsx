@@ -4,7 +4,7 @@ import { Button } from '../ui/button';
 import { Badge } from '../ui/badge';
 import { CategoryPicker } from '../transactions/CategoryPicker';
 import { formatCurrency, formatDate } from '../../lib/format';
-import type { ParsedTransaction, Category } from '../../types';
+import type { ParsedTransaction, Category, CategoryPrediction } from '../../types';
 import { cn } from '../../lib/utils';
 
 interface ParsePreviewProps {
@@ -13,6 +13,7 @@ interface ParsePreviewProps {
   errors: string[];
   categories: Category[];
   categoryAssignments: Record<number, string>;
+  predictions?: CategoryPrediction[]; // by index; used where nothing is assigned
   onCategoryChange: (index: number, categoryId: string) => void;
   onConfirm: () => void;
   onCancel: () => void;
@@ -25,6 +26,7 @@ export function ParsePreview({
   errors,
   categories,
   categoryAssignments,
+  predictions,
   onCategoryChange,
   onConfirm,
   onCancel,
@@ -72,81 +74,109 @@ export function ParsePreview({
       {/* Transaction List */}
       {transactions.length > 0 && (
         <div className="space-y-2">
-          {transactions.map((transaction, index) => (
-            <div
-              key={index}
-              className="border rounded-lg overflow-hidden"
-            >
+          {transactions.map((transaction, index) => {
+            const prediction = predictions?.[index];
+            const assignedId = categoryAssignments[index];
+            const categoryId = assignedId ?? prediction?.category_id ?? undefined;
+            const categoryName = categories.find((c) => c.id === categoryId)?.name;
+
+            return (
               <div
-                className="flex items-center justify-between p-4 cursor-pointer hover:bg-accent/50"
-                onClick={() => toggleExpanded(index)}
+                key={index}
+                className="border rounded-lg overflow-hidden"
               >
-                <div className="flex items-center gap-4 flex-1 min-w-0">
-                  <div className="flex-1 min-w-0">
-                    <p className="font-medium truncate">{transaction.merchant}</p>
-                    <p className="text-sm text-muted-foreground">
-                      {formatDate(transaction.transaction_date)} via {transaction.provider}
-                    </p>
+                <div
+                  className="flex items-center justify-between p-4 cursor-pointer hover:bg-accent/50"
+                  onClick={() => toggleExpanded(index)}
+                >
+                  <div className="flex items-center gap-4 flex-1 min-w-0">
+                    <div className="flex-1 min-w-0">
+                      <p className="font-medium truncate">{transaction.merchant}</p>
+                      <p className="text-sm text-muted-foreground">
+                        {formatDate(transaction.transaction_date)} via {transaction.provider}
+                        {categoryName && <> · {categoryName}</>}
+                      </p>
+                    </div>
+                    <div className="text-right">
+                      <p className="font-medium">{formatCurrency(transaction.amount)}</p>
+                      <p className={cn(
+                        'text-xs',
+                        transaction.confidence >= 0.8 ? 'text-success' :
+                        transaction.confidence >= 0.5 ? 'text-warning' :
+                        'text-destructive'
+                      )}>
+                        {Math.round(transaction.confidence * 100)}% confidence
+                      </p>
+                    </div>
                   </div>
-                  <div className="text-right">
-                    <p className="font-medium">{formatCurrency(transaction.amount)}</p>
-                    <p className={cn(
-                      'text-xs',
-                      transaction.confidence >= 0.8 ? 'text-success' :
-                      transaction.confidence >= 0.5 ? 'text-warning' :
-                      'text-destructive'
-                    )}>
-                      {Math.round(transaction.confidence * 100)}% confidence
-                    </p>
+                  <div className="ml-4">
+                    {expandedIndex === index ? (
+                      <ChevronUp className="h-4 w-4 text-muted-foreground" />
+                    ) : (
+                      <ChevronDown className="h-4 w-4 text-muted-foreground" />
+                    )}
                   </div>
                 </div>
-                <div className="ml-4">
-                  {expandedIndex === index ? (
-                    <ChevronUp className="h-4 w-4 text-muted-foreground" />
-                  ) : (
-                    <ChevronDown className="h-4 w-4 text-muted-foreground" />
-                  )}
-                </div>
-              </div>
-
-              {/* Expanded Details */}
-              {expandedIndex === index && (
-                <div className="border-t p-4 bg-muted/30 space-y-3">
-                  {/* Category Selection */}
-                  <div>
-                    <label className="text-sm font-medium mb-1 block">Category</label>
-                    <CategoryPicker
-                      categories={categories}
-                      value={categoryAssignments[index]}
-                      onChange={(categoryId) => onCategoryChange(index, categoryId)}
-                    />
-                  </div>
 
-                  {/* Items */}
-                  {transaction.items.length > 0 && (
+                {/* Expanded Details */}
+                {expandedIndex === index && (
+                  <div className="border-t p-4 bg-muted/30 space-y-3">
+                    {/* Category Selection */}
                     <div>
-                      <label className="text-sm font-medium mb-1 block">Items</label>
-                      <div className="space-y-1">
-                        {transaction.items.map((item, itemIndex) => (
-                          <div
-                            key={itemIndex}
-                            className="flex justify-between text-sm py-1"
-                          >
-                            <span>
-                              {item.quantity}x {item.name}
-                            </span>
-                            <span className="text-muted-foreground">
-                              {formatCurrency(item.total_price)}
-                            </span>
-                          </div>
-                        ))}
-                      </div>
+                      <label className="text-sm font-medium mb-1 block">Category</label>
+                      <CategoryPicker
+                        categories={categories}
+                        value={categoryId}
+                        onChange={(categoryId) => onCategoryChange(index, categoryId)}
+                      />
+                      {prediction && !assignedId && (
+                        <p className="text-xs text-muted-foreground mt-1">{prediction.reason}</p>
+                      )}
+                      {prediction && prediction.alternatives.length > 0 && (
+                        <div className="flex flex-wrap items-center gap-1 mt-2">
+                          <span className="text-xs text-muted-foreground">Or:</span>
+                          {prediction.alternatives.map((alternative) => (
+                            <Button
+                              key={alternative.category_id}
+                              variant="outline"
+                              size="sm"
+                              className="h-6 px-2 text-xs"
+                              title={alternative.reason}
+                              onClick={() => onCategoryChange(index, alternative.category_id)}
+                            >
+                              {alternative.category_name ?? 'Unknown'}
+                            </Button>
+                          ))}
+                        </div>
+                      )}
                     </div>
-                  )}
-                </div>
-              )}
-            </div>
-          ))}
+
+                    {/* Items */}
+                    {transaction.items.length > 0 && (
+                      <div>
+                        <label className="text-sm font-medium mb-1 block">Items</label>
+                        <div className="space-y-1">
+                          {transaction.items.map((item, itemIndex) => (
+                            <div
+                              key={itemIndex}
+                              className="flex justify-between text-sm py-1"
+                            >
+                              <span>
+                                {item.quantity}x {item.name}
+                              </span>
+                              <span className="text-muted-foreground">
+                                {formatCurrency(item.total_price)}
+                              </span>
+                            </div>
+                          ))}
+                        </div>
+                      </div>
+                    )}
+                  </div>
+                )}
+              </div>
+            );
+          })}
         </div>
       )}
 
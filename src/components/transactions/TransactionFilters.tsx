@@ -42,7 +42,7 @@ export function TransactionFilters({
         {/* Category Filter */}
         <div className="w-48">
           <CategoryPicker
-            categories={[{ id: '', user_id: '', name: 'All Categories', icon: '', color: '#6b7280', is_system: true, created_at: '', updated_at: '' }, ...categories]}
+            categories={[{ id: '', user_id: '', name: 'All Categories', icon: '', color: '#6b7280', parent_id: null, is_system: true, system_key: null, is_hidden: false, created_at: '', updated_at: '' }, ...categories]}
             value={filters.categoryId || ''}
             onChange={(categoryId) => onFiltersChange({ categoryId: categoryId || null })}
             placeholder="All Categories"
@@ -1,22 +1,28 @@
 import { useState, useCallback } from 'react';
 import * as tauri from '../lib/tauri';
-import type { ParsedTransaction } from '../types';
+import type { ImportResult, OcrImportPreview, ParsedTransaction } from '../types';
 
 interface CameraState {
   isCapturing: boolean;
   isProcessing: boolean;
+  isSaving: boolean;
   error: string | null;
   ocrText: string | null;
   transaction: ParsedTransaction | null;
+  preview: OcrImportPreview | null;
+  importResult: ImportResult | null;
 }
 
 export function useCamera() {
   const [state, setState] = useState<CameraState>({
     isCapturing: false,
     isProcessing: false,
+    isSaving: false,
     error: null,
     ocrText: null,
     transaction: null,
+    preview: null,
+    importResult: null,
   });
 
   const captureReceipt = useCallback(async (source: 'camera' | 'library') => {
@@ -41,13 +47,14 @@ export function useCamera() {
         ocrText: result.text,
       }));
 
-      // Parse the OCR text into a transaction
-      const transaction = await tauri.importReceiptFromOcr(result.text, result.confidence);
+      // Parse the OCR text into a transaction with a suggested category
+      const preview = await tauri.importReceiptFromOcr(result.text, result.confidence);
 
       setState((prev) => ({
         ...prev,
         isProcessing: false,
-        transaction,
+        transaction: preview.transaction,
+        preview,
       }));
     } catch (err) {
       const message = err instanceof Error ? err.message : String(err);
@@ -65,19 +72,47 @@ export function useCamera() {
     }
   }, []);
 
+  // Save the previewed receipt; without a category the suggested one is kept
+  const confirmReceipt = useCallback(
+    async (categoryId?: string) => {
+      if (!state.transaction) return;
+      setState((prev) => ({ ...prev, isSaving: true, error: null }));
+
+      try {
+        const importResult = await tauri.confirmOcrImport(
+          state.transaction,
+          categoryId,
+          state.preview?.prediction
+        );
+        setState((prev) => ({ ...prev, isSaving: false, importResult }));
+      } catch (err) {
+        setState((prev) => ({
+          ...prev,
+          isSaving: false,
+          error: err instanceof Error ? err.message : String(err),
+        }));
+      }
+    },
+    [state.transaction, state.preview]
+  );
+
   const reset = useCallback(() => {
     setState({
       isCapturing: false,
       isProcessing: false,
+      isSaving: false,
       error: null,
       ocrText: null,
       transaction: null,
+      preview: null,
+      importResult: null,
     });
   }, []);
 
   return {
     ...state,
     captureReceipt,
+    confirmReceipt,
     reset,
   };
 }
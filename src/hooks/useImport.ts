@@ -1,12 +1,13 @@
 import { useState, useCallback } from 'react';
 import * as tauri from '../lib/tauri';
-import type { ParsedTransaction } from '../types';
+import type { CategoryPrediction, ParsedTransaction } from '../types';
 
 interface ImportState {
   step: 'select' | 'preview' | 'done';
   isLoading: boolean;
   error: string | null;
   transactions: ParsedTransaction[];
+  predictions: CategoryPrediction[];
   duplicates: number;
   errors: string[];
   importResult: { imported: number; skipped: number; errors: string[] } | null;
@@ -19,6 +20,7 @@ export function useImport() {
     isLoading: false,
     error: null,
     transactions: [],
+    predictions: [],
     duplicates: 0,
     errors: [],
     importResult: null,
@@ -35,13 +37,17 @@ export function useImport() {
       );
 
       // Parse via Tauri
-      const result = await tauri.importReceipts(htmlContents);
+      const result = await tauri.importReceipts(
+        htmlContents,
+        files.map((file) => file.name)
+      );
 
       setState((prev) => ({
         ...prev,
         step: 'preview',
         isLoading: false,
         transactions: result.transactions,
+        predictions: result.predictions,
         duplicates: result.duplicates,
         errors: result.errors,
         categoryAssignments: {},
@@ -71,7 +77,8 @@ export function useImport() {
     try {
       const result = await tauri.confirmImport(
         state.transactions,
-        state.categoryAssignments
+        state.categoryAssignments,
+        state.predictions
       );
 
       setState((prev) => ({
@@ -87,7 +94,7 @@ export function useImport() {
         error: err instanceof Error ? err.message : String(err),
       }));
     }
-  }, [state.transactions, state.categoryAssignments]);
+  }, [state.transactions, state.categoryAssignments, state.predictions]);
 
   const reset = useCallback(() => {
     setState({
@@ -95,6 +102,7 @@ export function useImport() {
       isLoading: false,
       error: null,
       transactions: [],
+      predictions: [],
       duplicates: 0,
       errors: [],
       importResult: null,
@@ -33,13 +33,14 @@ export function useTransactions() {
 
   const updateCategory = useCallback(async (transactionId: string, categoryId: string | null) => {
     try {
-      await tauri.updateTransactionCategory(transactionId, categoryId);
+      const suggestion = await tauri.updateTransactionCategory(transactionId, categoryId);
       // Update local state
       setTransactions(
         transactions.map((t) =>
           t.id === transactionId ? { ...t, category_id: categoryId } : t
         )
       );
+      return suggestion;
     } catch (err) {
       throw err;
     }
@@ -1,6 +1,6 @@
 import type { Category, Provider } from '../types';
 
-export const DEFAULT_CATEGORIES: Omit<Category, 'id' | 'user_id' | 'created_at' | 'updated_at'>[] = [
+export const DEFAULT_CATEGORIES: Pick<Category, 'name' | 'icon' | 'color' | 'is_system'>[] = [
   { name: 'Food & Dining', icon: 'utensils', color: '#ef4444', is_system: true },
   { name: 'Food Delivery', icon: 'bike', color: '#f97316', is_system: true },
   { name: 'Transportation', icon: 'car', color: '#eab308', is_system: true },
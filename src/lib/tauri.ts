@@ -2,29 +2,75 @@ import { invoke } from '@tauri-apps/api/core';
 import type {
   TransactionWithCategory,
   TransactionFilters,
+  TransactionSearchHit,
+  TransactionPage,
+  TransactionPageParams,
+  TransactionDetail,
+  RecategorizationChange,
+  ItemSearchResult,
   ParsedTransaction,
+  ImportPreview,
   ImportResult,
+  OcrImportPreview,
+  CategoryPrediction,
   Category,
   CategorySpending,
+  CategoryMergeResult,
+  MerchantCategoryRule,
+  RuleInput,
+  RuleSuggestion,
+  RuleMatchPreview,
+  TrainingSummary,
+  KnowledgeBaseInfo,
+  MerchantPatternOverride,
   BudgetWithProgress,
   Budget,
+  Account,
+  AccountType,
+  AccountSpending,
+  Merchant,
+  MerchantAlias,
+  MerchantAliasMatchType,
+  MerchantTotal,
+  TransactionDirection,
+  RecurringIncome,
+  RecurringIncomeInput,
+  CashFlowPeriod,
+  CashFlowEntry,
   GmailConnectionStatus,
   SenderFilter,
   GmailSyncResult,
+  DatabaseStatus,
+  BackupInfo,
+  BackupManifest,
+  BackupSchedule,
+  ExportFormat,
+  ExportSummary,
+  DatasetImportResult,
+  RuleSetImportResult,
+  AccountingFormat,
+  AccountMapping,
+  ColumnMapping,
+  StatementPreset,
+  StatementImportResult,
+  Reconciliation,
 } from '../types';
 
 // Import commands
 export async function importReceipts(
-  htmlContents: string[]
-): Promise<{ transactions: ParsedTransaction[]; duplicates: number; errors: string[] }> {
-  return invoke('import_receipts', { htmlContents });
+  htmlContents: string[],
+  fileNames?: string[]
+): Promise<ImportPreview> {
+  return invoke('import_receipts', { htmlContents, fileNames });
 }
 
+// Transactions without an assignment are filed under their previewed prediction
 export async function confirmImport(
   transactions: ParsedTransaction[],
-  categoryAssignments: Record<number, string>
+  categoryAssignments: Record<number, string>,
+  predictions?: CategoryPrediction[]
 ): Promise<ImportResult> {
-  return invoke('confirm_import', { transactions, categoryAssignments });
+  return invoke('confirm_import', { transactions, categoryAssignments, predictions });
 }
 
 // Transaction commands
@@ -34,13 +80,78 @@ export async function getTransactions(
   return invoke('get_transactions', { filters });
 }
 
+export async function getTransactionsPage(
+  filters?: TransactionFilters,
+  page?: TransactionPageParams
+): Promise<TransactionPage> {
+  return invoke('get_transactions_page', { filters, page });
+}
+
+export async function searchTransactions(
+  query: string,
+  filters?: TransactionFilters,
+  limit?: number
+): Promise<TransactionSearchHit[]> {
+  return invoke('search_transactions', { query, filters, limit });
+}
+
+export async function getTransactionDetail(
+  transactionId: string
+): Promise<TransactionDetail> {
+  return invoke('get_transaction_detail', { transactionId });
+}
+
+export async function recategorizeTransactionItems(
+  transactionId: string
+): Promise<TransactionDetail> {
+  return invoke('recategorize_transaction_items', { transactionId });
+}
+
+export async function searchItems(
+  query: string,
+  filters?: TransactionFilters
+): Promise<ItemSearchResult> {
+  return invoke('search_items', { query, filters });
+}
+
+// Resolves to a rule suggestion once the merchant has been recategorized consistently
 export async function updateTransactionCategory(
   transactionId: string,
   categoryId: string | null
-): Promise<void> {
+): Promise<RuleSuggestion | null> {
   return invoke('update_transaction_category', { transactionId, categoryId });
 }
 
+export async function previewRecategorization(
+  filters?: TransactionFilters,
+  includeManual?: boolean
+): Promise<RecategorizationChange[]> {
+  return invoke('preview_recategorization', { filters, includeManual });
+}
+
+// Pass the accepted transaction IDs from the preview to apply only those
+export async function applyRecategorization(
+  filters?: TransactionFilters,
+  includeManual?: boolean,
+  transactionIds?: string[]
+): Promise<RecategorizationChange[]> {
+  return invoke('apply_recategorization', { filters, includeManual, transactionIds });
+}
+
+export async function updateTransactionAccount(
+  transactionId: string,
+  accountId: string | null
+): Promise<void> {
+  return invoke('update_transaction_account', { transactionId, accountId });
+}
+
+export async function updateTransactionDirection(
+  transactionId: string,
+  direction: TransactionDirection
+): Promise<void> {
+  return invoke('update_transaction_direction', { transactionId, direction });
+}
+
 export async function deleteTransaction(transactionId: string): Promise<void> {
   return invoke('delete_transaction', { transactionId });
 }
@@ -53,9 +164,10 @@ export async function getCategories(): Promise<Category[]> {
 export async function createCategory(
   name: string,
   icon: string,
-  color: string
+  color: string,
+  parentId?: string | null
 ): Promise<Category> {
-  return invoke('create_category', { name, icon, color });
+  return invoke('create_category', { name, icon, color, parentId });
 }
 
 export async function updateCategory(
@@ -67,10 +179,35 @@ export async function updateCategory(
   return invoke('update_category', { categoryId, name, icon, color });
 }
 
+export async function setCategoryParent(
+  categoryId: string,
+  parentId: string | null
+): Promise<void> {
+  return invoke('set_category_parent', { categoryId, parentId });
+}
+
+export async function setCategoryHidden(categoryId: string, hidden: boolean): Promise<void> {
+  return invoke('set_category_hidden', { categoryId, hidden });
+}
+
 export async function deleteCategory(categoryId: string): Promise<void> {
   return invoke('delete_category', { categoryId });
 }
 
+export async function deleteCategoryWithReassign(
+  categoryId: string,
+  targetCategoryId: string
+): Promise<CategoryMergeResult> {
+  return invoke('delete_category_with_reassign', { categoryId, targetCategoryId });
+}
+
+export async function mergeCategories(
+  sourceIds: string[],
+  targetId: string
+): Promise<CategoryMergeResult> {
+  return invoke('merge_categories', { sourceIds, targetId });
+}
+
 export async function getCategorySpending(
   startDate: string,
   endDate: string
@@ -95,15 +232,136 @@ export async function deleteBudget(budgetId: string): Promise<void> {
   return invoke('delete_budget', { budgetId });
 }
 
+// Account commands
+export async function getAccounts(): Promise<Account[]> {
+  return invoke('get_accounts');
+}
+
+export async function createAccount(
+  name: string,
+  accountType: AccountType,
+  institution?: string,
+  last4?: string
+): Promise<Account> {
+  return invoke('create_account', { name, accountType, institution, last4 });
+}
+
+export async function updateAccount(
+  accountId: string,
+  name: string,
+  accountType: AccountType,
+  institution?: string,
+  last4?: string
+): Promise<void> {
+  return invoke('update_account', { accountId, name, accountType, institution, last4 });
+}
+
+export async function deleteAccount(accountId: string): Promise<void> {
+  return invoke('delete_account', { accountId });
+}
+
+export async function getAccountSpending(
+  startDate: string,
+  endDate: string
+): Promise<AccountSpending[]> {
+  return invoke('get_account_spending', { startDate, endDate });
+}
+
+// Income commands
+export async function addIncome(
+  source: string,
+  amount: number,
+  date: string,
+  categoryId?: string,
+  accountId?: string
+): Promise<string> {
+  return invoke('add_income', { source, amount, date, categoryId, accountId });
+}
+
+export async function getRecurringIncomes(): Promise<RecurringIncome[]> {
+  return invoke('get_recurring_incomes');
+}
+
+export async function createRecurringIncome(
+  input: RecurringIncomeInput
+): Promise<RecurringIncome> {
+  return invoke('create_recurring_income', { input });
+}
+
+export async function updateRecurringIncome(
+  recurringIncomeId: string,
+  input: RecurringIncomeInput
+): Promise<void> {
+  return invoke('update_recurring_income', { recurringIncomeId, input });
+}
+
+export async function deleteRecurringIncome(recurringIncomeId: string): Promise<void> {
+  return invoke('delete_recurring_income', { recurringIncomeId });
+}
+
+export async function getCashFlow(
+  startDate: string,
+  endDate: string,
+  period?: CashFlowPeriod
+): Promise<CashFlowEntry[]> {
+  return invoke('get_cash_flow', { startDate, endDate, period });
+}
+
+// Merchant commands
+export async function getMerchants(): Promise<Merchant[]> {
+  return invoke('get_merchants');
+}
+
+export async function getMerchantAliases(merchantId: string): Promise<MerchantAlias[]> {
+  return invoke('get_merchant_aliases', { merchantId });
+}
+
+export async function updateMerchant(
+  merchantId: string,
+  name: string,
+  defaultCategoryId?: string | null,
+  icon?: string | null,
+  logoUrl?: string | null
+): Promise<void> {
+  return invoke('update_merchant', { merchantId, name, defaultCategoryId, icon, logoUrl });
+}
+
+export async function addMerchantAlias(
+  merchantId: string,
+  pattern: string,
+  matchType: MerchantAliasMatchType
+): Promise<MerchantAlias> {
+  return invoke('add_merchant_alias', { merchantId, pattern, matchType });
+}
+
+export async function removeMerchantAlias(aliasId: string): Promise<void> {
+  return invoke('remove_merchant_alias', { aliasId });
+}
+
+export async function mergeMerchants(sourceIds: string[], targetId: string): Promise<number> {
+  return invoke('merge_merchants', { sourceIds, targetId });
+}
+
+export async function splitMerchant(
+  merchantId: string,
+  transactionIds: string[],
+  newName: string
+): Promise<Merchant> {
+  return invoke('split_merchant', { merchantId, transactionIds, newName });
+}
+
 // Dashboard commands
 export interface DashboardStats {
   total_spent: number;
+  total_income: number;
+  savings_rate: number | null; // share of income not spent, null without income
   transaction_count: number;
   category_count: number;
   budget_health: 'good' | 'warning' | 'over';
   category_spending: CategorySpending[];
   recent_transactions: TransactionWithCategory[];
-  top_merchants: { merchant: string; total: number; count: number }[];
+  top_merchants: MerchantTotal[];
+  account_spending: AccountSpending[];
 }
 
 export async function getDashboardStats(
@@ -123,6 +381,158 @@ export async function initializeDatabase(): Promise<void> {
   return invoke('initialize_database');
 }
 
+export async function getDatabaseStatus(): Promise<DatabaseStatus> {
+  return invoke('get_database_status');
+}
+
+export async function unlockDatabase(passphrase: string): Promise<void> {
+  return invoke('unlock_database', { passphrase });
+}
+
+export async function lockDatabase(): Promise<void> {
+  return invoke('lock_database');
+}
+
+export async function enableDatabaseEncryption(passphrase: string): Promise<void> {
+  return invoke('enable_database_encryption', { passphrase });
+}
+
+export async function disableDatabaseEncryption(passphrase: string): Promise<void> {
+  return invoke('disable_database_encryption', { passphrase });
+}
+
+// Export commands
+export async function exportTransactions(
+  path: string,
+  format: ExportFormat,
+  filters?: TransactionFilters
+): Promise<ExportSummary> {
+  return invoke('export_transactions', { path, format, filters });
+}
+
+export async function exportItems(
+  path: string,
+  format: ExportFormat,
+  filters?: TransactionFilters
+): Promise<ExportSummary> {
+  return invoke('export_items', { path, format, filters });
+}
+
+export async function exportBudgets(
+  path: string,
+  format: ExportFormat
+): Promise<ExportSummary> {
+  return invoke('export_budgets', { path, format });
+}
+
+export async function exportDataset(path: string): Promise<ExportSummary> {
+  return invoke('export_dataset', { path });
+}
+
+export async function importDataset(path: string): Promise<DatasetImportResult> {
+  return invoke('import_dataset', { path });
+}
+
+export async function exportRules(path: string): Promise<ExportSummary> {
+  return invoke('export_rules', { path });
+}
+
+export async function importRules(path: string): Promise<RuleSetImportResult> {
+  return invoke('import_rules', { path });
+}
+
+export async function exportAccounting(
+  path: string,
+  format: AccountingFormat,
+  filters?: TransactionFilters
+): Promise<ExportSummary> {
+  return invoke('export_accounting', { path, format, filters });
+}
+
+export async function getAccountMapping(): Promise<AccountMapping> {
+  return invoke('get_account_mapping');
+}
+
+export async function setAccountMapping(mapping: AccountMapping): Promise<void> {
+  return invoke('set_account_mapping', { mapping });
+}
+
+// Backup commands
+export async function createBackup(
+  destination?: string,
+  compress?: boolean
+): Promise<BackupInfo> {
+  return invoke('create_backup', { destination, compress });
+}
+
+export async function inspectBackup(path: string): Promise<BackupManifest> {
+  return invoke('inspect_backup', { path });
+}
+
+export async function restoreBackup(
+  path: string,
+  passphrase?: string
+): Promise<BackupManifest> {
+  return invoke('restore_backup', { path, passphrase });
+}
+
+export async function listBackups(): Promise<BackupInfo[]> {
+  return invoke('list_backups');
+}
+
+export async function getBackupSchedule(): Promise<BackupSchedule> {
+  return invoke('get_backup_schedule');
+}
+
+export async function setBackupSchedule(schedule: BackupSchedule): Promise<void> {
+  return invoke('set_backup_schedule', { schedule });
+}
+
+// Statement commands
+export async function listStatementPresets(): Promise<StatementPreset[]> {
+  return invoke('list_statement_presets');
+}
+
+export async function importStatementCsv(
+  csvContent: string,
+  options: { preset?: string; mapping?: ColumnMapping; accountLabel?: string } = {}
+): Promise<StatementImportResult> {
+  return invoke('import_statement_csv', { csvContent, ...options });
+}
+
+export async function autoMatchStatements(): Promise<number> {
+  return invoke('auto_match_statements');
+}
+
+export async function getReconciliation(
+  startDate?: string,
+  endDate?: string
+): Promise<Reconciliation> {
+  return invoke('get_reconciliation', { startDate, endDate });
+}
+
+export async function confirmStatementMatch(statementTransactionId: string): Promise<void> {
+  return invoke('confirm_statement_match', { statementTransactionId });
+}
+
+export async function setStatementMatch(
+  statementTransactionId: string,
+  transactionId: string | null
+): Promise<void> {
+  return invoke('set_statement_match', { statementTransactionId, transactionId });
+}
+
+export async function resetStatementMatch(statementTransactionId: string): Promise<void> {
+  return invoke('reset_statement_match', { statementTransactionId });
+}
+
+export async function createTransactionFromStatement(
+  statementTransactionId: string,
+  categoryId?: string
+): Promise<string> {
+  return invoke('create_transaction_from_statement', { statementTransactionId, categoryId });
+}
+
 export async function setMerchantCategoryRule(
   merchantPattern: string,
   categoryId: string,
@@ -135,14 +545,80 @@ export async function setMerchantCategoryRule(
   });
 }
 
+export async function createCategoryRule(rule: RuleInput): Promise<MerchantCategoryRule> {
+  return invoke('create_category_rule', { rule });
+}
+
+export async function getCategoryRules(): Promise<MerchantCategoryRule[]> {
+  return invoke('get_category_rules');
+}
+
+export async function updateCategoryRule(ruleId: string, rule: RuleInput): Promise<MerchantCategoryRule> {
+  return invoke('update_category_rule', { ruleId, rule });
+}
+
+export async function deleteCategoryRule(ruleId: string): Promise<void> {
+  return invoke('delete_category_rule', { ruleId });
+}
+
+export async function testCategoryRule(rule: RuleInput, limit?: number): Promise<RuleMatchPreview> {
+  return invoke('test_category_rule', { rule, limit });
+}
+
+// Retrain the local classifier; it also retrains on every launch
+export async function trainClassifier(): Promise<TrainingSummary> {
+  return invoke('train_classifier');
+}
+
+export async function getMerchantKnowledgeBaseInfo(): Promise<KnowledgeBaseInfo> {
+  return invoke('get_merchant_knowledge_base_info');
+}
+
+export async function getMerchantPatternOverrides(): Promise<MerchantPatternOverride[]> {
+  return invoke('get_merchant_pattern_overrides');
+}
+
+// Pass null to disable the bundled pattern
+export async function setMerchantPatternOverride(
+  pattern: string,
+  categoryId: string | null
+): Promise<void> {
+  return invoke('set_merchant_pattern_override', { pattern, categoryId });
+}
+
+export async function deleteMerchantPatternOverride(overrideId: string): Promise<void> {
+  return invoke('delete_merchant_pattern_override', { overrideId });
+}
+
+export async function getRuleSuggestions(): Promise<RuleSuggestion[]> {
+  return invoke('get_rule_suggestions');
+}
+
+export async function acceptRuleSuggestion(suggestionId: string): Promise<MerchantCategoryRule> {
+  return invoke('accept_rule_suggestion', { suggestionId });
+}
+
+export async function rejectRuleSuggestion(suggestionId: string): Promise<void> {
+  return invoke('reject_rule_suggestion', { suggestionId });
+}
+
 // OCR commands
 export async function importReceiptFromOcr(
   ocrText: string,
   confidence: number
-): Promise<ParsedTransaction> {
+): Promise<OcrImportPreview> {
   return invoke('import_receipt_from_ocr', { ocrText, confidence });
 }
 
+// Omit categoryId to keep the predicted category
+export async function confirmOcrImport(
+  transaction: ParsedTransaction,
+  categoryId?: string,
+  prediction?: CategoryPrediction
+): Promise<ImportResult> {
+  return invoke('confirm_ocr_import', { transaction, categoryId, prediction });
+}
+
 export async function captureReceiptPhoto(
   source: 'camera' | 'library'
 ): Promise<{ text: string; confidence: number }> {
@@ -17,6 +17,7 @@ export function Import() {
     isLoading,
     error,
     transactions,
+    predictions,
     duplicates,
     errors,
     importResult,
@@ -138,6 +139,7 @@ export function Import() {
               errors={errors}
               categories={categories}
               categoryAssignments={categoryAssignments}
+              predictions={predictions}
               onCategoryChange={setCategoryAssignment}
               onConfirm={confirmImport}
               onCancel={reset}
@@ -26,6 +26,7 @@ export function MobileImport() {
     isLoading,
     error: importError,
     transactions,
+    predictions,
     duplicates,
     errors,
     importResult,
@@ -39,9 +40,13 @@ export function MobileImport() {
   const {
     isCapturing,
     isProcessing,
+    isSaving,
     error: cameraError,
     transaction: ocrTransaction,
+    preview: ocrPreview,
+    importResult: ocrImportResult,
     captureReceipt,
+    confirmReceipt,
     reset: resetCamera,
   } = useCamera();
 
@@ -51,9 +56,11 @@ export function MobileImport() {
   const error = importError || cameraError;
 
   // When OCR produces a transaction, show it in the same preview flow
-  const showOcrPreview = ocrTransaction && step === 'select';
+  const showOcrPreview = ocrTransaction && step === 'select' && !ocrImportResult;
   const previewTransactions = showOcrPreview ? [ocrTransaction] : transactions;
-  const currentStep = showOcrPreview ? 'preview' : step;
+  const previewPredictions = showOcrPreview && ocrPreview ? [ocrPreview.prediction] : predictions;
+  const doneResult = ocrImportResult ?? importResult;
+  const currentStep = ocrImportResult ? 'done' : showOcrPreview ? 'preview' : step;
 
   const handleReset = () => {
     resetImport();
@@ -149,26 +156,27 @@ export function MobileImport() {
         {currentStep === 'preview' && (
           <ParsePreview
             transactions={previewTransactions}
-            duplicates={showOcrPreview ? 0 : duplicates}
+            duplicates={showOcrPreview ? (ocrPreview?.is_duplicate ? 1 : 0) : duplicates}
             errors={showOcrPreview ? [] : errors}
             categories={categories}
             categoryAssignments={categoryAssignments}
+            predictions={previewPredictions}
             onCategoryChange={setCategoryAssignment}
-            onConfirm={confirmImport}
+            onConfirm={showOcrPreview ? () => confirmReceipt(categoryAssignments[0]) : confirmImport}
             onCancel={handleReset}
-            isLoading={isLoading}
+            isLoading={showOcrPreview ? isSaving : isLoading}
           />
         )}
 
-        {currentStep === 'done' && importResult && (
+        {currentStep === 'done' && doneResult && (
           <div className="text-center py-8">
             <CheckCircle className="h-14 w-14 text-success mx-auto mb-4" />
             <h2 className="text-xl font-semibold mb-2">Import Complete!</h2>
             <p className="text-sm text-muted-foreground mb-6">
-              Successfully imported {importResult.imported} transaction
-              {importResult.imported !== 1 ? 's' : ''}.
-              {importResult.skipped > 0 && (
-                <> Skipped {importResult.skipped} duplicate{importResult.skipped !== 1 ? 's' : ''}.</>
+              Successfully imported {doneResult.imported} transaction
+              {doneResult.imported !== 1 ? 's' : ''}.
+              {doneResult.skipped > 0 && (
+                <> Skipped {doneResult.skipped} duplicate{doneResult.skipped !== 1 ? 's' : ''}.</>
               )}
             </p>
 
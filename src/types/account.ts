@@ -0,0 +1,21 @@
+export type AccountType = 'credit_card' | 'debit_card' | 'bank' | 'wallet' | 'cash' | 'other';
+
+export interface Account {
+  id: string;
+  user_id: string;
+  name: string;
+  account_type: AccountType;
+  institution: string | null; // card network or wallet, e.g. "Visa", "Venmo"
+  last4: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface AccountSpending {
+  account_id: string | null; // null for transactions with no account
+  account_name: string | null;
+  account_type: AccountType | null;
+  last4: string | null;
+  total: number; // cents
+  transaction_count: number;
+}
@@ -0,0 +1,35 @@
+export interface BackupCounts {
+  transactions: number;
+  transaction_items: number;
+  categories: number;
+  budgets: number;
+  merchant_category_rules: number;
+}
+
+export interface BackupManifest {
+  format_version: number;
+  app_version: string;
+  schema_version: number;
+  created_at: string;
+  encrypted: boolean;
+  compressed: boolean;
+  database_sha256: string;
+  counts: BackupCounts;
+}
+
+export interface BackupInfo {
+  path: string;
+  file_name: string;
+  file_size: number;
+  is_automatic: boolean;
+  manifest: BackupManifest;
+}
+
+export interface BackupSchedule {
+  enabled: boolean;
+  interval_hours: number;
+  keep: number;
+}
+
+/** Error returned by restoreBackup when the backup is encrypted and no passphrase was given */
+export const PASSPHRASE_REQUIRED = 'PASSPHRASE_REQUIRED';
@@ -1,29 +1,114 @@
+import type { TransactionWithCategory } from './transaction';
+
 export interface Category {
   id: string;
   user_id: string;
   name: string;
   icon: string;
   color: string;
+  parent_id: string | null; // spending and budgets roll up into the parent
   is_system: boolean;
+  system_key: string | null; // seeded name, kept when a system category is renamed
+  is_hidden: boolean; // hidden from pickers and automatic categorization
   created_at: string;
   updated_at: string;
 }
 
+export type RuleMatchType = 'exact' | 'contains' | 'glob' | 'regex';
+
+// Every condition that is set must hold; rules are tried in ascending priority
 export interface MerchantCategoryRule {
   id: string;
   user_id: string;
-  merchant_pattern: string;
+  name: string | null;
+  merchant_pattern: string | null;
+  match_type: RuleMatchType;
+  provider: string | null;
+  min_amount: number | null; // cents, inclusive
+  max_amount: number | null; // cents, exclusive
+  days_of_week: string | null; // comma-separated, 1 = Monday .. 7 = Sunday
+  item_keyword: string | null;
+  sender: string | null;
   category_id: string;
+  priority: number;
+  enabled: boolean;
   is_exact_match: boolean;
   created_at: string;
+  updated_at: string;
+}
+
+// Past transactions a rule would match, ignoring rule order
+export interface RuleMatchPreview {
+  match_count: number;
+  transactions: TransactionWithCategory[]; // most recent first, up to the limit
+}
+
+export interface RuleInput {
+  name?: string | null;
+  merchant_pattern?: string | null;
+  match_type?: RuleMatchType;
+  provider?: string | null;
+  min_amount?: number | null;
+  max_amount?: number | null;
+  days_of_week?: string | null;
+  item_keyword?: string | null;
+  sender?: string | null;
+  category_id: string;
+  priority?: number | null; // omitted places the rule after all others
+  enabled?: boolean;
+}
+
+// Merchant rule proposed after repeated consistent manual recategorizations
+export interface RuleSuggestion {
+  id: string;
+  user_id: string;
+  merchant_normalized: string;
+  category_id: string;
+  category_name: string | null;
+  assignment_count: number;
+  status: 'pending' | 'accepted' | 'rejected';
+  rule_id: string | null; // set once accepted
+  created_at: string;
+  updated_at: string;
+}
+
+export interface KnowledgeBaseInfo {
+  version: number;
+  merchants: number;
+  keywords: number;
+}
+
+// The user's category for a bundled merchant pattern
+export interface MerchantPatternOverride {
+  id: string;
+  user_id: string;
+  pattern: string; // normalized merchant words
+  category_id: string | null; // null disables the bundled entry
+  created_at: string;
+  updated_at: string;
+}
+
+export interface TrainingSummary {
+  transactions: number;
+  categories: number;
+  vocabulary: number; // distinct tokens
+}
+
+export interface CategoryMergeResult {
+  transactions_moved: number;
+  rules_moved: number;
+  budgets_moved: number;
+  categories_deleted: number;
 }
 
 export interface CategorySpending {
   category_id: string;
+  parent_id: string | null;
   category_name: string;
   category_color: string;
   category_icon: string;
-  total: number; // cents
+  total: number; // cents, including subcategories
   transaction_count: number;
   percentage: number; // 0.0 to 1.0
+  children: CategorySpending[]; // subcategories with spending, largest first
 }
@@ -0,0 +1,10 @@
+export interface DatabaseStatus {
+  is_encrypted: boolean;
+  is_locked: boolean;
+}
+
+/** Error returned by every database command while an encrypted database is locked */
+export const DATABASE_LOCKED = 'DATABASE_LOCKED';
+
+/** Error returned by unlock/disable when the passphrase doesn't match */
+export const WRONG_PASSPHRASE = 'WRONG_PASSPHRASE';
@@ -0,0 +1,29 @@
+export type ExportFormat = 'csv' | 'json';
+
+export interface ExportSummary {
+  path: string;
+  row_count: number;
+}
+
+export interface DatasetImportResult {
+  categories_created: number;
+  categories_updated: number;
+  merchant_category_rules: number;
+  budgets: number;
+  sender_filters: number;
+}
+
+export interface RuleSetImportResult {
+  imported: number; // created, or existing rules with the same conditions updated
+  skipped: string[]; // reason for each rule that couldn't be imported
+}
+
+export type AccountingFormat = 'ofx' | 'qif' | 'ledger' | 'beancount';
+
+export interface AccountMapping {
+  funding_account: string;
+  uncategorized_account: string;
+  currency: string;
+  /** Category ID -> account, e.g. "Expenses:Food-Dining" */
+  categories: Record<string, string>;
+}
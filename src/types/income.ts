@@ -0,0 +1,36 @@
+export type IncomeFrequency = 'weekly' | 'biweekly' | 'semimonthly' | 'monthly';
+
+export interface RecurringIncome {
+  id: string;
+  user_id: string;
+  name: string;
+  amount: number; // cents per occurrence
+  frequency: IncomeFrequency;
+  start_date: string; // YYYY-MM-DD
+  next_date: string; // next occurrence not yet recorded
+  category_id: string | null;
+  account_id: string | null;
+  enabled: boolean;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface RecurringIncomeInput {
+  name: string;
+  amount: number; // cents
+  frequency: IncomeFrequency;
+  start_date: string; // past dates are backfilled
+  category_id: string | null;
+  account_id: string | null;
+  enabled?: boolean;
+}
+
+export type CashFlowPeriod = 'weekly' | 'monthly' | 'yearly';
+
+export interface CashFlowEntry {
+  period_start: string; // YYYY-MM-DD, weeks start on Monday
+  income: number; // cents
+  expenses: number; // cents
+  net: number; // cents
+  savings_rate: number | null; // 0.0 to 1.0, null without income
+}
@@ -1,5 +1,12 @@
 export * from './transaction';
 export * from './category';
+export * from './account';
+export * from './merchant';
 export * from './budget';
 export * from './provider';
 export * from './gmail';
+export * from './database';
+export * from './backup';
+export * from './export';
+export * from './statement';
+export * from './income';
@@ -0,0 +1,29 @@
+export type MerchantAliasMatchType = 'exact' | 'prefix' | 'contains';
+
+export interface Merchant {
+  id: string;
+  user_id: string;
+  name: string; // canonical display name
+  name_normalized: string;
+  default_category_id: string | null;
+  icon: string | null;
+  logo_url: string | null;
+  transaction_count: number;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface MerchantAlias {
+  id: string;
+  merchant_id: string;
+  pattern: string; // normalized merchant text
+  match_type: MerchantAliasMatchType;
+  created_at: string;
+}
+
+export interface MerchantTotal {
+  merchant_id: string | null; // null for transactions not yet linked to a merchant
+  merchant: string;
+  total: number; // cents
+  count: number;
+}
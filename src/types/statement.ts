@@ -0,0 +1,59 @@
+import type { TransactionWithCategory } from './transaction';
+
+/** How columns of a statement CSV map to statement rows */
+export interface ColumnMapping {
+  date_column: string;
+  description_column: string;
+  /** Single signed amount column */
+  amount_column: string | null;
+  /** Separate unsigned debit/credit columns, used when there's no amount column */
+  debit_column: string | null;
+  credit_column: string | null;
+  /** chrono format, e.g. "%m/%d/%Y" */
+  date_format: string;
+  /** True when the bank writes purchases as negative amounts */
+  spending_is_negative: boolean;
+}
+
+export interface StatementPreset {
+  id: string;
+  name: string;
+  mapping: ColumnMapping;
+}
+
+export type StatementMatchStatus = 'unmatched' | 'auto' | 'confirmed' | 'no_match';
+
+export interface StatementTransaction {
+  id: string;
+  import_id: string;
+  account_label: string | null;
+  posted_date: string;
+  description: string;
+  description_normalized: string;
+  /** Cents, positive for spending */
+  amount: number;
+  matched_transaction_id: string | null;
+  match_status: StatementMatchStatus;
+  match_score: number | null;
+  created_at: string;
+}
+
+export interface StatementImportResult {
+  import_id: string;
+  imported: number;
+  duplicates: number;
+  errors: string[];
+  detected_preset: string | null;
+  auto_matched: number;
+}
+
+export interface StatementMatch {
+  statement: StatementTransaction;
+  transaction: TransactionWithCategory;
+}
+
+export interface Reconciliation {
+  matched: StatementMatch[];
+  unmatched_statement: StatementTransaction[];
+  unmatched_receipts: TransactionWithCategory[];
+}
@@ -1,3 +1,5 @@
+import type { AccountType } from './account';
+
 export interface Transaction {
   id: string;
   user_id: string;
@@ -13,6 +15,8 @@ export interface Transaction {
   updated_at: string;
 }
 
+export type TransactionDirection = 'expense' | 'income';
+
 export interface TransactionItem {
   id: string;
   transaction_id: string;
@@ -20,6 +24,86 @@ export interface TransactionItem {
   quantity: number;
   unit_price: number; // cents
   total_price: number; // cents
+  category_id: string | null; // set when the item belongs in a different category than the transaction
+  category_source: CategorySource | null;
+  category_reason: string | null;
+}
+
+// A share of a transaction counted toward one category, when its items span several
+export interface TransactionSplit {
+  id: string;
+  transaction_id: string;
+  category_id: string | null;
+  category_name: string | null;
+  amount: number; // cents
+}
+
+export interface TransactionFee {
+  id: string;
+  transaction_id: string;
+  kind: FeeKind;
+  label: string;
+  amount: number; // cents, negative for discounts
+}
+
+export type FeeKind = 'tax' | 'tip' | 'delivery_fee' | 'service_fee' | 'discount' | 'other';
+
+export type CategorySource =
+  | 'user_rule'
+  | 'merchant_default'
+  | 'previous_transaction'
+  | 'merchant_pattern'
+  | 'classifier'
+  | 'provider_default'
+  | 'manual'
+  | 'uncategorized';
+
+export interface TransactionDetail {
+  transaction: TransactionWithCategory;
+  items: TransactionItem[];
+  fees: TransactionFee[];
+  splits: TransactionSplit[]; // empty unless the items span several categories
+  source_type: 'gmail' | 'import' | 'ocr' | 'statement' | 'manual' | 'recurring' | null;
+  source_ref: string | null; // Gmail message ID or file name
+  receipt_text: string | null;
+  category_source: CategorySource | null;
+  category_reason: string | null;
+  category_rule_id: string | null; // rule that assigned the category, while it still exists
+}
+
+// A transaction whose category changes when categorized again with the current rules
+export interface RecategorizationChange {
+  transaction_id: string;
+  merchant: string;
+  transaction_date: string;
+  amount: number; // cents
+  old_category_id: string | null;
+  old_category_name: string | null;
+  old_source: CategorySource | null;
+  new_category_id: string | null;
+  new_category_name: string | null;
+  new_source: CategorySource;
+  new_reason: string;
+  new_rule_id: string | null;
+}
+
+export interface ItemMatch {
+  item_id: string;
+  transaction_id: string;
+  name: string;
+  quantity: number;
+  unit_price: number; // cents
+  total_price: number; // cents
+  merchant: string;
+  provider: string;
+  transaction_date: string;
+}
+
+export interface ItemSearchResult {
+  items: ItemMatch[];
+  total_spent: number; // cents
+  total_quantity: number;
+  transaction_count: number;
 }
 
 export interface ParsedTransaction {
@@ -27,11 +111,27 @@ export interface ParsedTransaction {
   amount: number; // cents
   transaction_date: string; // YYYY-MM-DD
   provider: string;
+  is_income?: boolean; // money received, e.g. a Venmo payment from someone
   items: ParsedItem[];
+  fees?: ParsedFee[];
+  payment_method?: ParsedPaymentMethod;
+  source_ref?: string;
   raw_text?: string;
   confidence: number; // 0.0 to 1.0
 }
 
+export interface ParsedPaymentMethod {
+  institution: string | null;
+  account_type: AccountType;
+  last4: string | null;
+}
+
+export interface ParsedFee {
+  kind: FeeKind;
+  label: string;
+  amount: number; // cents, negative for discounts
+}
+
 export interface ParsedItem {
   name: string;
   quantity: number;
@@ -43,24 +143,81 @@ export interface TransactionWithCategory extends Transaction {
   category_name: string | null;
   category_color: string | null;
   category_icon: string | null;
+  account_id: string | null;
+  account_name: string | null;
+  direction: TransactionDirection;
+  merchant_id: string | null;
+}
+
+export interface TransactionSearchHit {
+  transaction: TransactionWithCategory;
+  snippet: string; // HTML-escaped text with matched terms wrapped in <mark> tags
+  rank: number; // BM25 score, lower is more relevant
+}
+
+export type TransactionSortKey = 'date' | 'amount' | 'merchant' | 'category';
+
+export interface TransactionPageParams {
+  sortBy?: TransactionSortKey;
+  sortDirection?: 'asc' | 'desc';
+  cursor?: string | null;
+  limit?: number;
+}
+
+export interface TransactionPage {
+  transactions: TransactionWithCategory[];
+  next_cursor: string | null;
+  total_count: number;
+  total_amount: number; // cents, across all pages
 }
 
 export interface TransactionFilters {
   search?: string;
   categoryId?: string | null;
   provider?: string | null;
+  accountId?: string | null;
+  direction?: TransactionDirection | null;
+  categorySource?: CategorySource | null;
   startDate?: string;
   endDate?: string;
   minAmount?: number;
   maxAmount?: number;
 }
 
+// A category the transaction could also belong in
+export interface CategoryAlternative {
+  category_id: string;
+  category_name: string | null;
+  source: CategorySource;
+  reason: string;
+  confidence: number | null;
+}
+
+// The category a parsed transaction will be filed under, with the runners-up
+export interface CategoryPrediction {
+  category_id: string | null;
+  category_name: string | null;
+  source: CategorySource;
+  reason: string;
+  rule_id: string | null;
+  confidence: number | null; // classifier probability, for 'classifier'
+  alternatives: CategoryAlternative[]; // best first
+}
+
 export interface ImportPreview {
   transactions: ParsedTransaction[];
+  predictions: CategoryPrediction[]; // by index into transactions
   duplicates: number;
   errors: string[];
 }
 
+// A receipt read by OCR, with the category it would be saved under
+export interface OcrImportPreview {
+  transaction: ParsedTransaction;
+  prediction: CategoryPrediction;
+  is_duplicate: boolean; // the same receipt was already imported
+}
+
 export interface ImportResult {
   imported: number;
   skipped: number;